@@ -0,0 +1,200 @@
+// Showing a ParseLineError the way rustc shows its errors, or as JSON for tools
+use colored::*;
+use serde::Serialize;
+
+use crate::ParseLineError;
+
+/// Lines shown before and after the one with the error.
+const CONTEXT_LINES: usize = 1;
+
+impl ParseLineError {
+    /// Last column of the value that failed to parse (inclusive, in characters).
+    fn end_column(&self) -> usize {
+        self.column + self.token.chars().count().max(1) - 1
+    }
+
+    /// Something the user can do about the error, guessed from what the value looks like.
+    fn help(&self) -> String {
+        let integer_target = self.expected.starts_with('i') || self.expected.starts_with('u');
+        if integer_target && self.token.parse::<i128>().is_ok() {
+            format!("value out of range for {}", self.expected)
+        } else if integer_target && self.token.parse::<f64>().is_ok() {
+            format!(
+                "{} cannot have a fractional part or exponent",
+                self.expected
+            )
+        } else if self.token.chars().any(|c| c.is_ascii_digit()) {
+            format!(
+                "expected a value of type {}, remove the characters that are not part of the number",
+                self.expected
+            )
+        } else {
+            format!(
+                "expected a value of type {}, comment the line out with `#` if it is not data",
+                self.expected
+            )
+        }
+    }
+}
+
+/// Render `error` found in `source` (the full text of `file_name`) with the lines around it,
+/// a caret under the value that failed and a hint, like:
+///
+/// ```text
+/// error: invalid digit found in string
+///  --> numbers.txt:8:6
+///   |
+/// 7 |
+/// 8 | 5, 6 x7 8
+///   |      ^^
+/// 9 | 99999999999
+///   |
+///   = help: expected a value of type i32, remove the characters that are not part of the number
+/// ```
+pub fn render(file_name: &str, source: &str, error: &ParseLineError) -> String {
+    let lines: Vec<&str> = source.lines().collect();
+    let first = error.line_number.saturating_sub(CONTEXT_LINES).max(1);
+    let last = (error.line_number + CONTEXT_LINES).min(lines.len().max(error.line_number));
+    let width = last.to_string().len();
+    let gutter = format!("{:width$} |", "", width = width).blue().bold();
+
+    let mut out = format!("{}: {}\n", "error".red().bold(), error.message.bold());
+    out += &format!(
+        "{:width$}{} {}:{}:{}\n",
+        "",
+        "-->".blue().bold(),
+        file_name,
+        error.line_number,
+        error.column,
+        width = width
+    );
+    out += &format!("{}\n", gutter);
+    for number in first..=last {
+        // the error line comes from the error itself, in case `source` is not what was parsed
+        let text = if number == error.line_number {
+            error.line.as_str()
+        } else {
+            lines.get(number - 1).copied().unwrap_or("")
+        };
+        let prefix = format!("{:>width$} |", number, width = width).blue().bold();
+        // no trailing spaces after the gutter of an empty line
+        out += format!("{} {}", prefix, text).trim_end();
+        out.push('\n');
+        if number == error.line_number {
+            let carets = "^".repeat(error.end_column() - error.column + 1);
+            out += &format!(
+                "{} {}{}\n",
+                gutter,
+                " ".repeat(error.column - 1),
+                carets.red().bold()
+            );
+        }
+    }
+    out += &format!("{}\n", gutter);
+    out += &format!(
+        "{:width$} {} {}: {}\n",
+        "",
+        "=".blue().bold(),
+        "help".bold(),
+        error.help(),
+        width = width
+    );
+    out
+}
+
+#[derive(Serialize)]
+struct JsonDiagnostic<'a> {
+    file: &'a str,
+    line: usize,
+    column: usize,
+    end_column: usize,
+    message: &'a str,
+    help: String,
+    source: &'a str,
+}
+
+/// One JSON object, on a single line, for tools to parse.
+pub fn to_json(file_name: &str, error: &ParseLineError) -> String {
+    let diagnostic = JsonDiagnostic {
+        file: file_name,
+        line: error.line_number,
+        column: error.column,
+        end_column: error.end_column(),
+        message: &error.message,
+        help: error.help(),
+        source: &error.line,
+    };
+    serde_json::to_string(&diagnostic).expect("diagnostics always serialize")
+}
+
+#[cfg(test)]
+fn bad_fixture_errors() -> Vec<ParseLineError> {
+    let mut reader = include_str!("numbers_bad.txt").as_bytes();
+    crate::parse_numbers::<i32>(&mut reader, crate::ParseMode::CollectErrors)
+        .unwrap()
+        .errors
+}
+
+#[test]
+fn test_render() {
+    colored::control::set_override(false);
+    let errors = bad_fixture_errors();
+    let source = include_str!("numbers_bad.txt");
+    let expected = "\
+error: invalid digit found in string
+ --> numbers_bad.txt:8:6
+  |
+7 |
+8 | 5, 6 x7 8   # 7 has a typo
+  |      ^^
+9 | 99999999999
+  |
+  = help: expected a value of type i32, remove the characters that are not part of the number
+";
+    assert_eq!(render("numbers_bad.txt", source, &errors[1]), expected);
+
+    let rendered = render("numbers_bad.txt", source, &errors[0]);
+    assert!(
+        rendered.contains("5 | a\n  | ^\n6 | # comments"),
+        "{}",
+        rendered
+    );
+    let rendered = render("numbers_bad.txt", source, &errors[2]);
+    assert!(rendered.ends_with("= help: value out of range for i32\n"));
+    assert!(rendered.contains(" 9 | 99999999999\n   | ^^^^^^^^^^^\n10 | -1\n"));
+}
+
+#[test]
+fn test_render_first_and_last_lines() {
+    colored::control::set_override(false);
+    let source = "x\n1";
+    let mut reader = source.as_bytes();
+    let report = crate::parse_numbers::<u8>(&mut reader, crate::ParseMode::FailFast).unwrap();
+    let rendered = render("<stdin>", source, &report.errors[0]);
+    assert!(
+        rendered.contains(" --> <stdin>:1:1\n  |\n1 | x\n  | ^\n2 | 1\n"),
+        "{}",
+        rendered
+    );
+
+    let source = "1\n2.5";
+    let mut reader = source.as_bytes();
+    let report = crate::parse_numbers::<u8>(&mut reader, crate::ParseMode::FailFast).unwrap();
+    let rendered = render("<stdin>", source, &report.errors[0]);
+    assert!(rendered.contains("2 | 2.5\n  | ^^^\n  |\n"), "{}", rendered);
+    assert!(rendered.contains("u8 cannot have a fractional part"));
+}
+
+#[test]
+fn test_to_json() {
+    let errors = bad_fixture_errors();
+    let json: serde_json::Value =
+        serde_json::from_str(&to_json("numbers_bad.txt", &errors[2])).unwrap();
+    assert_eq!(json["file"], "numbers_bad.txt");
+    assert_eq!(json["line"], 9);
+    assert_eq!(json["column"], 1);
+    assert_eq!(json["end_column"], 11);
+    assert_eq!(json["message"], "number too large to fit in target type");
+    assert_eq!(json["help"], "value out of range for i32");
+    assert_eq!(json["source"], "99999999999");
+}
@@ -1,55 +1,431 @@
+mod diagnostic;
+mod stats;
+
 use colored::*;
+use std::backtrace::{Backtrace, BacktraceStatus};
 use std::error;
-use std::io::{self, stdin, BufRead, BufReader};
+use std::fmt;
+use std::io::{self, stdin, BufRead, Read};
+use std::str::FromStr;
 use thiserror::Error;
 
-// Ability to convert any error
-type GenericError = Box<dyn error::Error>;
-type GenericResult<T> = Result<T, GenericError>;
-
-// Make it thread safe
+// Ability to convert any error, and send it to another thread
 type ThreadSafeGenericError = Box<dyn error::Error + Send + Sync + 'static>;
-type ThreadSafeGenericResult<T> = Result<T, ThreadSafeGenericError>;
 
 #[derive(Debug, Error)]
-#[error("'{message}' on line {line_number}: '{line}'")]
+#[error("'{message}' on line {line_number}, column {column}: '{line}'")]
 pub struct ParseLineError {
     message: String,
     line: String,
     line_number: usize,
+    // in characters, starting at 1
+    column: usize,
+    // the value that failed to parse, and the name of the type it should have been
+    token: String,
+    expected: &'static str,
 }
 
-fn read_numbers(file: &mut dyn BufRead) -> GenericResult<Vec<i32>> {
-    let mut numbers = vec![];
+// Every error the application can end with, `?` converts io and parse errors to it.
+#[derive(Debug, Error)]
+pub enum AppError {
+    #[error("could not read the input")]
+    Io(#[from] io::Error),
+    #[error("the input is not a list of numbers")]
+    Parse(#[from] ParseLineError),
+    #[error("invalid configuration: {0}")]
+    Config(String),
+    #[error("no numbers in the input")]
+    NoNumbers,
+    // whatever came back from another thread and is none of the above
+    #[error(transparent)]
+    Other(ThreadSafeGenericError),
+}
+
+type AppResult<T> = Result<T, AppError>;
+
+impl AppError {
+    /// Exit codes from BSD's sysexits.h, so scripts can tell the failures apart.
+    fn exit_code(&self) -> i32 {
+        match self {
+            AppError::Config(_) => 64,                      // EX_USAGE
+            AppError::Parse(_) | AppError::NoNumbers => 65, // EX_DATAERR
+            AppError::Io(_) => 74,                          // EX_IOERR
+            AppError::Other(_) => 1,
+        }
+    }
+}
+
+// Errors cross threads boxed as `ThreadSafeGenericError`, this gets the typed error back
+impl From<ThreadSafeGenericError> for AppError {
+    fn from(error: ThreadSafeGenericError) -> Self {
+        let error = match error.downcast::<AppError>() {
+            Ok(app_error) => return *app_error,
+            Err(error) => error,
+        };
+        let error = match error.downcast::<io::Error>() {
+            Ok(io_error) => return AppError::Io(*io_error),
+            Err(error) => error,
+        };
+        match error.downcast::<ParseLineError>() {
+            Ok(parse_error) => AppError::Parse(*parse_error),
+            Err(error) => AppError::Other(error),
+        }
+    }
+}
+
+// An AppError with the backtrace of where `?` turned it into a report.
+// `Backtrace::capture` only does the work when RUST_BACKTRACE or RUST_LIB_BACKTRACE is set.
+#[derive(Debug)]
+struct ErrorReport {
+    // boxed to keep `Result<_, ErrorReport>` small, as clippy asks
+    error: Box<AppError>,
+    backtrace: Backtrace,
+}
+
+impl<E: Into<AppError>> From<E> for ErrorReport {
+    fn from(error: E) -> Self {
+        ErrorReport {
+            error: Box::new(error.into()),
+            backtrace: Backtrace::capture(),
+        }
+    }
+}
+
+/// The error and everything that caused it, one per line, then the backtrace if there is one.
+impl fmt::Display for ErrorReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}: {}", "error".red().bold(), self.error)?;
+        let mut source = error::Error::source(&self.error);
+        while let Some(cause) = source {
+            writeln!(f, "  {}: {}", "caused by".yellow(), cause)?;
+            source = cause.source();
+        }
+        if self.backtrace.status() == BacktraceStatus::Captured {
+            writeln!(f, "{}", self.backtrace)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_exit_codes() {
+    let io_error = AppError::from(io::Error::new(io::ErrorKind::NotFound, "gone"));
+    assert_eq!(io_error.exit_code(), 74);
+    let mut reader = "x".as_bytes();
+    let parse_error = read_numbers::<i32>(&mut reader).unwrap_err();
+    assert!(matches!(parse_error, AppError::Parse(_)));
+    assert_eq!(parse_error.exit_code(), 65);
+    assert_eq!(AppError::Config("bad".to_string()).exit_code(), 64);
+    assert_eq!(AppError::NoNumbers.exit_code(), 65);
+    assert_eq!(AppError::Other("thread failed".into()).exit_code(), 1);
+}
+
+#[test]
+fn test_error_chain() {
+    colored::control::set_override(false);
+    let error = ErrorReport::from(io::Error::new(io::ErrorKind::NotFound, "gone"));
+    // followed by a backtrace when RUST_BACKTRACE is set
+    assert!(error
+        .to_string()
+        .starts_with("error: could not read the input\n  caused by: gone\n"));
+    let error = read_numbers::<i32>(&mut "1\n2 x".as_bytes()).unwrap_err();
+    let source = error::Error::source(&error).unwrap();
+    assert!(source.to_string().contains("on line 2, column 3"));
+}
+
+#[test]
+fn test_errors_across_threads() {
+    let handle = std::thread::spawn(|| -> Result<Vec<i32>, ThreadSafeGenericError> {
+        Ok(read_numbers(&mut "1\n2\nthree".as_bytes())?)
+    });
+    let error = AppError::from(handle.join().unwrap().unwrap_err());
+    assert!(matches!(error, AppError::Parse(_)));
+
+    // boxed by someone who never heard of AppError
+    let boxed: ThreadSafeGenericError = Box::new(io::Error::other("disk"));
+    assert!(matches!(AppError::from(boxed), AppError::Io(_)));
+    let boxed: ThreadSafeGenericError = "something else".into();
+    assert!(matches!(AppError::from(boxed), AppError::Other(_)));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParseMode {
+    // stop at the first value that does not parse
+    FailFast,
+    // keep going and report every value that does not parse
+    CollectErrors,
+}
+
+#[derive(Debug)]
+struct ParseReport<T> {
+    numbers: Vec<T>,
+    errors: Vec<ParseLineError>,
+}
+
+/// Values are separated by whitespace and/or commas, several per line if needed.
+/// Blank lines and everything after a `#` are skipped.
+fn parse_numbers<T>(file: &mut dyn BufRead, mode: ParseMode) -> io::Result<ParseReport<T>>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let mut report = ParseReport {
+        numbers: vec![],
+        errors: vec![],
+    };
     for (line_index, line_result) in file.lines().enumerate() {
         let line = line_result?;
-        match line.parse::<i32>() {
-            Ok(number) => numbers.push(number),
-            Err(parse_error) => {
-                return Err(Box::new(ParseLineError {
-                    message: parse_error.to_string(),
-                    line,
-                    line_number: line_index + 1,
-                }));
+        let content = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => &line,
+        };
+        for (column, token) in tokens(content) {
+            match token.parse::<T>() {
+                Ok(number) => report.numbers.push(number),
+                Err(parse_error) => {
+                    report.errors.push(ParseLineError {
+                        message: parse_error.to_string(),
+                        line: line.clone(),
+                        line_number: line_index + 1,
+                        column,
+                        token: token.to_string(),
+                        expected: std::any::type_name::<T>(),
+                    });
+                    if mode == ParseMode::FailFast {
+                        return Ok(report);
+                    }
+                }
             }
         }
     }
-    Ok(numbers)
+    Ok(report)
 }
-fn main() {
-    let stdin = stdin();
-    let mut reader = BufReader::new(stdin);
-    let numbers = read_numbers(&mut reader);
-    match numbers {
-        Ok(numbers) => println!("Numbers: {:?}", numbers),
-        Err(err) => {
-            if let Some(io_error) = err.downcast_ref::<io::Error>() {
-                eprintln!("{}: {}", "IO Error".yellow().underline(), io_error);
-            } else if let Some(parse_error) = err.downcast_ref::<ParseLineError>() {
-                eprintln!("{}: {}", "ParseLineError".yellow().bold(), parse_error);
+
+/// Split a line on whitespace and commas, with the (1-based, in characters) column of each token.
+fn tokens(line: &str) -> Vec<(usize, &str)> {
+    let mut tokens = vec![];
+    let mut start = None;
+    for (column, (index, c)) in line.char_indices().enumerate() {
+        let separator = c.is_whitespace() || c == ',';
+        match (start, separator) {
+            (None, false) => start = Some((column + 1, index)),
+            (Some((token_column, token_index)), true) => {
+                tokens.push((token_column, &line[token_index..index]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some((token_column, token_index)) = start {
+        tokens.push((token_column, &line[token_index..]));
+    }
+    tokens
+}
+
+#[test]
+fn test_tokens() {
+    assert_eq!(tokens(""), vec![]);
+    assert_eq!(tokens("  12"), vec![(3, "12")]);
+    assert_eq!(
+        tokens("1, 2,3\t-4 ,,x"),
+        vec![(1, "1"), (4, "2"), (6, "3"), (8, "-4"), (13, "x")]
+    );
+    assert_eq!(tokens("é,7"), vec![(1, "é"), (3, "7")]);
+}
+
+fn read_numbers<T>(file: &mut dyn BufRead) -> AppResult<Vec<T>>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let mut report = parse_numbers(file, ParseMode::FailFast)?;
+    match report.errors.pop() {
+        Some(error) => Err(error.into()),
+        None => Ok(report.numbers),
+    }
+}
+
+#[test]
+fn test_parse_numbers_good_fixture() {
+    let text = include_str!("numbers.txt");
+    for mode in [ParseMode::FailFast, ParseMode::CollectErrors] {
+        let report = parse_numbers::<i32>(&mut text.as_bytes(), mode).unwrap();
+        assert_eq!(report.numbers, vec![1, 2, 3, 4]);
+        assert!(report.errors.is_empty());
+    }
+    assert_eq!(
+        read_numbers::<i32>(&mut text.as_bytes()).unwrap(),
+        vec![1, 2, 3, 4]
+    );
+}
+
+#[test]
+fn test_parse_numbers_bad_fixture_fail_fast() {
+    let mut reader = include_str!("numbers_bad.txt").as_bytes();
+    let report = parse_numbers::<i32>(&mut reader, ParseMode::FailFast).unwrap();
+    assert_eq!(report.numbers, vec![1, 2, 3, 4]);
+    assert_eq!(report.errors.len(), 1);
+    assert_eq!(
+        (report.errors[0].line_number, report.errors[0].column),
+        (5, 1)
+    );
+
+    let mut reader = include_str!("numbers_bad.txt").as_bytes();
+    match read_numbers::<i32>(&mut reader).unwrap_err() {
+        AppError::Parse(error) => assert_eq!(error.line, "a"),
+        other => panic!("unexpected error: {}", other),
+    }
+}
+
+#[test]
+fn test_parse_numbers_bad_fixture_collect_errors() {
+    let mut reader = include_str!("numbers_bad.txt").as_bytes();
+    let report = parse_numbers::<i32>(&mut reader, ParseMode::CollectErrors).unwrap();
+    assert_eq!(report.numbers, vec![1, 2, 3, 4, 5, 6, 8, -1]);
+    let positions: Vec<(usize, usize)> = report
+        .errors
+        .iter()
+        .map(|e| (e.line_number, e.column))
+        .collect();
+    assert_eq!(positions, vec![(5, 1), (8, 6), (9, 1)]);
+    assert_eq!(report.errors[1].message, "invalid digit found in string");
+    assert_eq!(
+        report.errors[2].message,
+        "number too large to fit in target type"
+    );
+}
+
+#[test]
+fn test_parse_numbers_other_types() {
+    let mut reader = "1.5, 2e3 # floats\n-0.25".as_bytes();
+    let report = parse_numbers::<f64>(&mut reader, ParseMode::FailFast).unwrap();
+    assert_eq!(report.numbers, vec![1.5, 2000.0, -0.25]);
+    // 99999999999 is fine in an i64
+    let mut reader = include_str!("numbers_bad.txt").as_bytes();
+    let report = parse_numbers::<i64>(&mut reader, ParseMode::CollectErrors).unwrap();
+    assert_eq!(report.errors.len(), 2);
+    assert!(report.numbers.contains(&99999999999));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Table,
+    Json,
+}
+
+fn run(args: &[String]) -> Result<(), ErrorReport> {
+    // --all: report every value that does not parse instead of stopping at the first one
+    let mut all = false;
+    // --json: diagnostics as one JSON object per line, for tools
+    let mut json = false;
+    // --format table|json: how the statistics are printed
+    let mut format = Format::Table;
+    // --buckets N: the most buckets in the histogram
+    let mut buckets = 10;
+    let mut paths = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--all" => all = true,
+            "--json" => json = true,
+            "--backtrace" => {}
+            "--format" => {
+                format = match args.next().map(String::as_str) {
+                    Some("table") => Format::Table,
+                    Some("json") => Format::Json,
+                    _ => {
+                        let message = "--format needs 'table' or 'json'".to_string();
+                        return Err(AppError::Config(message).into());
+                    }
+                }
+            }
+            "--buckets" => {
+                buckets = match args.next().map(|n| n.parse::<usize>()) {
+                    Some(Ok(n)) if n > 0 => n,
+                    _ => {
+                        let message = "--buckets needs a number above 0".to_string();
+                        return Err(AppError::Config(message).into());
+                    }
+                }
+            }
+            option if option.starts_with("--") => {
+                return Err(AppError::Config(format!("unknown option '{}'", option)).into())
+            }
+            file => paths.push(file),
+        }
+    }
+
+    let mut numbers = vec![];
+    let mut first_error = None;
+    // stdin without a FILE
+    let inputs = if paths.is_empty() {
+        vec![None]
+    } else {
+        paths.into_iter().map(Some).collect()
+    };
+    for path in inputs {
+        // the whole text is kept to show the lines around an error
+        let (file_name, source) = match path {
+            Some(path) => (path, std::fs::read_to_string(path)?),
+            None => {
+                let mut source = String::new();
+                stdin().read_to_string(&mut source)?;
+                ("<stdin>", source)
+            }
+        };
+        let report_error = |parse_error: &ParseLineError| {
+            if json {
+                eprintln!("{}", diagnostic::to_json(file_name, parse_error));
             } else {
-                eprintln!("{}: {}", "Unknown Error".red().bold(), err);
+                eprint!("{}", diagnostic::render(file_name, &source, parse_error));
+            }
+        };
+
+        if all {
+            let report = parse_numbers::<i64>(&mut source.as_bytes(), ParseMode::CollectErrors)?;
+            numbers.extend(report.numbers);
+            report.errors.iter().for_each(report_error);
+            first_error = first_error.or(report.errors.into_iter().next());
+            continue;
+        }
+        match read_numbers::<i64>(&mut source.as_bytes()) {
+            Ok(file_numbers) => numbers.extend(file_numbers),
+            Err(error) => {
+                if let AppError::Parse(parse_error) = &error {
+                    report_error(parse_error);
+                }
+                return Err(error.into());
             }
         }
     }
+
+    // with --all, the statistics of what could be read, and still an error at the end
+    let stats = stats::summarize(&numbers, buckets).ok_or(AppError::NoNumbers)?;
+    match format {
+        Format::Table => print!("{}", stats.to_table()),
+        Format::Json => println!("{}", stats.to_json()),
+    }
+    match first_error {
+        Some(error) => Err(error.into()),
+        None => Ok(()),
+    }
+}
+
+fn main() {
+    // error_handling [--all] [--json] [--format table|json] [--buckets N] [--backtrace] [FILE]...
+    // reads stdin without a FILE
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // --backtrace: capture a backtrace with io and parse errors (as RUST_LIB_BACKTRACE=1 does)
+    if args.iter().any(|arg| arg == "--backtrace") {
+        std::env::set_var("RUST_LIB_BACKTRACE", "1");
+    }
+    if let Err(report) = run(&args) {
+        // parse errors have been shown with their diagnostic already
+        if !matches!(*report.error, AppError::Parse(_))
+            || report.backtrace.status() == BacktraceStatus::Captured
+        {
+            eprint!("{}", report);
+        }
+        std::process::exit(report.error.exit_code());
+    }
 }
@@ -0,0 +1,243 @@
+// Summary statistics of the numbers read, as a table for people or JSON for tools
+use serde::Serialize;
+
+/// Percentiles reported besides the median.
+const PERCENTILES: [u8; 4] = [25, 75, 90, 99];
+/// Widest histogram bar, in characters.
+const BAR_WIDTH: usize = 40;
+
+#[derive(Debug, PartialEq)]
+pub struct Extrema<T> {
+    pub greatest: T,
+    pub least: T,
+}
+
+/// Find the greatest and least numbers in a slice, `None` if it is empty.
+pub fn find_extrema<T>(slice: &[T]) -> Option<Extrema<T>>
+where
+    T: PartialOrd + Copy,
+{
+    let mut extrema = Extrema {
+        greatest: *slice.first()?,
+        least: slice[0],
+    };
+    for &element in &slice[1..] {
+        if element < extrema.least {
+            extrema.least = element;
+        }
+        if element > extrema.greatest {
+            extrema.greatest = element;
+        }
+    }
+    Some(extrema)
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Percentile {
+    pub percent: u8,
+    pub value: i64,
+}
+
+/// Numbers from `start` to `end`, both included.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Bucket {
+    pub start: i64,
+    pub end: i64,
+    pub count: usize,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Stats {
+    pub count: usize,
+    // an i64 cannot overflow an i128 sum before 2^64 numbers, the i64 is there when it fits
+    pub sum: i128,
+    pub sum_i64: Option<i64>,
+    pub mean: f64,
+    pub median: f64,
+    pub min: i64,
+    pub max: i64,
+    pub percentiles: Vec<Percentile>,
+    pub histogram: Vec<Bucket>,
+}
+
+/// Nearest-rank percentile of sorted numbers: the smallest value with at least `percent`% of
+/// the numbers at or below it.
+fn percentile(sorted: &[i64], percent: u8) -> i64 {
+    let rank = (percent as usize * sorted.len()).div_ceil(100);
+    sorted[rank.max(1) - 1]
+}
+
+/// At most `buckets` buckets of equal width from the least to the greatest number.
+fn histogram(numbers: &[i64], extrema: &Extrema<i64>, buckets: usize) -> Vec<Bucket> {
+    // i128, as the range of i64 numbers does not fit in an i64
+    let range = extrema.greatest as i128 - extrema.least as i128 + 1;
+    let width = (range + buckets.max(1) as i128 - 1) / buckets.max(1) as i128;
+    let count = ((range + width - 1) / width) as usize;
+    let mut histogram: Vec<Bucket> = (0..count as i128)
+        .map(|i| {
+            let start = extrema.least as i128 + i * width;
+            Bucket {
+                start: start as i64,
+                end: (start + width - 1).min(extrema.greatest as i128) as i64,
+                count: 0,
+            }
+        })
+        .collect();
+    for &number in numbers {
+        histogram[((number as i128 - extrema.least as i128) / width) as usize].count += 1;
+    }
+    histogram
+}
+
+/// Everything about `numbers` in one pass over them and one sort, `None` without numbers.
+pub fn summarize(numbers: &[i64], buckets: usize) -> Option<Stats> {
+    let extrema = find_extrema(numbers)?;
+    let sum: i128 = numbers.iter().map(|&n| n as i128).sum();
+    let mut sorted = numbers.to_vec();
+    sorted.sort_unstable();
+    let middle = sorted.len() / 2;
+    let median = if sorted.len().is_multiple_of(2) {
+        (sorted[middle - 1] as f64 + sorted[middle] as f64) / 2.0
+    } else {
+        sorted[middle] as f64
+    };
+    Some(Stats {
+        count: numbers.len(),
+        sum,
+        sum_i64: i64::try_from(sum).ok(),
+        mean: sum as f64 / numbers.len() as f64,
+        median,
+        min: extrema.least,
+        max: extrema.greatest,
+        percentiles: PERCENTILES
+            .iter()
+            .map(|&percent| Percentile {
+                percent,
+                value: percentile(&sorted, percent),
+            })
+            .collect(),
+        histogram: histogram(numbers, &extrema, buckets),
+    })
+}
+
+impl Stats {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("stats always serialize")
+    }
+
+    /// Aligned names and values, then the histogram with a bar per bucket.
+    pub fn to_table(&self) -> String {
+        let mut rows = vec![
+            ("count".to_string(), self.count.to_string()),
+            ("sum".to_string(), self.sum.to_string()),
+            ("mean".to_string(), format!("{:.3}", self.mean)),
+            ("median".to_string(), self.median.to_string()),
+            ("min".to_string(), self.min.to_string()),
+            ("max".to_string(), self.max.to_string()),
+        ];
+        for percentile in &self.percentiles {
+            rows.push((
+                format!("p{}", percentile.percent),
+                percentile.value.to_string(),
+            ));
+        }
+        let mut out = String::new();
+        for (name, value) in rows {
+            out += &format!("{:<8}{:>20}\n", name, value);
+        }
+
+        out += "\nhistogram\n";
+        let ranges: Vec<String> = self
+            .histogram
+            .iter()
+            .map(|bucket| format!("{}..={}", bucket.start, bucket.end))
+            .collect();
+        let range_width = ranges.iter().map(String::len).max().unwrap_or(0);
+        let most = self.histogram.iter().map(|b| b.count).max().unwrap_or(0);
+        let count_width = most.to_string().len();
+        for (bucket, range) in self.histogram.iter().zip(ranges) {
+            let bar = "#".repeat(bucket.count * BAR_WIDTH / most.max(1));
+            out += format!(
+                "{:>range_width$} {:>count_width$} {}",
+                range,
+                bucket.count,
+                bar,
+                range_width = range_width,
+                count_width = count_width
+            )
+            .trim_end();
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[test]
+fn test_find_extrema() {
+    assert_eq!(find_extrema::<i64>(&[]), None);
+    assert_eq!(
+        find_extrema(&[3, -7, 12, 0]),
+        Some(Extrema {
+            greatest: 12,
+            least: -7
+        })
+    );
+}
+
+#[test]
+fn test_summarize() {
+    let numbers: Vec<i64> = (1..=10).rev().collect();
+    let stats = summarize(&numbers, 5).unwrap();
+    assert_eq!((stats.count, stats.sum, stats.sum_i64), (10, 55, Some(55)));
+    assert_eq!((stats.mean, stats.median), (5.5, 5.5));
+    assert_eq!((stats.min, stats.max), (1, 10));
+    let percentiles: Vec<(u8, i64)> = stats
+        .percentiles
+        .iter()
+        .map(|p| (p.percent, p.value))
+        .collect();
+    assert_eq!(percentiles, vec![(25, 3), (75, 8), (90, 9), (99, 10)]);
+    assert_eq!(stats.histogram.len(), 5);
+    assert!(stats.histogram.iter().all(|bucket| bucket.count == 2));
+    assert_eq!((stats.histogram[4].start, stats.histogram[4].end), (9, 10));
+
+    assert_eq!(summarize(&[], 10), None);
+    let stats = summarize(&[42], 10).unwrap();
+    assert_eq!((stats.median, stats.histogram.len()), (42.0, 1));
+}
+
+#[test]
+fn test_sums_do_not_overflow() {
+    let stats = summarize(&[i64::MAX, i64::MAX, i64::MIN], 10).unwrap();
+    assert_eq!(stats.sum, i64::MAX as i128 - 1);
+    assert_eq!(stats.sum_i64, Some(i64::MAX - 1));
+    let stats = summarize(&[i64::MAX, i64::MAX], 10).unwrap();
+    assert_eq!(stats.sum, 2 * i64::MAX as i128);
+    assert_eq!(stats.sum_i64, None);
+    assert_eq!(stats.mean, i64::MAX as f64);
+
+    // the whole i64 range in 10 buckets
+    let stats = summarize(&[i64::MIN, i64::MAX], 10).unwrap();
+    assert_eq!(stats.sum, -1);
+    let counts: usize = stats.histogram.iter().map(|b| b.count).sum();
+    assert_eq!((stats.histogram.len(), counts), (10, 2));
+    assert_eq!(stats.histogram[9].end, i64::MAX);
+}
+
+#[test]
+fn test_formats() {
+    let stats = summarize(&[1, 2, 2, 3], 3).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&stats.to_json()).unwrap();
+    assert_eq!(json["sum"], 8);
+    assert_eq!(json["median"], 2.0);
+    assert_eq!(json["histogram"][1]["count"], 2);
+    assert_eq!(json["percentiles"][0]["percent"], 25);
+
+    let table = stats.to_table();
+    assert!(table.starts_with("count                      4\nsum                        8\n"));
+    assert!(
+        table.ends_with("histogram\n1..=1 1 ####################\n2..=2 2 ########################################\n3..=3 1 ####################\n"),
+        "{}",
+        table
+    );
+}
@@ -0,0 +1,479 @@
+// The same ordered set as BinaryTree, kept balanced with AVL rotations so that
+// sorted input does not make it a linked list
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt::Debug;
+
+use crate::binary_tree::{Order, Traversal};
+
+type Link<T> = Option<Box<AvlNode<T>>>;
+
+#[derive(Debug)]
+struct AvlNode<T> {
+    element: T,
+    /// the number of nodes on the longest path down from this one, itself included
+    height: usize,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+/// An ordered set where the heights of the two subtrees of any node differ by at most one,
+/// so that the tree is never taller than about 1.44 log2(n).
+#[derive(Debug)]
+pub struct AvlTree<T> {
+    root: Link<T>,
+    len: usize,
+}
+
+impl<T> Default for AvlTree<T> {
+    fn default() -> Self {
+        AvlTree::new()
+    }
+}
+
+fn height<T>(link: &Link<T>) -> usize {
+    link.as_ref().map_or(0, |node| node.height)
+}
+
+impl<T> AvlNode<T> {
+    fn leaf(element: T) -> Box<Self> {
+        Box::new(AvlNode {
+            element,
+            height: 1,
+            left: None,
+            right: None,
+        })
+    }
+
+    fn update_height(&mut self) {
+        self.height = 1 + height(&self.left).max(height(&self.right));
+    }
+
+    /// Positive when the left subtree is taller.
+    fn balance(&self) -> isize {
+        height(&self.left) as isize - height(&self.right) as isize
+    }
+}
+
+//     node          left
+//     /  \          /  \
+//   left  c  ->    a   node
+//   /  \               /  \
+//  a    b             b    c
+fn rotate_right<T>(mut node: Box<AvlNode<T>>) -> Box<AvlNode<T>> {
+    let mut left = node.left.take().expect("rotating right needs a left child");
+    node.left = left.right.take();
+    node.update_height();
+    left.right = Some(node);
+    left.update_height();
+    left
+}
+
+fn rotate_left<T>(mut node: Box<AvlNode<T>>) -> Box<AvlNode<T>> {
+    let mut right = node
+        .right
+        .take()
+        .expect("rotating left needs a right child");
+    node.right = right.left.take();
+    node.update_height();
+    right.left = Some(node);
+    right.update_height();
+    right
+}
+
+/// After one addition or removal below `node`, its balance is at worst ±2: one rotation,
+/// or two when the taller child leans the other way, brings it back.
+fn rebalance<T>(mut node: Box<AvlNode<T>>) -> Box<AvlNode<T>> {
+    node.update_height();
+    match node.balance() {
+        2 => {
+            let left = node.left.take().expect("the left subtree is taller");
+            node.left = Some(if left.balance() < 0 {
+                rotate_left(left)
+            } else {
+                left
+            });
+            rotate_right(node)
+        }
+        -2 => {
+            let right = node.right.take().expect("the right subtree is taller");
+            node.right = Some(if right.balance() > 0 {
+                rotate_right(right)
+            } else {
+                right
+            });
+            rotate_left(node)
+        }
+        _ => node,
+    }
+}
+
+fn add_to<T: Ord>(link: &mut Link<T>, value: T) -> bool {
+    let Some(mut node) = link.take() else {
+        *link = Some(AvlNode::leaf(value));
+        return true;
+    };
+    let added = match value.cmp(&node.element) {
+        Ordering::Less => add_to(&mut node.left, value),
+        Ordering::Greater => add_to(&mut node.right, value),
+        Ordering::Equal => false,
+    };
+    *link = Some(rebalance(node));
+    added
+}
+
+fn remove_from<T, Q>(link: &mut Link<T>, value: &Q) -> bool
+where
+    T: Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    let Some(mut node) = link.take() else {
+        return false;
+    };
+    let removed = match value.cmp(node.element.borrow()) {
+        Ordering::Less => remove_from(&mut node.left, value),
+        Ordering::Greater => remove_from(&mut node.right, value),
+        Ordering::Equal => {
+            // a lone child takes its place, with two the successor moves up
+            let AvlNode { left, right, .. } = *node;
+            *link = match (left, right) {
+                (None, right) => right,
+                (left, None) => left,
+                (left, Some(right)) => {
+                    let (successor, right) = pop_min(right);
+                    let mut node = AvlNode::leaf(successor);
+                    node.left = left;
+                    node.right = right;
+                    Some(rebalance(node))
+                }
+            };
+            return true;
+        }
+    };
+    *link = Some(rebalance(node));
+    removed
+}
+
+/// The smallest element, and what is left of the subtree without it.
+fn pop_min<T>(mut node: Box<AvlNode<T>>) -> (T, Link<T>) {
+    match node.left.take() {
+        None => (node.element, node.right),
+        Some(left) => {
+            let (min, left) = pop_min(left);
+            node.left = left;
+            (min, Some(rebalance(node)))
+        }
+    }
+}
+
+/// The height and length of a subtree whose elements must all be between `low` and `high`.
+fn check_node<'a, T: Ord + Debug>(
+    link: &'a Link<T>,
+    low: Option<&'a T>,
+    high: Option<&'a T>,
+) -> Result<(usize, usize), String> {
+    let Some(node) = link else {
+        return Ok((0, 0));
+    };
+    if low.is_some_and(|low| node.element <= *low) || high.is_some_and(|high| node.element >= *high)
+    {
+        return Err(format!("{:?} is out of order", node.element));
+    }
+    let (left_height, left_len) = check_node(&node.left, low, Some(&node.element))?;
+    let (right_height, right_len) = check_node(&node.right, Some(&node.element), high)?;
+    let height = 1 + left_height.max(right_height);
+    if node.height != height {
+        return Err(format!(
+            "{:?} has height {} instead of {}",
+            node.element, node.height, height
+        ));
+    }
+    if left_height.abs_diff(right_height) > 1 {
+        return Err(format!(
+            "{:?} is unbalanced, its subtrees have heights {} and {}",
+            node.element, left_height, right_height
+        ));
+    }
+    Ok((height, 1 + left_len + right_len))
+}
+
+impl<T> AvlTree<T> {
+    pub fn new() -> Self {
+        AvlTree { root: None, len: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn height(&self) -> usize {
+        height(&self.root)
+    }
+
+    pub fn min(&self) -> Option<&T> {
+        let mut node = self.root.as_ref()?;
+        while let Some(left) = &node.left {
+            node = left;
+        }
+        Some(&node.element)
+    }
+
+    pub fn max(&self) -> Option<&T> {
+        let mut node = self.root.as_ref()?;
+        while let Some(right) = &node.right {
+            node = right;
+        }
+        Some(&node.element)
+    }
+
+    /// In order, smallest first.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter::new(self, Order::In)
+    }
+
+    pub fn pre_order(&self) -> Iter<'_, T> {
+        Iter::new(self, Order::Pre)
+    }
+
+    pub fn post_order(&self) -> Iter<'_, T> {
+        Iter::new(self, Order::Post)
+    }
+
+    pub fn level_order(&self) -> Iter<'_, T> {
+        Iter::new(self, Order::Level)
+    }
+
+    pub fn into_pre_order(self) -> IntoIter<T> {
+        IntoIter::new(self, Order::Pre)
+    }
+
+    pub fn into_post_order(self) -> IntoIter<T> {
+        IntoIter::new(self, Order::Post)
+    }
+
+    pub fn into_level_order(self) -> IntoIter<T> {
+        IntoIter::new(self, Order::Level)
+    }
+}
+
+impl<T: Ord> AvlTree<T> {
+    /// Add `value` unless an equal element is already there, returns whether it was added.
+    pub fn add(&mut self, value: T) -> bool {
+        let added = add_to(&mut self.root, value);
+        self.len += added as usize;
+        added
+    }
+
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut link = &self.root;
+        while let Some(node) = link {
+            link = match value.cmp(node.element.borrow()) {
+                Ordering::Less => &node.left,
+                Ordering::Greater => &node.right,
+                Ordering::Equal => return true,
+            };
+        }
+        false
+    }
+
+    /// Remove the element equal to `value`, returns whether there was one.
+    pub fn remove<Q>(&mut self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let removed = remove_from(&mut self.root, value);
+        self.len -= removed as usize;
+        removed
+    }
+
+    /// Check every invariant of the tree: the elements are in order, each node knows its
+    /// height, no node is unbalanced and `len` is right. The first broken one is described.
+    pub fn check(&self) -> Result<(), String>
+    where
+        T: Debug,
+    {
+        let (_, len) = check_node(&self.root, None, None)?;
+        if len != self.len {
+            return Err(format!("{} elements but len is {}", len, self.len));
+        }
+        Ok(())
+    }
+}
+
+fn split<T>(node: &AvlNode<T>) -> (&T, Option<&AvlNode<T>>, Option<&AvlNode<T>>) {
+    (&node.element, node.left.as_deref(), node.right.as_deref())
+}
+
+/// The elements of a `&AvlTree`, in one of the traversal orders.
+pub struct Iter<'a, T>(Traversal<&'a AvlNode<T>, &'a T>);
+
+impl<'a, T> Iter<'a, T> {
+    fn new(tree: &'a AvlTree<T>, order: Order) -> Self {
+        Iter(Traversal::new(tree.root.as_deref(), order))
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.0.next_with(split)
+    }
+}
+
+/// The elements of an `AvlTree` taken apart, in one of the traversal orders.
+pub struct IntoIter<T>(Traversal<Box<AvlNode<T>>, T>);
+
+impl<T> IntoIter<T> {
+    fn new(tree: AvlTree<T>, order: Order) -> Self {
+        IntoIter(Traversal::new(tree.root, order))
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.0
+            .next_with(|node| (node.element, node.left, node.right))
+    }
+}
+
+impl<'a, T> IntoIterator for &'a AvlTree<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// In order, smallest first.
+impl<T> IntoIterator for AvlTree<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter::new(self, Order::In)
+    }
+}
+
+impl<T: Ord> Extend<T> for AvlTree<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.add(value);
+        }
+    }
+}
+
+impl<T: Ord> FromIterator<T> for AvlTree<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut tree = AvlTree::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+#[test]
+fn test_rotations() {
+    // each of the four cases, on three elements
+    for order in [[1, 2, 3], [3, 2, 1], [1, 3, 2], [3, 1, 2]] {
+        let tree: AvlTree<i32> = order.into_iter().collect();
+        tree.check().unwrap();
+        assert_eq!(tree.pre_order().copied().collect::<Vec<_>>(), [2, 1, 3]);
+    }
+    let tree: AvlTree<i32> = [4, 2, 6, 1, 3, 5, 7].into_iter().collect();
+    assert_eq!(
+        tree.level_order().copied().collect::<Vec<_>>(),
+        [4, 2, 6, 1, 3, 5, 7]
+    );
+    assert_eq!(
+        tree.post_order().copied().collect::<Vec<_>>(),
+        [1, 3, 2, 5, 7, 6, 4]
+    );
+}
+
+#[test]
+fn test_sorted_input_stays_balanced() {
+    let mut tree: AvlTree<u32> = (0..1000).collect();
+    tree.check().unwrap();
+    assert_eq!(tree.len(), 1000);
+    // a perfect tree of 1023 nodes has 10 levels
+    assert!(tree.height() <= 11, "height {}", tree.height());
+    for n in (0..1000).step_by(2) {
+        assert!(tree.remove(&n));
+    }
+    tree.check().unwrap();
+    assert!(tree.height() <= 10);
+    assert_eq!(tree.min(), Some(&1));
+    assert_eq!(tree.max(), Some(&999));
+    assert!(tree.into_iter().eq((1..1000).step_by(2)));
+}
+
+#[test]
+fn test_check_finds_broken_trees() {
+    let mut tree: AvlTree<i32> = [2, 1, 3].into_iter().collect();
+    tree.root.as_mut().unwrap().element = 0;
+    assert_eq!(tree.check(), Err("1 is out of order".to_string()));
+
+    let mut tree: AvlTree<i32> = [2, 1, 3].into_iter().collect();
+    tree.root.as_mut().unwrap().height = 5;
+    assert_eq!(tree.check(), Err("2 has height 5 instead of 2".to_string()));
+
+    let mut tree: AvlTree<i32> = [2, 1, 3, 4].into_iter().collect();
+    tree.root.as_mut().unwrap().left = None;
+    assert_eq!(
+        tree.check(),
+        Err("2 is unbalanced, its subtrees have heights 0 and 2".to_string())
+    );
+}
+
+#[test]
+fn test_same_as_btreeset() {
+    use std::collections::BTreeSet;
+
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut random = |n: u64| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state % n
+    };
+    for _ in 0..50 {
+        let mut tree = AvlTree::new();
+        let mut set = BTreeSet::new();
+        for _ in 0..300 {
+            let value = random(100);
+            match random(3) {
+                0 => assert_eq!(tree.remove(&value), set.remove(&value)),
+                1 => assert_eq!(tree.contains(&value), set.contains(&value)),
+                _ => assert_eq!(tree.add(value), set.insert(value)),
+            }
+            tree.check().unwrap();
+        }
+        assert!(tree.iter().eq(set.iter()));
+        assert_eq!((tree.min(), tree.max()), (set.first(), set.last()));
+        for order in [tree.pre_order(), tree.post_order(), tree.level_order()] {
+            let mut elements: Vec<_> = order.collect();
+            elements.sort();
+            assert!(elements.into_iter().eq(set.iter()));
+        }
+        let rebuilt: AvlTree<u64> = tree.pre_order().copied().collect();
+        rebuilt.check().unwrap();
+        assert!(rebuilt.into_iter().eq(set.iter().copied()));
+        let len = tree.len();
+        assert_eq!(tree.into_post_order().count(), len);
+    }
+    let names: AvlTree<String> = ["Mars", "Venus"].map(String::from).into_iter().collect();
+    assert!(names.contains("Mars"));
+    assert!(AvlTree::<u8>::default().into_level_order().next().is_none());
+}
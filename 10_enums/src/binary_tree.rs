@@ -0,0 +1,745 @@
+// An ordered set as a binary search tree of boxed nodes, with its traversals as iterators
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::fmt;
+use std::mem;
+
+/// An ordered set on an unbalanced `Tree`, keeping its number of elements as it goes.
+/// Every walk down the tree is a loop, so a tree as deep as it is long, from sorted input,
+/// does not run out of stack.
+#[derive(Debug, Default)]
+pub struct BinaryTree<T> {
+    root: Tree<T>,
+    len: usize,
+}
+
+/// The nodes themselves: a tree is empty or a node with two subtrees.
+#[derive(Debug, Default)]
+pub enum Tree<T> {
+    #[default]
+    Empty,
+    NonEmpty(Box<TreeNode<T>>),
+}
+
+#[derive(Debug)]
+pub struct TreeNode<T> {
+    pub element: T,
+    pub left: Tree<T>,
+    pub right: Tree<T>,
+}
+
+impl<T> Tree<T> {
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Tree::Empty)
+    }
+
+    fn root(&self) -> Option<&TreeNode<T>> {
+        match self {
+            Tree::Empty => None,
+            Tree::NonEmpty(node) => Some(node),
+        }
+    }
+
+    fn into_root(self) -> Option<Box<TreeNode<T>>> {
+        match self {
+            Tree::Empty => None,
+            Tree::NonEmpty(node) => Some(node),
+        }
+    }
+
+    /// In order, smallest first.
+    pub(crate) fn iter(&self) -> Iter<'_, T> {
+        Iter::new(self, Order::In)
+    }
+
+    /// Remove the root node and return its element. A lone child takes its place, and with two
+    /// children the smallest element on the right, its successor, moves up.
+    pub(crate) fn pop_root(&mut self) -> Option<T> {
+        let node = mem::take(self).into_root()?;
+        let TreeNode {
+            element,
+            left,
+            mut right,
+        } = *node;
+        *self = match (left, right.is_empty()) {
+            (Tree::Empty, _) => right,
+            (left, true) => left,
+            (left, false) => {
+                let successor = right.pop_min().expect("the right subtree is not empty");
+                Tree::NonEmpty(Box::new(TreeNode {
+                    element: successor,
+                    left,
+                    right,
+                }))
+            }
+        };
+        Some(element)
+    }
+
+    fn pop_min(&mut self) -> Option<T> {
+        let mut tree = self;
+        loop {
+            let has_left = matches!(tree, Tree::NonEmpty(node) if !node.left.is_empty());
+            tree = match (has_left, tree) {
+                (true, Tree::NonEmpty(node)) => &mut node.left,
+                (_, tree) => return tree.pop_root(),
+            };
+        }
+    }
+
+    /// Take the tree apart a node at a time. The derived drop would recurse once per level.
+    pub(crate) fn clear(&mut self) {
+        let mut pending: Vec<_> = mem::take(self).into_root().into_iter().collect();
+        while let Some(mut node) = pending.pop() {
+            pending.extend(mem::take(&mut node.left).into_root());
+            pending.extend(mem::take(&mut node.right).into_root());
+        }
+    }
+}
+
+impl<T: Ord> Tree<T> {
+    /// The subtree where `value` is, or the empty one where it would go.
+    fn find<Q>(&mut self, value: &Q) -> &mut Tree<T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut tree = self;
+        loop {
+            let direction = match tree {
+                Tree::NonEmpty(node) => value.cmp(node.element.borrow()),
+                Tree::Empty => Ordering::Equal,
+            };
+            tree = match (direction, tree) {
+                (Ordering::Less, Tree::NonEmpty(node)) => &mut node.left,
+                (Ordering::Greater, Tree::NonEmpty(node)) => &mut node.right,
+                (_, tree) => return tree,
+            };
+        }
+    }
+}
+
+impl<T> BinaryTree<T> {
+    pub fn new() -> Self {
+        BinaryTree {
+            root: Tree::Empty,
+            len: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// The number of nodes on the longest path from the root, 0 for an empty tree.
+    pub fn height(&self) -> usize {
+        let mut height = 0;
+        let mut pending: Vec<_> = self.root.root().map(|node| (node, 1)).into_iter().collect();
+        while let Some((node, depth)) = pending.pop() {
+            height = height.max(depth);
+            for child in [&node.left, &node.right] {
+                pending.extend(child.root().map(|child| (child, depth + 1)));
+            }
+        }
+        height
+    }
+
+    /// The smallest element, the leftmost node.
+    pub fn min(&self) -> Option<&T> {
+        let mut node = self.root.root()?;
+        while let Some(left) = node.left.root() {
+            node = left;
+        }
+        Some(&node.element)
+    }
+
+    /// The largest element, the rightmost node.
+    pub fn max(&self) -> Option<&T> {
+        let mut node = self.root.root()?;
+        while let Some(right) = node.right.root() {
+            node = right;
+        }
+        Some(&node.element)
+    }
+
+    /// In order, smallest first.
+    pub fn iter(&self) -> Iter<'_, T> {
+        self.root.iter()
+    }
+
+    /// Each node before its left then right subtrees: adding them in this order rebuilds the tree.
+    pub fn pre_order(&self) -> Iter<'_, T> {
+        Iter::new(&self.root, Order::Pre)
+    }
+
+    /// Each node after its subtrees, the root last.
+    pub fn post_order(&self) -> Iter<'_, T> {
+        Iter::new(&self.root, Order::Post)
+    }
+
+    /// Level by level from the root, left to right.
+    pub fn level_order(&self) -> Iter<'_, T> {
+        Iter::new(&self.root, Order::Level)
+    }
+
+    pub fn into_pre_order(mut self) -> IntoIter<T> {
+        IntoIter::new(mem::take(&mut self.root), Order::Pre)
+    }
+
+    pub fn into_post_order(mut self) -> IntoIter<T> {
+        IntoIter::new(mem::take(&mut self.root), Order::Post)
+    }
+
+    pub fn into_level_order(mut self) -> IntoIter<T> {
+        IntoIter::new(mem::take(&mut self.root), Order::Level)
+    }
+}
+
+impl<T: Ord> BinaryTree<T> {
+    /// Add `value` unless an equal element is already there, returns whether it was added.
+    pub fn add(&mut self, value: T) -> bool {
+        let slot = self.root.find(&value);
+        if !slot.is_empty() {
+            return false;
+        }
+        *slot = Tree::NonEmpty(Box::new(TreeNode {
+            element: value,
+            left: Tree::Empty,
+            right: Tree::Empty,
+        }));
+        self.len += 1;
+        true
+    }
+
+    /// Like `BTreeSet`, a `BinaryTree<String>` can be searched with a `&str`.
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut tree = &self.root;
+        while let Tree::NonEmpty(node) = tree {
+            tree = match value.cmp(node.element.borrow()) {
+                Ordering::Less => &node.left,
+                Ordering::Greater => &node.right,
+                Ordering::Equal => return true,
+            };
+        }
+        false
+    }
+
+    /// Remove the element equal to `value`, returns whether there was one.
+    pub fn remove<Q>(&mut self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let removed = self.root.find(value).pop_root().is_some();
+        if removed {
+            self.len -= 1;
+        }
+        removed
+    }
+}
+
+impl<T> Drop for BinaryTree<T> {
+    fn drop(&mut self) {
+        self.root.clear();
+    }
+}
+
+/// A tree put together by hand, its nodes counted once. They should already be in order.
+impl<T> From<Tree<T>> for BinaryTree<T> {
+    fn from(root: Tree<T>) -> Self {
+        let len = Iter::new(&root, Order::Pre).count();
+        BinaryTree { root, len }
+    }
+}
+
+/// What is left to write of a tree, kept on a stack rather than in recursive calls:
+/// a subtree, or what to write once the steps pushed after it are done.
+enum Step<'a, T, W> {
+    Tree(&'a Tree<T>),
+    Write(W),
+}
+
+impl<T: fmt::Display> BinaryTree<T> {
+    /// The tree in Graphviz's DOT language, for `dot -Tsvg`. A missing child is an invisible
+    /// node so that a lone child is still drawn on its own side.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph BinaryTree {\n    node [shape=box, style=rounded];\n");
+        // nodes are numbered in pre-order, each edge is written after the subtree it leads to
+        let mut steps = vec![Step::Tree(&self.root)];
+        // the nodes written whose edge from their parent is not written yet, the last on top
+        let mut children = vec![];
+        let mut count = 0;
+        while let Some(step) = steps.pop() {
+            match step {
+                Step::Tree(Tree::Empty) => {
+                    dot.push_str(&format!("    n{} [style=invis];\n", count));
+                }
+                Step::Tree(Tree::NonEmpty(node)) => {
+                    let label = node
+                        .element
+                        .to_string()
+                        .replace('\\', "\\\\")
+                        .replace('"', "\\\"");
+                    dot.push_str(&format!("    n{} [label=\"{}\"];\n", count, label));
+                    if !node.left.is_empty() || !node.right.is_empty() {
+                        // pushed last is popped first
+                        for child in [&node.right, &node.left] {
+                            steps.push(Step::Write((count, child.is_empty())));
+                            steps.push(Step::Tree(child));
+                        }
+                    }
+                }
+                // the edge from node `from` to the child written just before
+                Step::Write((from, invisible)) => {
+                    let to = children.pop().expect("the child was written");
+                    let style = if invisible { " [style=invis]" } else { "" };
+                    dot.push_str(&format!("    n{} -> n{}{};\n", from, to, style));
+                    continue;
+                }
+            }
+            children.push(count);
+            count += 1;
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Where a node is drawn relative to its parent, sideways the right child is above.
+#[derive(Clone, Copy, PartialEq)]
+enum Branch {
+    Root,
+    Upper,
+    Lower,
+}
+
+/// Drawn sideways, the root on the left and larger elements above:
+///
+/// ```text
+///     ┌── Venus
+/// ┌── Uranus
+/// Saturn
+/// │   ┌── Mercury
+/// └── Mars
+///     └── Jupiter
+/// ```
+impl<T: fmt::Display> fmt::Display for BinaryTree<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "(empty)");
+        }
+        // larger elements first, each step with the prefix of the lines it writes
+        let mut steps = vec![(Step::Tree(&self.root), String::new(), Branch::Root)];
+        while let Some((step, prefix, branch)) = steps.pop() {
+            match step {
+                Step::Tree(Tree::Empty) => {}
+                Step::Tree(Tree::NonEmpty(node)) => {
+                    // the line from a parent to a child further away runs past this one
+                    let extend = |line_through: Branch| match branch {
+                        Branch::Root => prefix.clone(),
+                        _ if branch == line_through => format!("{}│   ", prefix),
+                        _ => format!("{}    ", prefix),
+                    };
+                    // pushed last is popped first
+                    steps.push((Step::Tree(&node.left), extend(Branch::Upper), Branch::Lower));
+                    steps.push((Step::Write(&node.element), prefix.clone(), branch));
+                    steps.push((
+                        Step::Tree(&node.right),
+                        extend(Branch::Lower),
+                        Branch::Upper,
+                    ));
+                }
+                Step::Write(element) => {
+                    let connector = match branch {
+                        Branch::Root => "",
+                        Branch::Upper => "┌── ",
+                        Branch::Lower => "└── ",
+                    };
+                    writeln!(f, "{}{}{}", prefix, connector, element)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The order in which a traversal visits the elements.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Order {
+    In,
+    Pre,
+    Post,
+    Level,
+}
+
+/// A subtree still to visit, or an element ready to be returned.
+enum Visit<N, E> {
+    Node(N),
+    Element(E),
+}
+
+/// The state of a traversal of nodes `N` giving elements `E`, borrowed or owned.
+/// It knows nothing of the nodes themselves, so the balanced trees use it too.
+pub(crate) struct Traversal<N, E> {
+    order: Order,
+    /// a stack, popped at the back, for the depth-first orders,
+    /// and a queue, popped at the front, for the level order
+    pending: VecDeque<Visit<N, E>>,
+}
+
+impl<N, E> Traversal<N, E> {
+    pub(crate) fn new(root: Option<N>, order: Order) -> Self {
+        Traversal {
+            order,
+            pending: root.map(Visit::Node).into_iter().collect(),
+        }
+    }
+
+    /// The next element, `split` takes a node apart into its element and children.
+    pub(crate) fn next_with(
+        &mut self,
+        split: impl Fn(N) -> (E, Option<N>, Option<N>),
+    ) -> Option<E> {
+        loop {
+            let visit = if self.order == Order::Level {
+                self.pending.pop_front()
+            } else {
+                self.pending.pop_back()
+            };
+            let (element, left, right) = match visit? {
+                Visit::Element(element) => return Some(element),
+                Visit::Node(node) => split(node),
+            };
+            let (left, right) = (left.map(Visit::Node), right.map(Visit::Node));
+            let element = Visit::Element(element);
+            match self.order {
+                // pushed last is popped first
+                Order::In => self
+                    .pending
+                    .extend(right.into_iter().chain([element]).chain(left)),
+                Order::Pre => self
+                    .pending
+                    .extend(right.into_iter().chain(left).chain([element])),
+                Order::Post => self
+                    .pending
+                    .extend([element].into_iter().chain(right).chain(left)),
+                Order::Level => {
+                    self.pending.push_front(element);
+                    self.pending.extend(left.into_iter().chain(right));
+                }
+            }
+        }
+    }
+}
+
+/// The elements of a `&BinaryTree`, in one of the traversal orders.
+pub struct Iter<'a, T>(Traversal<&'a TreeNode<T>, &'a T>);
+
+impl<'a, T> Iter<'a, T> {
+    fn new(tree: &'a Tree<T>, order: Order) -> Self {
+        Iter(Traversal::new(tree.root(), order))
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.0
+            .next_with(|node| (&node.element, node.left.root(), node.right.root()))
+    }
+}
+
+/// The elements of a `BinaryTree` taken apart, in one of the traversal orders.
+pub struct IntoIter<T>(Traversal<Box<TreeNode<T>>, T>);
+
+impl<T> IntoIter<T> {
+    fn new(tree: Tree<T>, order: Order) -> Self {
+        IntoIter(Traversal::new(tree.into_root(), order))
+    }
+}
+
+/// What is left of the tree goes a node at a time too.
+impl<T> Drop for IntoIter<T> {
+    fn drop(&mut self) {
+        for _ in self {}
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.0.next_with(|node| {
+            let TreeNode {
+                element,
+                left,
+                right,
+            } = *node;
+            (element, left.into_root(), right.into_root())
+        })
+    }
+}
+
+impl<'a, T> IntoIterator for &'a BinaryTree<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// In order, smallest first.
+impl<T> IntoIterator for BinaryTree<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(mut self) -> IntoIter<T> {
+        IntoIter::new(mem::take(&mut self.root), Order::In)
+    }
+}
+
+impl<T: Ord> Extend<T> for BinaryTree<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.add(value);
+        }
+    }
+}
+
+impl<T: Ord> FromIterator<T> for BinaryTree<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut tree = BinaryTree::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+#[test]
+fn test_traversals() {
+    //       4
+    //     /   \
+    //    2     6
+    //   / \     \
+    //  1   3     7
+    let tree: BinaryTree<i32> = [4, 2, 6, 1, 3, 7].into_iter().collect();
+    let orders = |iter: Iter<i32>| iter.copied().collect::<Vec<_>>();
+    assert_eq!(orders(tree.iter()), [1, 2, 3, 4, 6, 7]);
+    assert_eq!(orders(tree.pre_order()), [4, 2, 1, 3, 6, 7]);
+    assert_eq!(orders(tree.post_order()), [1, 3, 2, 7, 6, 4]);
+    assert_eq!(orders(tree.level_order()), [4, 2, 6, 1, 3, 7]);
+    let mut sum = 0;
+    for element in &tree {
+        sum += element;
+    }
+    assert_eq!(sum, 23);
+
+    let rebuild = || -> BinaryTree<i32> { tree.pre_order().copied().collect() };
+    assert_eq!(
+        rebuild().into_iter().collect::<Vec<_>>(),
+        [1, 2, 3, 4, 6, 7]
+    );
+    assert_eq!(
+        rebuild().into_pre_order().collect::<Vec<_>>(),
+        [4, 2, 1, 3, 6, 7]
+    );
+    assert_eq!(
+        rebuild().into_post_order().collect::<Vec<_>>(),
+        [1, 3, 2, 7, 6, 4]
+    );
+    assert_eq!(
+        rebuild().into_level_order().collect::<Vec<_>>(),
+        [4, 2, 6, 1, 3, 7]
+    );
+    assert_eq!(BinaryTree::<i32>::new().iter().next(), None);
+}
+
+#[test]
+fn test_set_operations() {
+    let mut tree = BinaryTree::new();
+    assert!(tree.is_empty());
+    assert_eq!((tree.len(), tree.height(), tree.min()), (0, 0, None));
+    for planet in ["Saturn", "Mars", "Uranus", "Jupiter", "Mercury", "Venus"] {
+        assert!(tree.add(planet.to_string()));
+    }
+    assert!(!tree.add("Mars".to_string()));
+    assert_eq!((tree.len(), tree.height()), (6, 3));
+    assert!(tree.contains("Venus"));
+    assert!(!tree.contains("Pluto"));
+    assert_eq!(tree.min().map(String::as_str), Some("Jupiter"));
+    assert_eq!(tree.max().map(String::as_str), Some("Venus"));
+
+    // the root, with two children
+    assert!(tree.remove("Saturn"));
+    assert!(!tree.remove("Saturn"));
+    assert_eq!(tree.pre_order().next().map(String::as_str), Some("Uranus"));
+    assert_eq!(
+        tree.iter().map(String::as_str).collect::<Vec<_>>(),
+        ["Jupiter", "Mars", "Mercury", "Uranus", "Venus"]
+    );
+}
+
+#[test]
+fn test_same_as_btreeset() {
+    use std::collections::BTreeSet;
+
+    // xorshift, enough to make up operations
+    let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+    let mut random = |n: u64| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state % n
+    };
+    for _ in 0..50 {
+        let mut tree = BinaryTree::new();
+        let mut set = BTreeSet::new();
+        for _ in 0..200 {
+            let value = random(64);
+            match random(3) {
+                0 => assert_eq!(tree.remove(&value), set.remove(&value)),
+                1 => assert_eq!(tree.contains(&value), set.contains(&value)),
+                _ => assert_eq!(tree.add(value), set.insert(value)),
+            }
+            assert_eq!(tree.len(), set.len());
+        }
+        assert!(tree.iter().eq(set.iter()));
+        assert_eq!(tree.min(), set.first());
+        assert_eq!(tree.max(), set.last());
+        for order in [tree.pre_order(), tree.post_order(), tree.level_order()] {
+            let mut elements: Vec<_> = order.collect();
+            elements.sort();
+            assert!(elements.into_iter().eq(set.iter()));
+        }
+        assert!(tree.height() <= tree.len());
+        let mut extended: BinaryTree<u64> = set.iter().copied().rev().collect();
+        extended.extend(set.iter().copied());
+        assert!(extended.into_iter().eq(set.into_iter()));
+    }
+}
+
+#[test]
+fn test_display() {
+    let tree: BinaryTree<&str> = [
+        "Saturn", "Mars", "Uranus", "Jupiter", "Mercury", "Venus", "Earth",
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(
+        tree.to_string(),
+        "    ┌── Venus
+┌── Uranus
+Saturn
+│   ┌── Mercury
+└── Mars
+    └── Jupiter
+        └── Earth
+"
+    );
+    let tree: BinaryTree<i32> = [5, 2, 8, 1, 4, 3, 9].into_iter().collect();
+    assert_eq!(
+        tree.to_string(),
+        "    ┌── 9
+┌── 8
+5
+│   ┌── 4
+│   │   └── 3
+└── 2
+    └── 1
+"
+    );
+    assert_eq!(BinaryTree::<i32>::new().to_string(), "(empty)\n");
+}
+
+#[test]
+fn test_to_dot() {
+    let tree: BinaryTree<&str> = ["b", "a\"quoted\"", "c", "d"].into_iter().collect();
+    assert_eq!(
+        tree.to_dot(),
+        r#"digraph BinaryTree {
+    node [shape=box, style=rounded];
+    n0 [label="b"];
+    n1 [label="a\"quoted\""];
+    n0 -> n1;
+    n2 [label="c"];
+    n3 [style=invis];
+    n2 -> n3 [style=invis];
+    n4 [label="d"];
+    n2 -> n4;
+    n0 -> n2;
+}
+"#
+    );
+    assert_eq!(
+        BinaryTree::<i32>::new().to_dot(),
+        "digraph BinaryTree {\n    node [shape=box, style=rounded];\n    n0 [style=invis];\n}\n"
+    );
+}
+
+#[test]
+fn test_deep_tree_does_not_overflow_the_stack() {
+    // what sorted input makes, each element right of the last: a list 50,000 levels deep,
+    // built directly since adding them one by one takes quadratic time
+    let list = || {
+        (0..50_000).rev().fold(Tree::Empty, |right, element| {
+            Tree::NonEmpty(Box::new(TreeNode {
+                element,
+                left: Tree::Empty,
+                right,
+            }))
+        })
+    };
+    let mut tree = BinaryTree::from(list());
+    assert_eq!((tree.len(), tree.height()), (50_000, 50_000));
+    assert!(tree.add(50_000));
+    assert!(!tree.add(49_999));
+    assert!(tree.contains(&49_999));
+    assert!(tree.remove(&50_000));
+    assert!(tree.remove(&0));
+    assert_eq!(
+        (tree.len(), tree.min(), tree.max()),
+        (49_999, Some(&1), Some(&49_999))
+    );
+    assert_eq!(tree.iter().nth(12_344), Some(&12_345));
+    let dot = tree.to_dot();
+    // each node but the last has an invisible left child
+    assert!(dot.contains("    n99996 [label=\"49999\"];\n"));
+    assert!(dot.ends_with("    n2 -> n4;\n    n0 -> n2;\n}\n"));
+    drop(tree);
+
+    let mut taken = BinaryTree::from(list()).into_iter();
+    assert_eq!(taken.next(), Some(0));
+    drop(taken);
+    assert_eq!(BinaryTree::from(list()).into_post_order().count(), 50_000);
+}
+
+#[test]
+fn test_from_tree() {
+    let leaf = |element| {
+        Tree::NonEmpty(Box::new(TreeNode {
+            element,
+            left: Tree::Empty,
+            right: Tree::Empty,
+        }))
+    };
+    let mut tree = BinaryTree::from(Tree::NonEmpty(Box::new(TreeNode {
+        element: 2,
+        left: leaf(1),
+        right: leaf(3),
+    })));
+    assert_eq!((tree.len(), tree.height()), (3, 2));
+    assert!(tree.add(4));
+    assert!(tree.remove(&2));
+    assert_eq!(tree.iter().copied().collect::<Vec<_>>(), [1, 3, 4]);
+    assert!(BinaryTree::<i32>::from(Tree::Empty).is_empty());
+}
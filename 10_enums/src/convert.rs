@@ -0,0 +1,352 @@
+// Conversions between Json and Rust values, and the json! macro to write documents inline
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::json::Json;
+
+/// A Json value that is not what the Rust type needs, `path` is a JSON pointer to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvertError {
+    pub message: String,
+    pub path: String,
+}
+
+impl ConvertError {
+    pub fn new(message: impl Into<String>) -> Self {
+        ConvertError {
+            message: message.into(),
+            path: String::new(),
+        }
+    }
+
+    fn expected(expected: &str, found: &Json) -> Self {
+        ConvertError::new(format!("expected {}, found {}", expected, found.kind()))
+    }
+
+    /// The same error, one level deeper: `/name` becomes `/3/name` when it happened in element 3.
+    pub(crate) fn inside(mut self, token: &str) -> Self {
+        let token = token.replace('~', "~0").replace('/', "~1");
+        self.path = format!("/{}{}", token, self.path);
+        self
+    }
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{} at {}", self.message, self.path)
+        }
+    }
+}
+
+impl Error for ConvertError {}
+
+impl Json {
+    /// What kind of value this is, with an article, for messages.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Json::Null => "null",
+            Json::Bool(_) => "a boolean",
+            Json::Number(_) => "a number",
+            Json::String(_) => "a string",
+            Json::Array(_) => "an array",
+            Json::Object(_) => "an object",
+        }
+    }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Json {
+        Json::Bool(b)
+    }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Json {
+        Json::String(s)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Json {
+        Json::String(s.to_string())
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(elements: Vec<T>) -> Json {
+        Json::Array(elements.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<Json>> From<HashMap<String, T>> for Json {
+    fn from(members: HashMap<String, T>) -> Json {
+        Json::Object(Box::new(
+            members.into_iter().map(|(k, v)| (k, v.into())).collect(),
+        ))
+    }
+}
+
+/// `None` is null.
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Json {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+// JSON has only one kind of number
+macro_rules! from_number {
+    ($($t:ty)*) => {
+        $(
+            impl From<$t> for Json {
+                fn from(n: $t) -> Json {
+                    Json::Number(n as f64)
+                }
+            }
+        )*
+    };
+}
+
+from_number!(f64 f32 i8 i16 i32 i64 isize u8 u16 u32 u64 usize);
+
+impl TryFrom<&Json> for bool {
+    type Error = ConvertError;
+    fn try_from(json: &Json) -> Result<bool, ConvertError> {
+        match json {
+            Json::Bool(b) => Ok(*b),
+            other => Err(ConvertError::expected("a boolean", other)),
+        }
+    }
+}
+
+impl TryFrom<&Json> for f64 {
+    type Error = ConvertError;
+    fn try_from(json: &Json) -> Result<f64, ConvertError> {
+        match json {
+            Json::Number(n) => Ok(*n),
+            other => Err(ConvertError::expected("a number", other)),
+        }
+    }
+}
+
+impl TryFrom<&Json> for String {
+    type Error = ConvertError;
+    fn try_from(json: &Json) -> Result<String, ConvertError> {
+        match json {
+            Json::String(s) => Ok(s.clone()),
+            other => Err(ConvertError::expected("a string", other)),
+        }
+    }
+}
+
+// Integers must be whole numbers in range, 2.5 is not silently 2
+macro_rules! try_from_integer {
+    ($($t:ty)*) => {
+        $(
+            impl TryFrom<&Json> for $t {
+                type Error = ConvertError;
+                fn try_from(json: &Json) -> Result<$t, ConvertError> {
+                    let n = f64::try_from(json)?;
+                    if n.fract() != 0.0 {
+                        return Err(ConvertError::new(format!("{} is not an integer", n)));
+                    }
+                    // MAX is 2^k - 1, which f64 cannot hold for 64 bits and rounds up to 2^k:
+                    // compare against 2^k itself, the first value out of range
+                    let end = <$t>::MAX as f64 + 1.0;
+                    if n < <$t>::MIN as f64 || n >= end {
+                        return Err(ConvertError::new(format!(
+                            "{} is out of range for {}",
+                            n,
+                            stringify!($t)
+                        )));
+                    }
+                    Ok(n as $t)
+                }
+            }
+        )*
+    };
+}
+
+try_from_integer!(i32 i64 u8 u32 u64 usize);
+
+impl<T> TryFrom<&Json> for Vec<T>
+where
+    T: for<'a> TryFrom<&'a Json, Error = ConvertError>,
+{
+    type Error = ConvertError;
+    fn try_from(json: &Json) -> Result<Vec<T>, ConvertError> {
+        match json {
+            Json::Array(elements) => elements
+                .iter()
+                .enumerate()
+                .map(|(i, element)| T::try_from(element).map_err(|e| e.inside(&i.to_string())))
+                .collect(),
+            other => Err(ConvertError::expected("an array", other)),
+        }
+    }
+}
+
+impl<T> TryFrom<&Json> for HashMap<String, T>
+where
+    T: for<'a> TryFrom<&'a Json, Error = ConvertError>,
+{
+    type Error = ConvertError;
+    fn try_from(json: &Json) -> Result<HashMap<String, T>, ConvertError> {
+        match json {
+            Json::Object(members) => members
+                .iter()
+                .map(|(key, value)| match T::try_from(value) {
+                    Ok(value) => Ok((key.clone(), value)),
+                    Err(e) => Err(e.inside(key)),
+                })
+                .collect(),
+            other => Err(ConvertError::expected("an object", other)),
+        }
+    }
+}
+
+/// A Json value written like JSON, with Rust expressions where values go:
+///
+/// ```ignore
+/// let moons = 2;
+/// json!({ "name": "Mars", "moons": moons, "position": [1.52, -0.5], "rings": null })
+/// ```
+///
+/// Values are converted with `Json::from`, keys can be any expression giving a `&str` or `String`.
+macro_rules! json {
+    // the elements of an array, one at a time, up to the next comma
+    (@array [$($elements:expr,)*]) => {
+        vec![$($elements,)*]
+    };
+    (@array [$($elements:expr,)*] null $(, $($rest:tt)*)?) => {
+        json!(@array [$($elements,)* $crate::json::Json::Null,] $($($rest)*)?)
+    };
+    (@array [$($elements:expr,)*] [$($array:tt)*] $(, $($rest:tt)*)?) => {
+        json!(@array [$($elements,)* json!([$($array)*]),] $($($rest)*)?)
+    };
+    (@array [$($elements:expr,)*] {$($object:tt)*} $(, $($rest:tt)*)?) => {
+        json!(@array [$($elements,)* json!({$($object)*}),] $($($rest)*)?)
+    };
+    (@array [$($elements:expr,)*] $next:expr $(, $($rest:tt)*)?) => {
+        json!(@array [$($elements,)* json!($next),] $($($rest)*)?)
+    };
+
+    // the members of an object, inserted one at a time into `$object`
+    (@object $object:ident) => {};
+    (@object $object:ident $key:tt : null $(, $($rest:tt)*)?) => {
+        $object.insert(($key).to_string(), $crate::json::Json::Null);
+        json!(@object $object $($($rest)*)?);
+    };
+    (@object $object:ident $key:tt : [$($array:tt)*] $(, $($rest:tt)*)?) => {
+        $object.insert(($key).to_string(), json!([$($array)*]));
+        json!(@object $object $($($rest)*)?);
+    };
+    (@object $object:ident $key:tt : {$($inner:tt)*} $(, $($rest:tt)*)?) => {
+        $object.insert(($key).to_string(), json!({$($inner)*}));
+        json!(@object $object $($($rest)*)?);
+    };
+    (@object $object:ident $key:tt : $value:expr $(, $($rest:tt)*)?) => {
+        $object.insert(($key).to_string(), json!($value));
+        json!(@object $object $($($rest)*)?);
+    };
+
+    (null) => {
+        $crate::json::Json::Null
+    };
+    ([$($elements:tt)*]) => {
+        $crate::json::Json::Array(json!(@array [] $($elements)*))
+    };
+    ({$($members:tt)*}) => {{
+        #[allow(unused_mut)]
+        let mut object = ::std::collections::HashMap::new();
+        json!(@object object $($members)*);
+        $crate::json::Json::Object(Box::new(object))
+    }};
+    ($other:expr) => {
+        $crate::json::Json::from($other)
+    };
+}
+
+#[test]
+fn test_from() {
+    assert_eq!(Json::from(true), Json::Bool(true));
+    assert_eq!(Json::from(3), Json::Number(3.0));
+    assert_eq!(Json::from(2.5f32), Json::Number(2.5));
+    assert_eq!(Json::from("a"), Json::String("a".to_string()));
+    assert_eq!(Json::from(None::<bool>), Json::Null);
+    assert_eq!(Json::from(vec![1, 2]).to_string(), "[1,2]");
+    let mut map = HashMap::new();
+    map.insert("k".to_string(), vec![Some("v"), None]);
+    assert_eq!(Json::from(map).to_string(), r#"{"k":["v",null]}"#);
+}
+
+#[test]
+fn test_try_from() {
+    let doc = Json::parse(r#"{"a": [1, 2, 3], "b": {"x": true}, "c": 2.5, "d": -1}"#).unwrap();
+    assert_eq!(Vec::<u32>::try_from(&doc["a"]), Ok(vec![1, 2, 3]));
+    assert_eq!(f64::try_from(&doc["c"]), Ok(2.5));
+    assert_eq!(i64::try_from(&doc["d"]), Ok(-1));
+    let b = HashMap::<String, bool>::try_from(&doc["b"]).unwrap();
+    assert!(b["x"]);
+
+    let error = String::try_from(&doc["a"]).unwrap_err();
+    assert_eq!(error.to_string(), "expected a string, found an array");
+    assert_eq!(
+        u32::try_from(&doc["c"]).unwrap_err().to_string(),
+        "2.5 is not an integer"
+    );
+    assert_eq!(
+        u64::try_from(&doc["d"]).unwrap_err().to_string(),
+        "-1 is out of range for u64"
+    );
+    // the edges, where MAX as f64 is rounded up to one past MAX
+    let number = |n: f64| Json::Number(n);
+    assert_eq!(i64::try_from(&number(-9223372036854775808.0)), Ok(i64::MIN));
+    assert!(i64::try_from(&number(9223372036854775808.0)).is_err());
+    assert_eq!(
+        i64::try_from(&number(9223372036854774784.0)),
+        Ok(9223372036854774784)
+    );
+    assert!(u64::try_from(&number(18446744073709551616.0)).is_err());
+    assert_eq!(
+        u64::try_from(&number(18446744073709549568.0)),
+        Ok(18446744073709549568)
+    );
+    assert!(usize::try_from(&number(18446744073709551616.0)).is_err());
+    assert_eq!(u8::try_from(&number(255.0)), Ok(255));
+    assert!(u8::try_from(&number(256.0)).is_err());
+    assert!(i32::try_from(&number(2147483648.0)).is_err());
+    let nested = Json::parse(r#"{"list": [[1], [2, "x"]]}"#).unwrap();
+    let error = HashMap::<String, Vec<Vec<u8>>>::try_from(&nested).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "expected a number, found a string at /list/1/1"
+    );
+    assert_eq!(nested.get(&error.path), Some(&Json::from("x")));
+}
+
+#[test]
+fn test_json_macro() {
+    let moons = vec!["Phobos", "Deimos"];
+    let key = "computed";
+    let doc = json!({
+        "name": "Mars",
+        "moons": moons,
+        "position": [1.52, -0.5, [], {}],
+        "rings": null,
+        "nested": { "a": [null, true, 1 + 2], "b": {"c": false} },
+        key: format!("{}!", "yes"),
+    });
+    assert_eq!(
+        doc.to_string(),
+        r#"{"computed":"yes!","moons":["Phobos","Deimos"],"name":"Mars","nested":{"a":[null,true,3],"b":{"c":false}},"position":[1.52,-0.5,[],{}],"rings":null}"#
+    );
+    assert_eq!(json!(null), Json::Null);
+    assert_eq!(json!([]), Json::Array(vec![]));
+    assert_eq!(json!({}), Json::Object(Box::default()));
+    assert_eq!(json!(-7), Json::Number(-7.0));
+    assert_eq!(json!([1, 2,]), json!([1, 2]));
+}
@@ -0,0 +1,614 @@
+// A JSON document as an enum, with a recursive-descent parser and a serializer
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    #[allow(clippy::box_collection)]
+    Object(Box<HashMap<String, Json>>), // Using Box to void large size
+}
+
+/// Where and why parsing failed, `line` and `column` start at 1 and count characters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.message, self.line, self.column
+        )
+    }
+}
+
+impl Error for JsonError {}
+
+/// Arrays and objects nested deeper than this are refused instead of overflowing the stack.
+pub(crate) const MAX_DEPTH: usize = 256;
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: impl Into<String>) -> JsonError {
+        self.error_at(self.pos, message)
+    }
+
+    fn error_at(&self, pos: usize, message: impl Into<String>) -> JsonError {
+        let before = &self.text[..pos];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        JsonError {
+            message: message.into(),
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    /// What was found instead of what was expected, for error messages.
+    fn found(&self) -> String {
+        match self.text[self.pos..].chars().next() {
+            Some(c) => format!("'{}'", c.escape_default()),
+            None => "end of input".to_string(),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        // only these four, JSON does not know about other Unicode spaces
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), JsonError> {
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(format!(
+                "expected '{}', found {}",
+                byte as char,
+                self.found()
+            )))
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, JsonError> {
+        if self.text[self.pos..].starts_with(word) {
+            self.pos += word.len();
+            Ok(value)
+        } else {
+            Err(self.error(format!("expected a value, found {}", self.found())))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, JsonError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(b't') => self.literal("true", Json::Bool(true)),
+            Some(b'f') => self.literal("false", Json::Bool(false)),
+            Some(b'"') => Ok(Json::String(self.string()?)),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(b'[') => self.nested(Parser::array),
+            Some(b'{') => self.nested(Parser::object),
+            _ => Err(self.error(format!("expected a value, found {}", self.found()))),
+        }
+    }
+
+    fn nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<Json, JsonError>,
+    ) -> Result<Json, JsonError> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error(format!("nested deeper than {} levels", MAX_DEPTH)));
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn array(&mut self) -> Result<Json, JsonError> {
+        self.expect(b'[')?;
+        let mut elements = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Json::Array(elements));
+        }
+        loop {
+            elements.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Json::Array(elements));
+                }
+                _ => return Err(self.error(format!("expected ',' or ']', found {}", self.found()))),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, JsonError> {
+        self.expect(b'{')?;
+        let mut members = HashMap::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(Json::Object(Box::new(members)));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.error(format!("expected a key, found {}", self.found())));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(b':')?;
+            // like most parsers, the last of several equal keys wins
+            members.insert(key, self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Json::Object(Box::new(members)));
+                }
+                _ => {
+                    return Err(self.error(format!("expected ',' or '}}', found {}", self.found())))
+                }
+            }
+        }
+    }
+
+    fn digits(&mut self) -> usize {
+        let start = self.pos;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+
+    /// `-? (0 | [1-9][0-9]*) (. [0-9]+)? ([eE] [+-]? [0-9]+)?`, nothing more lenient.
+    fn number(&mut self) -> Result<Json, JsonError> {
+        let start = self.pos;
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some(b'0') => {
+                self.pos += 1;
+                if let Some(b'0'..=b'9') = self.peek() {
+                    return Err(self.error("leading zeros are not allowed"));
+                }
+            }
+            Some(b'1'..=b'9') => {
+                self.digits();
+            }
+            _ => return Err(self.error(format!("expected a digit, found {}", self.found()))),
+        }
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            if self.digits() == 0 {
+                return Err(self.error("expected a digit after the decimal point"));
+            }
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            self.pos += 1;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.pos += 1;
+            }
+            if self.digits() == 0 {
+                return Err(self.error("expected a digit in the exponent"));
+            }
+        }
+        let number: f64 = self.text[start..self.pos].parse().expect("checked above");
+        if number.is_infinite() {
+            return Err(self.error_at(start, "number out of range"));
+        }
+        Ok(Json::Number(number))
+    }
+
+    fn hex4(&mut self) -> Result<u32, JsonError> {
+        let digits = self.text.get(self.pos..self.pos + 4).unwrap_or("");
+        match u32::from_str_radix(digits, 16) {
+            Ok(code) if digits.bytes().all(|b| b.is_ascii_hexdigit()) => {
+                self.pos += 4;
+                Ok(code)
+            }
+            _ => Err(self.error("expected 4 hexadecimal digits")),
+        }
+    }
+
+    /// `\uXXXX`, or two of them for a character outside the Basic Multilingual Plane.
+    fn unicode_escape(&mut self, escape_start: usize) -> Result<char, JsonError> {
+        let first = self.hex4()?;
+        let code = match first {
+            0xD800..=0xDBFF => {
+                if !self.text[self.pos..].starts_with("\\u") {
+                    return Err(self.error_at(escape_start, "unpaired surrogate in \\u escape"));
+                }
+                self.pos += 2;
+                let second = self.hex4()?;
+                if !(0xDC00..=0xDFFF).contains(&second) {
+                    return Err(self.error_at(escape_start, "unpaired surrogate in \\u escape"));
+                }
+                0x10000 + ((first - 0xD800) << 10) + (second - 0xDC00)
+            }
+            0xDC00..=0xDFFF => {
+                return Err(self.error_at(escape_start, "unpaired surrogate in \\u escape"))
+            }
+            code => code,
+        };
+        Ok(char::from_u32(code).expect("surrogates are handled above"))
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.expect(b'"')?;
+        let mut string = String::new();
+        loop {
+            // copy the run of plain characters in one go
+            let rest = &self.text[self.pos..];
+            let run = rest
+                .find(|c: char| c == '"' || c == '\\' || c < ' ')
+                .unwrap_or(rest.len());
+            string.push_str(&rest[..run]);
+            self.pos += run;
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(string);
+                }
+                Some(b'\\') => {
+                    let escape_start = self.pos;
+                    self.pos += 1;
+                    let c = match self.peek() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            self.pos += 1;
+                            string.push(self.unicode_escape(escape_start)?);
+                            continue;
+                        }
+                        _ => {
+                            return Err(self.error_at(
+                                escape_start,
+                                format!("invalid escape '\\{}'", self.found().trim_matches('\'')),
+                            ))
+                        }
+                    };
+                    self.pos += 1;
+                    string.push(c);
+                }
+                Some(_) => return Err(self.error("control characters must be escaped in strings")),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+}
+
+/// Write `s` as a JSON string, quotes included.
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            c if c < ' ' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// The shortest text that reads back as the same `f64`.
+fn write_number(out: &mut String, n: f64) {
+    if !n.is_finite() {
+        // JSON has no NaN or infinity, JavaScript's JSON.stringify writes null too
+        out.push_str("null");
+    } else if n.fract() == 0.0 && n.abs() < 1e15 {
+        // 2 rather than 2.0
+        out.push_str(&format!("{}", n as i64));
+    } else {
+        // Debug switches to exponents for very large and very small numbers, Display does not
+        out.push_str(&format!("{:?}", n));
+    }
+}
+
+impl Json {
+    /// Parse a whole document, anything but whitespace after the value is an error.
+    pub fn parse(text: &str) -> Result<Json, JsonError> {
+        let mut parser = Parser {
+            text,
+            pos: 0,
+            depth: 0,
+        };
+        // a byte order mark is not JSON, but is common enough in files
+        if text.starts_with('\u{feff}') {
+            parser.pos = '\u{feff}'.len_utf8();
+        }
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos < text.len() {
+            return Err(parser.error(format!("unexpected {} after the document", parser.found())));
+        }
+        Ok(value)
+    }
+
+    /// On one line, without any space.
+    pub fn to_string_compact(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out, None, 0);
+        out
+    }
+
+    /// One element or member per line, indented by two spaces per level.
+    pub fn to_string_pretty(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out, Some(2), 0);
+        out
+    }
+
+    /// Object keys are written sorted, so the same document always gives the same text.
+    fn write(&self, out: &mut String, indent: Option<usize>, level: usize) {
+        let newline = |out: &mut String, level: usize| {
+            if let Some(width) = indent {
+                out.push('\n');
+                out.push_str(&" ".repeat(width * level));
+            }
+        };
+        match self {
+            Json::Null => out.push_str("null"),
+            Json::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            Json::Number(n) => write_number(out, *n),
+            Json::String(s) => write_string(out, s),
+            Json::Array(elements) if elements.is_empty() => out.push_str("[]"),
+            Json::Array(elements) => {
+                out.push('[');
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    newline(out, level + 1);
+                    element.write(out, indent, level + 1);
+                }
+                newline(out, level);
+                out.push(']');
+            }
+            Json::Object(members) if members.is_empty() => out.push_str("{}"),
+            Json::Object(members) => {
+                let mut keys: Vec<&String> = members.keys().collect();
+                keys.sort();
+                out.push('{');
+                for (i, key) in keys.into_iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    newline(out, level + 1);
+                    write_string(out, key);
+                    out.push_str(if indent.is_some() { ": " } else { ":" });
+                    members[key].write(out, indent, level + 1);
+                }
+                newline(out, level);
+                out.push('}');
+            }
+        }
+    }
+}
+
+/// Compact with `{}`, pretty with `{:#}`.
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            f.write_str(&self.to_string_pretty())
+        } else {
+            f.write_str(&self.to_string_compact())
+        }
+    }
+}
+
+// Named after the files of JSONTestSuite (github.com/nst/JSONTestSuite):
+// y_ must be accepted, n_ must be refused.
+#[test]
+fn test_suite_accepted() {
+    let accepted = [
+        ("y_array_empty", "[]"),
+        ("y_array_with_leading_space", " [1]"),
+        ("y_array_heterogeneous", r#"[null, 1, "1", {}]"#),
+        ("y_number_0e+1", "[0e+1]"),
+        ("y_number_minus_zero", "[-0]"),
+        ("y_number_real_capital_e_neg_exp", "[1E-2]"),
+        ("y_number_after_space", "[ 4]"),
+        (
+            "y_number_very_big_negative_int",
+            "[-237462374673276894279832749832423479823246327846]",
+        ),
+        ("y_object_duplicated_key", r#"{"a":"b","a":"c"}"#),
+        ("y_object_empty_key", r#"{"":0}"#),
+        ("y_object_with_newlines", "{\n\"a\": \"b\"\n}"),
+        ("y_string_allowed_escapes", r#"["\"\\\/\b\f\n\r\t"]"#),
+        ("y_string_escaped_null", r#"["a\u0000b"]"#),
+        (
+            "y_string_surrogates_U+1D11E_MUSICAL_SYMBOL_G_CLEF",
+            r#"["\uD834\uDd1e"]"#,
+        ),
+        ("y_string_utf8", "[\"€𝄞\"]"),
+        ("y_string_unescaped_del", "[\"\u{7f}\"]"),
+        ("y_structure_lonely_null", "null"),
+        ("y_structure_lonely_string", r#""asd""#),
+        ("y_structure_trailing_newline", "[\"a\"]\n"),
+        ("y_structure_whitespace_array", " [] "),
+    ];
+    for (name, text) in accepted {
+        assert!(
+            Json::parse(text).is_ok(),
+            "{}: {:?}",
+            name,
+            Json::parse(text)
+        );
+    }
+}
+
+#[test]
+fn test_suite_refused() {
+    let refused = [
+        ("n_array_comma_after_close", "[\"\"],"),
+        ("n_array_extra_comma", "[\"\",]"),
+        ("n_array_missing_value", "[   , \"\"]"),
+        ("n_array_unclosed", "[\"\""),
+        ("n_incomplete_false", "[fals]"),
+        ("n_incomplete_null", "[nul]"),
+        ("n_number_++", "[++1234]"),
+        ("n_number_-01", "[-01]"),
+        ("n_number_0.e1", "[0.e1]"),
+        ("n_number_2.e3", "[2.e3]"),
+        ("n_number_hex_1_digit", "[0x1]"),
+        ("n_number_infinity", "[Infinity]"),
+        ("n_number_NaN", "[NaN]"),
+        ("n_number_neg_with_garbage_at_end", "[-1x]"),
+        ("n_number_plus_1", "[+1]"),
+        ("n_number_starting_with_dot", "[.123]"),
+        ("n_number_huge_exp", "[1e400]"),
+        ("n_object_missing_colon", r#"{"a" b}"#),
+        ("n_object_non_string_key", "{1:1}"),
+        ("n_object_single_quote", "{'a':0}"),
+        ("n_object_trailing_comma", r#"{"id":0,}"#),
+        ("n_single_space", " "),
+        ("n_string_1_surrogate_then_escape", r#"["\uD800\"]"#),
+        (
+            "n_string_incomplete_surrogate_escape_invalid",
+            r#"["\uD800\uD800\x"]"#,
+        ),
+        ("n_string_invalid_backslash_esc", r#"["\a"]"#),
+        ("n_string_lone_second_surrogate", r#"["\uDFAA"]"#),
+        ("n_string_unescaped_tab", "[\"\t\"]"),
+        ("n_string_unescaped_newline", "[\"new\nline\"]"),
+        ("n_string_single_quote", "['single quote']"),
+        ("n_structure_close_unopened_array", "1]"),
+        ("n_structure_double_array", "[][]"),
+        ("n_structure_unclosed_object", r#"{"asd":"asd""#),
+        ("n_structure_whitespace_formfeed", "[\u{c}]"),
+    ];
+    for (name, text) in refused {
+        assert!(Json::parse(text).is_err(), "{} was accepted", name);
+    }
+}
+
+#[test]
+fn test_deep_nesting_is_an_error() {
+    let deep = "[".repeat(100_000);
+    let error = Json::parse(&deep).unwrap_err();
+    assert!(error.message.contains("nested deeper"), "{}", error);
+    let fine = format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+    assert!(Json::parse(&fine).is_ok());
+}
+
+#[test]
+fn test_error_positions() {
+    let error = Json::parse("{\n  \"a\": [1, 2,, 3]\n}").unwrap_err();
+    assert_eq!((error.line, error.column), (2, 14));
+    assert_eq!(error.message, "expected a value, found ','");
+    assert_eq!(
+        error.to_string(),
+        "expected a value, found ',' at line 2, column 14"
+    );
+    // columns count characters, not bytes
+    let error = Json::parse("[\"é\", x]").unwrap_err();
+    assert_eq!((error.line, error.column), (1, 7));
+    let error = Json::parse("[\"a\\qb\"]").unwrap_err();
+    assert_eq!(
+        (error.column, error.message.as_str()),
+        (4, "invalid escape '\\q'")
+    );
+    let error = Json::parse("[1").unwrap_err();
+    assert_eq!(error.message, "expected ',' or ']', found end of input");
+}
+
+#[test]
+fn test_strings() {
+    let parsed = Json::parse(r#""tab\t quote\" é 😀 \u0001""#).unwrap();
+    assert_eq!(parsed, Json::String("tab\t quote\" é 😀 \u{1}".to_string()));
+    assert_eq!(parsed.to_string_compact(), r#""tab\t quote\" é 😀 \u0001""#);
+}
+
+#[test]
+fn test_numbers() {
+    for (text, written) in [
+        ("0", "0"),
+        ("-0", "0"),
+        ("2.0", "2"),
+        ("1.5", "1.5"),
+        ("1e3", "1000"),
+        ("-12.5e-1", "-1.25"),
+        ("1e300", "1e300"),
+        ("0.000001", "1e-6"),
+        ("123456789012345678", "1.2345678901234568e17"),
+    ] {
+        let parsed = Json::parse(text).unwrap();
+        assert_eq!(parsed.to_string_compact(), written, "{}", text);
+        assert_eq!(Json::parse(written).unwrap(), parsed);
+    }
+    assert_eq!(Json::Number(f64::NAN).to_string_compact(), "null");
+}
+
+#[test]
+fn test_serialize() {
+    let text = r#"{"c": ["hello", 2, 10.5, {}], "b": "hello", "a": 1, "n": null, "B": [true, []]}"#;
+    let json = Json::parse(text).unwrap();
+    assert_eq!(
+        json.to_string_compact(),
+        r#"{"B":[true,[]],"a":1,"b":"hello","c":["hello",2,10.5,{}],"n":null}"#
+    );
+    let pretty = "\
+{
+  \"B\": [
+    true,
+    []
+  ],
+  \"a\": 1,
+  \"b\": \"hello\",
+  \"c\": [
+    \"hello\",
+    2,
+    10.5,
+    {}
+  ],
+  \"n\": null
+}";
+    assert_eq!(json.to_string_pretty(), pretty);
+    assert_eq!(format!("{:#}", json), pretty);
+    assert_eq!(format!("{}", json), json.to_string_compact());
+    // what is written reads back the same, both ways
+    assert_eq!(Json::parse(pretty).unwrap(), json);
+    assert_eq!(Json::parse(&json.to_string_compact()).unwrap(), json);
+}
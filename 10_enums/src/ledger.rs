@@ -0,0 +1,729 @@
+// Accounts kept by a ledger: every change is a Transaction, checked, applied and written to an
+// append-only log that rebuilds the same balances when replayed
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::serde_bridge;
+use crate::stream::JsonReader;
+
+/// An amount in cents: sums of money must not pick up the rounding errors of `f64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Money(i64);
+
+/// A calendar date, checked when made: there is no 2023-02-29.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    year: i32,
+    month: u8,
+    day: u8,
+}
+
+/// Text that is not an amount or a date.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    input: String,
+    message: String,
+}
+
+impl ParseError {
+    fn new(input: &str, message: impl Into<String>) -> Self {
+        ParseError {
+            input: input.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "'{}': {}", self.input, self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+
+    pub fn from_cents(cents: i64) -> Self {
+        Money(cents)
+    }
+
+    pub fn cents(self) -> i64 {
+        self.0
+    }
+
+    pub fn checked_add(self, other: Money) -> Option<Money> {
+        self.0.checked_add(other.0).map(Money)
+    }
+
+    pub fn checked_sub(self, other: Money) -> Option<Money> {
+        self.0.checked_sub(other.0).map(Money)
+    }
+}
+
+/// Always with two decimals, `-3.05`.
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let cents = self.0.unsigned_abs();
+        write!(f, "{}{}.{:02}", sign, cents / 100, cents % 100)
+    }
+}
+
+/// `12`, `12.5` or `-12.50`, but not `12.505`: a cent is the smallest amount.
+impl FromStr for Money {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Money, ParseError> {
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let (units, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        let all_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if units.is_empty() || !all_digits(units) || !all_digits(fraction) {
+            return Err(ParseError::new(s, "expected an amount like 12.50"));
+        }
+        if fraction.len() > 2 || (digits.contains('.') && fraction.is_empty()) {
+            return Err(ParseError::new(s, "expected one or two decimals"));
+        }
+        let cents = format!("{}{:0<2}", units, fraction)
+            .parse::<i64>()
+            .map_err(|_| ParseError::new(s, "amount out of range"))?;
+        Ok(Money(if negative { -cents } else { cents }))
+    }
+}
+
+fn days_in_month(year: i32, month: u8) -> u8 {
+    match month {
+        4 | 6 | 9 | 11 => 30,
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        _ => 31,
+    }
+}
+
+impl Date {
+    /// `None` unless the day exists, in a year of at most four digits: the log writes dates
+    /// as text, and must read back every date it writes.
+    pub fn new(year: i32, month: u8, day: u8) -> Option<Date> {
+        if !(0..=9999).contains(&year)
+            || !(1..=12).contains(&month)
+            || day == 0
+            || day > days_in_month(year, month)
+        {
+            return None;
+        }
+        Some(Date { year, month, day })
+    }
+}
+
+/// ISO 8601, `1990-01-31`.
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl FromStr for Date {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Date, ParseError> {
+        let parts: Vec<&str> = s.split('-').collect();
+        let [year, month, day] = parts[..] else {
+            return Err(ParseError::new(s, "expected a date like 1990-01-31"));
+        };
+        let digits = |part: &str, len: usize| {
+            if part.len() == len && part.bytes().all(|b| b.is_ascii_digit()) {
+                part.parse::<u16>().ok()
+            } else {
+                None
+            }
+        };
+        match (digits(year, 4), digits(month, 2), digits(day, 2)) {
+            (Some(year), Some(month), Some(day)) => Date::new(year as i32, month as u8, day as u8)
+                .ok_or_else(|| ParseError::new(s, "no such day")),
+            _ => Err(ParseError::new(s, "expected a date like 1990-01-31")),
+        }
+    }
+}
+
+// Amounts and dates are written as text in the log, the way people read them
+macro_rules! serde_as_string {
+    ($($t:ty)*) => {
+        $(
+            impl Serialize for $t {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.collect_str(self)
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $t {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<$t, D::Error> {
+                    String::deserialize(deserializer)?
+                        .parse()
+                        .map_err(serde::de::Error::custom)
+                }
+            }
+        )*
+    };
+}
+
+serde_as_string!(Money Date);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Account {
+    pub name: String,
+    pub lang: String,
+    pub id: usize,
+    pub balance: Money,
+    pub birthday: Date,
+    pub eye_color: String,
+    /// how far below zero the balance may go
+    pub overdraft_limit: Money,
+}
+
+impl Account {
+    /// Whether the overdraft limit lets the balance go down to `balance`.
+    /// `i64::MIN` cents has no negation, but it is refused at `Open` with any negative limit.
+    fn allows(&self, balance: Money) -> bool {
+        self.overdraft_limit
+            .cents()
+            .checked_neg()
+            .is_some_and(|floor| balance.cents() >= floor)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Transaction {
+    /// A new account, its balance is the opening balance.
+    Open(Account),
+    Deposit {
+        account: usize,
+        amount: Money,
+    },
+    Withdraw {
+        account: usize,
+        amount: Money,
+    },
+    Transfer {
+        from: usize,
+        to: usize,
+        amount: Money,
+    },
+}
+
+/// A transaction that was applied, numbered from 0 in the order of the log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub sequence: usize,
+    pub date: Date,
+    pub transaction: Transaction,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LedgerError {
+    UnknownAccount(usize),
+    DuplicateAccount(usize),
+    /// Amounts moved must be more than zero.
+    InvalidAmount(Money),
+    SameAccount(usize),
+    /// An overdraft limit is how far below zero a balance may go, so it is zero or more.
+    NegativeLimit {
+        account: usize,
+        limit: Money,
+    },
+    Overdraft {
+        account: usize,
+        balance: Money,
+        amount: Money,
+        limit: Money,
+    },
+    Overflow(usize),
+    /// The log goes on in date order.
+    DateBeforeLast {
+        date: Date,
+        last: Date,
+    },
+    /// A log that cannot be read or replayed as it is.
+    BadLog {
+        entry: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for LedgerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LedgerError::UnknownAccount(id) => write!(f, "no account {}", id),
+            LedgerError::DuplicateAccount(id) => write!(f, "account {} is already open", id),
+            LedgerError::InvalidAmount(amount) => {
+                write!(f, "{} is not an amount that can be moved", amount)
+            }
+            LedgerError::SameAccount(id) => write!(f, "cannot transfer from account {} to itself", id),
+            LedgerError::NegativeLimit { account, limit } => {
+                write!(f, "account {} cannot have a negative overdraft limit of {}", account, limit)
+            }
+            LedgerError::Overdraft {
+                account,
+                balance,
+                amount,
+                limit,
+            } => write!(
+                f,
+                "taking {} from account {} would bring its balance of {} below the overdraft limit of {}",
+                amount, account, balance, limit
+            ),
+            LedgerError::Overflow(id) => write!(f, "the balance of account {} is too large", id),
+            LedgerError::DateBeforeLast { date, last } => {
+                write!(f, "{} is before the last entry, on {}", date, last)
+            }
+            LedgerError::BadLog { entry, message } => write!(f, "log entry {}: {}", entry, message),
+        }
+    }
+}
+
+impl Error for LedgerError {}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Ledger {
+    accounts: BTreeMap<usize, Account>,
+    log: Vec<LogEntry>,
+}
+
+impl Ledger {
+    pub fn new() -> Self {
+        Ledger::default()
+    }
+
+    pub fn account(&self, id: usize) -> Result<&Account, LedgerError> {
+        self.accounts
+            .get(&id)
+            .ok_or(LedgerError::UnknownAccount(id))
+    }
+
+    /// In order of id.
+    pub fn accounts(&self) -> impl Iterator<Item = &Account> {
+        self.accounts.values()
+    }
+
+    /// The balance `id` would have after taking `amount` out, if its overdraft limit allows it.
+    fn after_taking(&self, id: usize, amount: Money) -> Result<Money, LedgerError> {
+        let account = self.account(id)?;
+        let overdraft = LedgerError::Overdraft {
+            account: id,
+            balance: account.balance,
+            amount,
+            limit: account.overdraft_limit,
+        };
+        match account.balance.checked_sub(amount) {
+            Some(balance) if account.allows(balance) => Ok(balance),
+            _ => Err(overdraft),
+        }
+    }
+
+    fn after_adding(&self, id: usize, amount: Money) -> Result<Money, LedgerError> {
+        self.account(id)?
+            .balance
+            .checked_add(amount)
+            .ok_or(LedgerError::Overflow(id))
+    }
+
+    /// Check `transaction` against the accounts, then apply it and log it. If it fails
+    /// nothing changes and nothing is logged.
+    pub fn apply(&mut self, date: Date, transaction: Transaction) -> Result<(), LedgerError> {
+        if let Some(last) = self.log.last() {
+            if date < last.date {
+                return Err(LedgerError::DateBeforeLast {
+                    date,
+                    last: last.date,
+                });
+            }
+        }
+        let amount = match &transaction {
+            Transaction::Open(_) => None,
+            Transaction::Deposit { amount, .. }
+            | Transaction::Withdraw { amount, .. }
+            | Transaction::Transfer { amount, .. } => Some(*amount),
+        };
+        if let Some(amount) = amount.filter(|amount| *amount <= Money::ZERO) {
+            return Err(LedgerError::InvalidAmount(amount));
+        }
+        // every check first, so that a failure leaves no half-done transfer
+        let changes = match &transaction {
+            Transaction::Open(account) => {
+                if self.accounts.contains_key(&account.id) {
+                    return Err(LedgerError::DuplicateAccount(account.id));
+                }
+                if account.overdraft_limit < Money::ZERO {
+                    return Err(LedgerError::NegativeLimit {
+                        account: account.id,
+                        limit: account.overdraft_limit,
+                    });
+                }
+                if !account.allows(account.balance) {
+                    return Err(LedgerError::Overdraft {
+                        account: account.id,
+                        balance: Money::ZERO,
+                        amount: Money::ZERO.checked_sub(account.balance).unwrap_or_default(),
+                        limit: account.overdraft_limit,
+                    });
+                }
+                self.accounts.insert(account.id, account.clone());
+                vec![]
+            }
+            Transaction::Deposit { account, amount } => {
+                vec![(*account, self.after_adding(*account, *amount)?)]
+            }
+            Transaction::Withdraw { account, amount } => {
+                vec![(*account, self.after_taking(*account, *amount)?)]
+            }
+            Transaction::Transfer { from, to, amount } => {
+                if from == to {
+                    return Err(LedgerError::SameAccount(*from));
+                }
+                vec![
+                    (*from, self.after_taking(*from, *amount)?),
+                    (*to, self.after_adding(*to, *amount)?),
+                ]
+            }
+        };
+        for (id, balance) in changes {
+            if let Some(account) = self.accounts.get_mut(&id) {
+                account.balance = balance;
+            }
+        }
+        self.log.push(LogEntry {
+            sequence: self.log.len(),
+            date,
+            transaction,
+        });
+        Ok(())
+    }
+
+    /// Rebuild a ledger from its log. Entries must be numbered in order and each must apply.
+    pub fn replay(log: &[LogEntry]) -> Result<Ledger, LedgerError> {
+        let mut ledger = Ledger::new();
+        for (expected, entry) in log.iter().enumerate() {
+            if entry.sequence != expected {
+                return Err(LedgerError::BadLog {
+                    entry: expected,
+                    message: format!("numbered {}", entry.sequence),
+                });
+            }
+            ledger.apply(entry.date, entry.transaction.clone())?;
+        }
+        Ok(ledger)
+    }
+
+    /// The log as JSON Lines, one entry per line, so that new entries are appended to the file.
+    pub fn write_log(&self, out: &mut impl Write) -> io::Result<()> {
+        for entry in &self.log {
+            let json = serde_bridge::to_json(entry).map_err(io::Error::other)?;
+            writeln!(out, "{}", json)?;
+        }
+        Ok(())
+    }
+
+    /// Read a log written by `write_log`, an entry at a time.
+    pub fn read_log(input: impl BufRead) -> Result<Vec<LogEntry>, LedgerError> {
+        let mut reader = JsonReader::new(input);
+        let mut log = vec![];
+        loop {
+            let bad_log = |message: String| LedgerError::BadLog {
+                entry: log.len(),
+                message,
+            };
+            match reader.read_value() {
+                Ok(Some(json)) => {
+                    let entry =
+                        serde_bridge::from_json(json).map_err(|e| bad_log(e.to_string()))?;
+                    log.push(entry);
+                }
+                Ok(None) => return Ok(log),
+                Err(e) => return Err(bad_log(e.to_string())),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn account(id: usize, balance: &str, overdraft_limit: &str) -> Account {
+    Account {
+        name: format!("Holder {}", id),
+        lang: "en".to_string(),
+        id,
+        balance: balance.parse().unwrap(),
+        birthday: "1990-01-01".parse().unwrap(),
+        eye_color: "blue".to_string(),
+        overdraft_limit: overdraft_limit.parse().unwrap(),
+    }
+}
+
+#[test]
+fn test_money() {
+    for (text, cents, shown) in [
+        ("12", 1200, "12.00"),
+        ("12.5", 1250, "12.50"),
+        ("-0.05", -5, "-0.05"),
+        ("0.00", 0, "0.00"),
+        ("1234567.89", 123456789, "1234567.89"),
+    ] {
+        let money: Money = text.parse().unwrap();
+        assert_eq!(money.cents(), cents);
+        assert_eq!(money.to_string(), shown);
+    }
+    for bad in [
+        "",
+        "-",
+        "1.",
+        ".5",
+        "1.234",
+        "1,5",
+        "+1",
+        "1e3",
+        "99999999999999999999",
+    ] {
+        assert!(bad.parse::<Money>().is_err(), "{}", bad);
+    }
+    // the classic: 0.1 + 0.2 is exactly 0.3
+    let sum = Money::from_cents(10).checked_add(Money::from_cents(20));
+    assert_eq!(sum, Some("0.3".parse().unwrap()));
+    assert_eq!(
+        Money::from_cents(i64::MAX).checked_add(Money::from_cents(1)),
+        None
+    );
+}
+
+#[test]
+fn test_dates() {
+    let date: Date = "2024-02-29".parse().unwrap();
+    assert_eq!(date, Date::new(2024, 2, 29).unwrap());
+    assert_eq!(date.to_string(), "2024-02-29");
+    assert!(date < "2024-03-01".parse().unwrap());
+    assert_eq!(
+        "2023-02-29".parse::<Date>().unwrap_err().to_string(),
+        "'2023-02-29': no such day"
+    );
+    for bad in [
+        "1900-02-29",
+        "2024-04-31",
+        "2024-13-01",
+        "2024-00-10",
+        "24-01-01",
+        "2024-1-1",
+        "2024/01/01",
+        "",
+    ] {
+        assert!(bad.parse::<Date>().is_err(), "{}", bad);
+    }
+    assert!(Date::new(2000, 2, 29).is_some());
+    // the years that can be written are the years that can be read
+    for (year, month, day) in [(0, 1, 1), (9999, 12, 31)] {
+        let date = Date::new(year, month, day).unwrap();
+        assert_eq!(date.to_string().parse::<Date>(), Ok(date));
+    }
+    assert_eq!(Date::new(0, 1, 1).unwrap().to_string(), "0000-01-01");
+    assert_eq!(Date::new(10000, 1, 1), None);
+    assert_eq!(Date::new(-5, 1, 1), None);
+}
+
+#[test]
+fn test_overdraft_and_errors() {
+    let day: Date = "2024-01-10".parse().unwrap();
+    let mut ledger = Ledger::new();
+    ledger
+        .apply(day, Transaction::Open(account(1, "100", "0")))
+        .unwrap();
+    ledger
+        .apply(day, Transaction::Open(account(2, "0", "50")))
+        .unwrap();
+    let amount = |text: &str| text.parse::<Money>().unwrap();
+
+    let error = ledger
+        .apply(
+            day,
+            Transaction::Withdraw {
+                account: 1,
+                amount: amount("100.01"),
+            },
+        )
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "taking 100.01 from account 1 would bring its balance of 100.00 below the overdraft limit of 0.00"
+    );
+    // down to the limit is allowed
+    ledger
+        .apply(
+            day,
+            Transaction::Withdraw {
+                account: 2,
+                amount: amount("50"),
+            },
+        )
+        .unwrap();
+    assert_eq!(ledger.account(2).unwrap().balance, amount("-50"));
+
+    // a transfer that fails on either side changes nothing
+    let before: Vec<Account> = ledger.accounts().cloned().collect();
+    for (transaction, expected) in [
+        (
+            Transaction::Transfer {
+                from: 2,
+                to: 1,
+                amount: amount("0.01"),
+            },
+            "Overdraft",
+        ),
+        (
+            Transaction::Transfer {
+                from: 1,
+                to: 3,
+                amount: amount("1"),
+            },
+            "UnknownAccount(3)",
+        ),
+        (
+            Transaction::Transfer {
+                from: 1,
+                to: 1,
+                amount: amount("1"),
+            },
+            "SameAccount(1)",
+        ),
+        (
+            Transaction::Deposit {
+                account: 1,
+                amount: amount("-5"),
+            },
+            "InvalidAmount(Money(-500))",
+        ),
+        (
+            Transaction::Deposit {
+                account: 1,
+                amount: Money::ZERO,
+            },
+            "InvalidAmount(Money(0))",
+        ),
+        (
+            Transaction::Open(account(1, "0", "0")),
+            "DuplicateAccount(1)",
+        ),
+        (Transaction::Open(account(3, "-1", "0")), "Overdraft"),
+        (
+            Transaction::Open(account(3, "0", "-0.01")),
+            "NegativeLimit { account: 3, limit: Money(-1) }",
+        ),
+        (
+            Transaction::Open(Account {
+                overdraft_limit: Money::from_cents(i64::MIN),
+                ..account(3, "0", "0")
+            }),
+            "NegativeLimit",
+        ),
+    ] {
+        let error = ledger.apply(day, transaction).unwrap_err();
+        assert!(format!("{:?}", error).starts_with(expected), "{:?}", error);
+    }
+    let error = ledger
+        .apply(
+            "2024-01-09".parse().unwrap(),
+            Transaction::Deposit {
+                account: 1,
+                amount: amount("1"),
+            },
+        )
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "2024-01-09 is before the last entry, on 2024-01-10"
+    );
+    assert_eq!(ledger.accounts().cloned().collect::<Vec<_>>(), before);
+    assert_eq!(ledger.log.len(), 3);
+
+    ledger
+        .apply(
+            day,
+            Transaction::Transfer {
+                from: 1,
+                to: 2,
+                amount: amount("75.5"),
+            },
+        )
+        .unwrap();
+    assert_eq!(ledger.account(1).unwrap().balance, amount("24.50"));
+    assert_eq!(ledger.account(2).unwrap().balance, amount("25.50"));
+}
+
+#[test]
+fn test_replay_is_deterministic() {
+    let mut state = 0x5851_f42d_4c95_7f2d_u64;
+    let mut random = |n: u64| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state % n
+    };
+    let mut ledger = Ledger::new();
+    let mut day = Date::new(2024, 1, 1).unwrap();
+    let mut failures = 0;
+    for _ in 0..500 {
+        if random(10) == 0 {
+            day = match day.month {
+                12 => Date::new(day.year + 1, 1, 1),
+                month => Date::new(day.year, month + 1, 1),
+            }
+            .unwrap();
+        }
+        let id = random(8) as usize;
+        let amount = Money::from_cents(random(20_000) as i64 - 1_000);
+        let transaction = match random(6) {
+            0 => Transaction::Open(account(id, "10", &(random(3) * 100).to_string())),
+            1 | 2 => Transaction::Deposit {
+                account: id,
+                amount,
+            },
+            3 | 4 => Transaction::Withdraw {
+                account: id,
+                amount,
+            },
+            _ => Transaction::Transfer {
+                from: id,
+                to: random(8) as usize,
+                amount,
+            },
+        };
+        if ledger.apply(day, transaction).is_err() {
+            failures += 1;
+        }
+    }
+    // the mix must exercise both paths
+    assert!(failures > 50 && ledger.log.len() > 50);
+
+    let replayed = Ledger::replay(&ledger.log).unwrap();
+    assert_eq!(replayed, ledger);
+    assert_eq!(Ledger::replay(&replayed.log).unwrap(), ledger);
+
+    let mut text = vec![];
+    ledger.write_log(&mut text).unwrap();
+    let read = Ledger::read_log(&text[..]).unwrap();
+    assert_eq!(read, ledger.log);
+    assert_eq!(Ledger::replay(&read).unwrap(), ledger);
+
+    // a log that was tampered with
+    let mut swapped = read.clone();
+    swapped.swap(3, 4);
+    assert!(matches!(
+        Ledger::replay(&swapped),
+        Err(LedgerError::BadLog { entry: 3, .. })
+    ));
+    let truncated = &text[..text.len() - 10];
+    assert!(Ledger::read_log(truncated).is_err());
+}
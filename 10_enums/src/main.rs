@@ -1,127 +1,416 @@
-use std::cmp::Ordering;
-use std::collections::HashMap;
-
-#[derive(Debug)]
-enum Json {
-    Null,
-    Bool(bool),
-    Number(f64),
-    String(String),
-    Array(Vec<Json>),
-    Object(Box<HashMap<String, Json>>), // Using Box to void large size
-}
-
-// Binary Tree Example
-#[derive(Debug)]
-enum BinaryTree<T> {
-    Empty,
-    NonEmpty(Box<TreeNode<T>>),
-}
+mod avl_tree;
+mod binary_tree;
+#[macro_use]
+mod convert;
+mod json;
+mod ledger;
+mod patch;
+mod query;
+mod serde_bridge;
+mod stream;
+mod tree_map;
 
-#[derive(Debug)]
-struct TreeNode<T> {
-    element: T,
-    left: BinaryTree<T>,
-    right: BinaryTree<T>,
-}
+use avl_tree::AvlTree;
+use binary_tree::{BinaryTree, Tree, TreeNode};
+use json::Json;
+use ledger::{Account, Date, Ledger, LedgerError, Money, Transaction};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::Bound;
+use std::time::{Duration, Instant};
+use tree_map::{Entry, TreeMap};
 
-#[derive(Debug)]
-pub struct Account {
-    pub name: String,
-    pub lang: String,
-    pub id: usize,
-    pub balance: f64,
-    pub birthday: String,
-    pub eye_color: String,
+#[derive(Debug, Serialize, Deserialize)]
+struct Player {
+    name: String,
+    items: Vec<String>,
+    health: u64,
 }
 
-// Add node to a TreeNode
-impl<T: Ord + std::fmt::Display> BinaryTree<T> {
-    fn add(&mut self, value: T) {
-        match *self {
-            // Add the first empty node
-            BinaryTree::Empty => {
-                *self = BinaryTree::NonEmpty(Box::new(TreeNode {
-                    element: value,
-                    left: BinaryTree::Empty,
-                    right: BinaryTree::Empty,
-                }));
-            }
-            // Recursion to add the left if less than node, else add right
-            BinaryTree::NonEmpty(ref mut node) => {
-                match node.element.cmp(&value) {
-                    Ordering::Less => node.right.add(value),
-                    Ordering::Greater => node.left.add(value),
-                    Ordering::Equal => eprintln!("{} is already in the tree", &value),
-                };
-            }
-        }
+/// Time adding `values` to an empty set, then looking each of them up.
+fn time_set<S: Default>(
+    values: &[u32],
+    add: impl Fn(&mut S, u32) -> bool,
+    contains: impl Fn(&S, &u32) -> bool,
+) -> (S, Duration, Duration) {
+    let mut set = S::default();
+    let start = Instant::now();
+    for &value in values {
+        add(&mut set, value);
     }
+    let insert = start.elapsed();
+    let start = Instant::now();
+    let found = values.iter().filter(|value| contains(&set, value)).count();
+    let lookup = start.elapsed();
+    assert_eq!(found, values.len());
+    (set, insert, lookup)
 }
 
 fn main() {
     println!("----------");
     println!("-- Json --");
     println!("----------");
-    let json = Json::Object(Box::new({
-        let mut inner = HashMap::new();
-        inner.insert(
-            "c".to_string(),
-            Json::Array(vec![
-                Json::String("hello".to_string()),
-                Json::Number(2.0),
-                Json::Number(10.),
-            ]),
-        );
-        inner.insert("b".to_string(), Json::String("hello".to_string()));
-        inner.insert("a".to_string(), Json::Number(1.0));
-        inner.insert("n".to_string(), Json::Null);
-        inner.insert("B".to_string(), Json::Bool(true));
-        inner
-    }));
+    let json = json!({
+        "c": ["hello", 2.0, 10.],
+        "b": "hello",
+        "a": 1.0,
+        "n": null,
+        "B": true,
+    });
     println!("{:#?}", json);
+    println!("Compact: {}", json);
+    println!("Pretty: {:#}", json);
+
+    let text =
+        r#"{"name": "Saturn", "moons": 146, "rings": true, "density": 0.687, "symbol": "\u2644"}"#;
+    match Json::parse(text) {
+        Ok(parsed) => println!("Parsed: {}", parsed),
+        Err(error) => println!("Error: {}", error),
+    }
+    match Json::parse("{\"name\": \"Saturn\",\n \"moons\": 14 6}") {
+        Ok(parsed) => println!("Parsed: {}", parsed),
+        Err(error) => println!("Error: {}", error),
+    }
+
+    let mut planets = Json::parse(
+        r#"{"planets": [{"name": "Mars", "moons": ["Phobos", "Deimos"]},
+                        {"name": "Venus", "moons": []}]}"#,
+    )
+    .expect("valid JSON");
+    println!(
+        "Pointer /planets/0/name: {:?}",
+        planets.get("/planets/0/name")
+    );
+    println!(
+        "Index [\"planets\"][1][\"name\"]: {}",
+        planets["planets"][1]["name"]
+    );
+    println!("Missing [\"planets\"][9]: {}", planets["planets"][9]);
+    match planets.query("$.planets[*].moons[*]") {
+        Ok(moons) => println!(
+            "All moons: {:?}",
+            moons.iter().map(|m| m.to_string()).collect::<Vec<_>>()
+        ),
+        Err(error) => println!("Error: {}", error),
+    }
+    if let Err(error) = planets.insert("/planets/1/moons/-", Json::String("None".to_string())) {
+        println!("Error: {}", error);
+    }
+    planets["planets"][0]["color"] = Json::String("red".to_string());
+    match planets.remove("/planets/1") {
+        Ok(removed) => println!("Removed: {}", removed),
+        Err(error) => println!("Error: {}", error),
+    }
+    println!("Planets now: {}", planets);
+
+    let moons: Vec<String> = match Vec::try_from(&planets["planets"][0]["moons"]) {
+        Ok(moons) => moons,
+        Err(error) => {
+            println!("Error: {}", error);
+            vec![]
+        }
+    };
+    println!("Moons of Mars: {:?}", moons);
+    if let Err(error) = HashMap::<String, f64>::try_from(&planets["planets"][0]) {
+        println!("Not all numbers: {}", error);
+    }
+    let player = Player {
+        name: "Ferris".to_string(),
+        items: moons,
+        health: 100,
+    };
+    match serde_bridge::to_json(&player) {
+        Ok(json) => {
+            println!("Player as Json: {}", json);
+            let back: Result<Player, _> = serde_bridge::from_json(json);
+            println!("And back: {:?}", back);
+        }
+        Err(error) => println!("Error: {}", error),
+    }
+
+    let before = json!({"name": "Mars", "moons": ["Phobos"], "color": "red", "rings": false});
+    let after = json!({"name": "Mars", "moons": ["Phobos", "Deimos"], "color": "rust"});
+    print!("Diff:\n{}", patch::diff_text(&before, &after));
+    let changes = patch::diff(&before, &after);
+    println!("As a JSON Patch: {}", patch::patch_to_json(&changes));
+    let mut patched = before.clone();
+    match patch::apply(&mut patched, &changes) {
+        Ok(()) => println!("Patched is after: {}", patched == after),
+        Err(error) => println!("Error: {}", error),
+    }
+    patch::merge_patch(&mut patched, &json!({"color": null, "radius_km": 3389.5}));
+    println!("Merge-patched: {}", patched);
+    let document = Json::parse(
+        r#"[{"op": "test", "path": "/name", "value": "Mars"},
+            {"op": "copy", "from": "/moons/1", "path": "/largest"},
+            {"op": "move", "from": "/largest", "path": "/smallest"}]"#,
+    )
+    .expect("valid JSON");
+    match patch::parse_patch(&document).and_then(|ops| patch::apply(&mut patched, &ops)) {
+        Ok(()) => println!("Patched again: {}", patched),
+        Err(error) => println!("Error: {}", error),
+    }
+
+    // a log too large to parse whole is read an event or an entry at a time
+    let log = r#"{"level": "info", "message": "started", "tags": ["boot"]}
+{"level": "error", "message": "disk full", "context": {"free": 0}}"#;
+    let mut reader = stream::JsonReader::new(log.as_bytes());
+    for _ in 0..6 {
+        match reader.next_event() {
+            Ok(Some((offset, event))) => {
+                println!(
+                    "Event at byte {}, depth {}: {:?}",
+                    offset,
+                    reader.depth(),
+                    event
+                )
+            }
+            Ok(None) => break,
+            Err(error) => println!("Error: {}", error),
+        }
+    }
+    let mut reader = stream::JsonReader::new(log.as_bytes());
+    loop {
+        match reader.read_value() {
+            Ok(Some(entry)) => println!(
+                "Entry {}: {}, read up to byte {}",
+                entry["level"],
+                entry["message"],
+                reader.offset()
+            ),
+            Ok(None) => break,
+            Err(error) => {
+                println!("Error: {}", error);
+                break;
+            }
+        }
+    }
+    match stream::JsonReader::new(log.as_bytes()).select("/tags/0") {
+        Ok(tag) => println!("Selected /tags/0: {:?}", tag),
+        Err(error) => println!("Error: {}", error),
+    }
 
     println!("--------------");
     println!("-- Patterns --");
     println!("--------------");
-    let jupiter_tree = BinaryTree::NonEmpty(Box::new(TreeNode {
+    let jupiter_tree = Tree::NonEmpty(Box::new(TreeNode {
         element: "Jupiter",
-        left: BinaryTree::Empty,
-        right: BinaryTree::Empty,
+        left: Tree::Empty,
+        right: Tree::Empty,
     }));
-    let mercury_tree = BinaryTree::NonEmpty(Box::new(TreeNode {
+    let mercury_tree = Tree::NonEmpty(Box::new(TreeNode {
         element: "Mercury",
-        left: BinaryTree::Empty,
-        right: BinaryTree::Empty,
+        left: Tree::Empty,
+        right: Tree::Empty,
     }));
-    let mars_tree = BinaryTree::NonEmpty(Box::new(TreeNode {
+    let mars_tree = Tree::NonEmpty(Box::new(TreeNode {
         element: "Mars",
         left: jupiter_tree,
         right: mercury_tree,
     }));
-    let venus_tree = BinaryTree::NonEmpty(Box::new(TreeNode {
+    let venus_tree = Tree::NonEmpty(Box::new(TreeNode {
         element: "Venus",
-        left: BinaryTree::Empty,
-        right: BinaryTree::Empty,
+        left: Tree::Empty,
+        right: Tree::Empty,
     }));
-    let uranus_tree = BinaryTree::NonEmpty(Box::new(TreeNode {
+    let uranus_tree = Tree::NonEmpty(Box::new(TreeNode {
         element: "Uranus",
-        left: BinaryTree::Empty,
+        left: Tree::Empty,
         right: venus_tree,
     }));
-    let mut saturn_tree = BinaryTree::NonEmpty(Box::new(TreeNode {
+    let saturn = Tree::NonEmpty(Box::new(TreeNode {
         element: "Saturn",
         left: mars_tree,
         right: uranus_tree,
     }));
 
-    println!("Saturn_Tree: {:#?}", saturn_tree);
+    // counted once, from then on the tree keeps its length as it changes
+    let mut saturn_tree = BinaryTree::from(saturn);
+
+    println!("Saturn_Tree:\n{}", saturn_tree);
+    println!("As Graphviz:\n{}", saturn_tree.to_dot());
 
     println!("-- Adding --");
-    saturn_tree.add("Earth");
-    println!("After adding Earth: {:#?}", saturn_tree);
+    println!("Added Earth: {}", saturn_tree.add("Earth"));
+    println!("After adding Earth:\n{}", saturn_tree);
     println!("-- Adding Again --");
-    saturn_tree.add("Earth");
+    println!("Added Earth again: {}", saturn_tree.add("Earth"));
+
+    println!("-- Searching --");
+    println!("Contains Mars: {}", saturn_tree.contains("Mars"));
+    println!(
+        "{} planets, {} levels, from {:?} to {:?}",
+        saturn_tree.len(),
+        saturn_tree.height(),
+        saturn_tree.min(),
+        saturn_tree.max()
+    );
+    println!("In order: {:?}", saturn_tree.iter().collect::<Vec<_>>());
+    println!(
+        "Pre-order: {:?}",
+        saturn_tree.pre_order().collect::<Vec<_>>()
+    );
+    println!(
+        "Post-order: {:?}",
+        saturn_tree.post_order().collect::<Vec<_>>()
+    );
+    println!(
+        "Level order: {:?}",
+        saturn_tree.level_order().collect::<Vec<_>>()
+    );
+    println!("-- Removing --");
+    println!("Removed Saturn: {}", saturn_tree.remove("Saturn"));
+    for planet in &saturn_tree {
+        print!("{} ", planet);
+    }
+    println!();
+    let mut numbers: BinaryTree<u32> = [5, 3, 8, 1].into_iter().collect();
+    numbers.extend([4, 9]);
+    println!("Is empty: {}", numbers.is_empty());
+    let copy: BinaryTree<u32> = numbers.pre_order().copied().collect();
+    println!(
+        "Taken pre-order: {:?}",
+        copy.into_pre_order().collect::<Vec<_>>()
+    );
+    let copy: BinaryTree<u32> = numbers.pre_order().copied().collect();
+    println!(
+        "Taken post-order: {:?}",
+        copy.into_post_order().collect::<Vec<_>>()
+    );
+    let copy: BinaryTree<u32> = numbers.pre_order().copied().collect();
+    println!(
+        "Taken level order: {:?}",
+        copy.into_level_order().collect::<Vec<_>>()
+    );
+    println!(
+        "Taken in order: {:?}",
+        numbers.into_iter().collect::<Vec<_>>()
+    );
+    println!(
+        "Default is empty: {}",
+        BinaryTree::<u32>::default().is_empty()
+    );
+
+    println!("-- Balancing --");
+    let mut balanced: AvlTree<&str> = ["Mercury", "Venus", "Earth", "Mars"].into_iter().collect();
+    balanced.extend(["Jupiter", "Saturn", "Uranus", "Neptune"]);
+    println!(
+        "{} planets in {} levels: {:?}",
+        balanced.len(),
+        balanced.height(),
+        balanced.level_order().collect::<Vec<_>>()
+    );
+    println!("Removed Earth: {}", balanced.remove("Earth"));
+    println!("Contains Earth: {}", balanced.contains("Earth"));
+    println!("From {:?} to {:?}", balanced.min(), balanced.max());
+    println!("Invariants: {:?}", balanced.check());
+    println!("Pre-order: {:?}", balanced.pre_order().collect::<Vec<_>>());
+    println!(
+        "Post-order: {:?}",
+        balanced.post_order().collect::<Vec<_>>()
+    );
+    for planet in &balanced {
+        print!("{} ", planet);
+    }
+    println!();
+    let copy: AvlTree<&str> = balanced.iter().copied().collect();
+    println!(
+        "Taken pre-order: {:?}",
+        copy.into_pre_order().collect::<Vec<_>>()
+    );
+    let copy: AvlTree<&str> = balanced.iter().copied().collect();
+    println!(
+        "Taken post-order: {:?}",
+        copy.into_post_order().collect::<Vec<_>>()
+    );
+    let copy: AvlTree<&str> = balanced.iter().copied().collect();
+    println!(
+        "Taken level order: {:?}",
+        copy.into_level_order().collect::<Vec<_>>()
+    );
+    println!(
+        "Taken in order: {:?}",
+        balanced.into_iter().collect::<Vec<_>>()
+    );
+    println!("Default is empty: {}", AvlTree::<u32>::default().is_empty());
+
+    // sorted input makes the unbalanced tree a list, each add and lookup walking all of it
+    let sorted: Vec<u32> = (0..5_000).collect();
+    // multiplying by an odd number mixes the order without repeating a value
+    let random: Vec<u32> = (0..5_000u32).map(|n| n.wrapping_mul(0x9e37_79b9)).collect();
+    for (input, values) in [("sorted", &sorted), ("random", &random)] {
+        let (tree, insert, lookup) =
+            time_set(values, BinaryTree::<u32>::add, |tree, n| tree.contains(n));
+        println!(
+            "BinaryTree, {} input: insert {:>10.2?}, lookup {:>10.2?}, height {}",
+            input,
+            insert,
+            lookup,
+            tree.height()
+        );
+        let (tree, insert, lookup) =
+            time_set(values, AvlTree::<u32>::add, |tree, n| tree.contains(n));
+        println!(
+            "AvlTree,    {} input: insert {:>10.2?}, lookup {:>10.2?}, height {}",
+            input,
+            insert,
+            lookup,
+            tree.height()
+        );
+    }
+
+    println!("-- Mapping --");
+    let mut distances: TreeMap<String, f64> = [("Mars", 1.52), ("Earth", 1.0), ("Venus", 0.72)]
+        .into_iter()
+        .map(|(planet, au)| (planet.to_string(), au))
+        .collect();
+    distances.insert("Jupiter".to_string(), 5.2);
+    println!(
+        "Previous Mars: {:?}",
+        distances.insert("Mars".to_string(), 1.524)
+    );
+    // String keys, looked up with &str
+    println!(
+        "Venus: {:?}, Pluto: {:?}",
+        distances.get("Venus"),
+        distances.get("Pluto")
+    );
+    if let Some(au) = distances.get_mut("Jupiter") {
+        *au = 5.203;
+    }
+    println!("Contains Earth: {}", distances.contains_key("Earth"));
+    for (planet, au) in
+        distances.range::<str, _>((Bound::Included("Earth"), Bound::Excluded("Mars")))
+    {
+        println!("  from Earth to Mars: {} at {} AU", planet, au);
+    }
+    *distances.entry("Saturn".to_string()).or_insert(9.5) += 0.04;
+    distances
+        .entry("Earth".to_string())
+        .and_modify(|au| *au = 1.0)
+        .or_insert_with(|| 1.0);
+    let entry = distances.entry("Mercury".to_string());
+    println!("Entry for {}", entry.key());
+    match entry {
+        Entry::Occupied(entry) => println!("Already at {}", entry.get()),
+        Entry::Vacant(entry) => println!("Added at {}", entry.insert(0.39)),
+    }
+    println!("Removed Venus: {:?}", distances.remove("Venus"));
+    println!(
+        "{} planets: {:?} at {:?}",
+        distances.len(),
+        distances.keys().collect::<Vec<_>>(),
+        distances.values().collect::<Vec<_>>()
+    );
+    for (planet, au) in &distances {
+        print!("{}={} ", planet, au);
+    }
+    println!();
+    let mut counts: TreeMap<char, u32> = TreeMap::new();
+    for c in "mississippi".chars() {
+        *counts.entry(c).or_default() += 1;
+    }
+    println!("Letters: {:?}, empty: {}", counts, counts.is_empty());
 
     println!("--------------");
     println!("-- Patterns --");
@@ -130,12 +419,14 @@ fn main() {
         name: "John".to_string(),
         lang: "en".to_string(),
         id: 1,
-        balance: 250.0,
-        birthday: "1990-01-01".to_string(),
+        balance: Money::from_cents(25_000),
+        birthday: Date::new(1990, 1, 1).unwrap(),
         eye_color: "blue".to_string(),
+        overdraft_limit: Money::ZERO,
     };
     println!("Account: {:#?}", account);
     // need reference to borrow name and lang
+    #[allow(clippy::match_single_binding)] // a `let` would do, the match is the example
     match account {
         Account {
             ref name, ref lang, ..
@@ -154,4 +445,97 @@ fn main() {
     //     println!("Digit: {}", digit);
     // }
     // };
+
+    println!("------------");
+    println!("-- Ledger --");
+    println!("------------");
+    let mut ledger = Ledger::new();
+    let jane = Account {
+        name: "Jane".to_string(),
+        id: 2,
+        balance: Money::ZERO,
+        birthday: "1985-02-28".parse().unwrap(),
+        overdraft_limit: "100".parse().unwrap(),
+        ..account.clone()
+    };
+    let day = |text: &str| text.parse::<Date>().unwrap();
+    let amount = |text: &str| text.parse::<Money>().unwrap();
+    let transactions = [
+        ("2024-03-01", Transaction::Open(account.clone())),
+        ("2024-03-01", Transaction::Open(jane)),
+        (
+            "2024-03-02",
+            Transaction::Deposit {
+                account: 1,
+                amount: amount("0.10"),
+            },
+        ),
+        (
+            "2024-03-02",
+            Transaction::Deposit {
+                account: 2,
+                amount: amount("0.20"),
+            },
+        ),
+        (
+            "2024-03-05",
+            Transaction::Transfer {
+                from: 2,
+                to: 1,
+                amount: amount("100.20"),
+            },
+        ),
+        (
+            "2024-03-06",
+            Transaction::Withdraw {
+                account: 2,
+                amount: amount("0.01"),
+            },
+        ),
+        (
+            "2024-03-07",
+            Transaction::Withdraw {
+                account: 1,
+                amount: amount("-3"),
+            },
+        ),
+        (
+            "2024-03-04",
+            Transaction::Deposit {
+                account: 1,
+                amount: amount("1"),
+            },
+        ),
+    ];
+    for (date, transaction) in transactions {
+        match ledger.apply(day(date), transaction) {
+            Ok(()) => println!("{}: ok", date),
+            Err(error @ LedgerError::Overdraft { .. }) => println!("{}: refused, {}", date, error),
+            Err(error) => println!("{}: {}", date, error),
+        }
+    }
+    if let Err(error) = ledger.account(3) {
+        println!("Account 3: {}", error);
+    }
+    ledger
+        .apply(
+            day("2024-03-08"),
+            Transaction::Deposit {
+                account: 1,
+                amount: amount("12.5"),
+            },
+        )
+        .unwrap();
+    for account in ledger.accounts() {
+        println!("{} (#{}): {}", account.name, account.id, account.balance);
+    }
+    let total = ledger.accounts().try_fold(Money::ZERO, |total, account| {
+        total.checked_add(account.balance)
+    });
+    println!("Total: {:?}", total.map(|total| total.to_string()));
+    let mut log = vec![];
+    ledger.write_log(&mut log).unwrap();
+    print!("Log:\n{}", String::from_utf8_lossy(&log));
+    let replayed = Ledger::replay(&Ledger::read_log(&log[..]).unwrap()).unwrap();
+    println!("Replayed the same ledger: {}", replayed == ledger);
 }
@@ -0,0 +1,516 @@
+// Comparing Json documents: JSON Patch (RFC 6902), JSON Merge Patch (RFC 7396),
+// and a coloured diff for people
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use colored::*;
+
+use crate::json::Json;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchOp {
+    Add { path: String, value: Json },
+    Remove { path: String },
+    Replace { path: String, value: Json },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Json },
+}
+
+/// The operation at index `op` of a patch could not be read or applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchError {
+    pub op: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "patch operation {}: {}", self.op, self.message)
+    }
+}
+
+impl Error for PatchError {}
+
+/// Escape a key to be one reference token of a JSON pointer.
+fn token(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+impl PatchOp {
+    /// As written in a JSON Patch document, `{"op": "add", "path": ..., "value": ...}`.
+    pub fn to_json(&self) -> Json {
+        let (op, path, from, value) = match self {
+            PatchOp::Add { path, value } => ("add", path, None, Some(value)),
+            PatchOp::Remove { path } => ("remove", path, None, None),
+            PatchOp::Replace { path, value } => ("replace", path, None, Some(value)),
+            PatchOp::Move { from, path } => ("move", path, Some(from), None),
+            PatchOp::Copy { from, path } => ("copy", path, Some(from), None),
+            PatchOp::Test { path, value } => ("test", path, None, Some(value)),
+        };
+        let mut members = HashMap::new();
+        members.insert("op".to_string(), Json::from(op));
+        members.insert("path".to_string(), Json::from(path.as_str()));
+        if let Some(from) = from {
+            members.insert("from".to_string(), Json::from(from.as_str()));
+        }
+        if let Some(value) = value {
+            members.insert("value".to_string(), value.clone());
+        }
+        Json::Object(Box::new(members))
+    }
+
+    pub fn from_json(json: &Json) -> Result<PatchOp, String> {
+        let text = |member: &str| match &json[member] {
+            Json::String(s) => Ok(s.clone()),
+            Json::Null => Err(format!("missing \"{}\"", member)),
+            other => Err(format!(
+                "\"{}\" must be a string, found {}",
+                member,
+                other.kind()
+            )),
+        };
+        let value = || match json {
+            Json::Object(members) if members.contains_key("value") => Ok(json["value"].clone()),
+            _ => Err("missing \"value\"".to_string()),
+        };
+        let path = text("path")?;
+        Ok(match text("op")?.as_str() {
+            "add" => PatchOp::Add {
+                path,
+                value: value()?,
+            },
+            "remove" => PatchOp::Remove { path },
+            "replace" => PatchOp::Replace {
+                path,
+                value: value()?,
+            },
+            "move" => PatchOp::Move {
+                from: text("from")?,
+                path,
+            },
+            "copy" => PatchOp::Copy {
+                from: text("from")?,
+                path,
+            },
+            "test" => PatchOp::Test {
+                path,
+                value: value()?,
+            },
+            other => return Err(format!("unknown operation \"{}\"", other)),
+        })
+    }
+
+    fn apply(&self, doc: &mut Json) -> Result<(), String> {
+        match self {
+            PatchOp::Add { path, value } => {
+                doc.insert(path, value.clone()).map_err(|e| e.to_string())?;
+            }
+            PatchOp::Remove { path } => {
+                doc.remove(path).map_err(|e| e.to_string())?;
+            }
+            PatchOp::Replace { path, value } => match doc.get_mut(path) {
+                Some(target) => *target = value.clone(),
+                None => return Err(format!("nothing to replace in '{}'", path)),
+            },
+            PatchOp::Move { from, path } => {
+                // a value cannot be moved inside itself
+                if path.starts_with(&format!("{}/", from)) {
+                    return Err(format!("cannot move '{}' into '{}'", from, path));
+                }
+                let value = doc.remove(from).map_err(|e| e.to_string())?;
+                doc.insert(path, value).map_err(|e| e.to_string())?;
+            }
+            PatchOp::Copy { from, path } => {
+                let value = doc
+                    .get(from)
+                    .cloned()
+                    .ok_or_else(|| format!("nothing to copy in '{}'", from))?;
+                doc.insert(path, value).map_err(|e| e.to_string())?;
+            }
+            PatchOp::Test { path, value } => {
+                if doc.get(path) != Some(value) {
+                    return Err(format!("test failed, '{}' is not {}", path, value));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read a JSON Patch document, an array of operations.
+pub fn parse_patch(json: &Json) -> Result<Vec<PatchOp>, PatchError> {
+    match json {
+        Json::Array(ops) => ops
+            .iter()
+            .enumerate()
+            .map(|(op, json)| {
+                PatchOp::from_json(json).map_err(|message| PatchError { op, message })
+            })
+            .collect(),
+        other => Err(PatchError {
+            op: 0,
+            message: format!("a patch must be an array, found {}", other.kind()),
+        }),
+    }
+}
+
+pub fn patch_to_json(patch: &[PatchOp]) -> Json {
+    Json::Array(patch.iter().map(PatchOp::to_json).collect())
+}
+
+/// Apply every operation in order. If one fails `doc` is left as it was.
+pub fn apply(doc: &mut Json, patch: &[PatchOp]) -> Result<(), PatchError> {
+    let mut patched = doc.clone();
+    for (op, operation) in patch.iter().enumerate() {
+        operation
+            .apply(&mut patched)
+            .map_err(|message| PatchError { op, message })?;
+    }
+    *doc = patched;
+    Ok(())
+}
+
+/// The operations that turn `a` into `b`: object members are compared one by one,
+/// array elements index by index, with additions and removals at the end.
+pub fn diff(a: &Json, b: &Json) -> Vec<PatchOp> {
+    let mut patch = vec![];
+    diff_at("", a, b, &mut patch);
+    patch
+}
+
+fn diff_at(path: &str, a: &Json, b: &Json, patch: &mut Vec<PatchOp>) {
+    match (a, b) {
+        _ if a == b => {}
+        (Json::Object(old), Json::Object(new)) => {
+            let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let path = format!("{}/{}", path, token(key));
+                match (old.get(key), new.get(key)) {
+                    (Some(old), Some(new)) => diff_at(&path, old, new, patch),
+                    (Some(_), None) => patch.push(PatchOp::Remove { path }),
+                    (None, Some(new)) => patch.push(PatchOp::Add {
+                        path,
+                        value: new.clone(),
+                    }),
+                    (None, None) => unreachable!("the key comes from one of them"),
+                }
+            }
+        }
+        (Json::Array(old), Json::Array(new)) => {
+            for (i, (old, new)) in old.iter().zip(new).enumerate() {
+                diff_at(&format!("{}/{}", path, i), old, new, patch);
+            }
+            for new in new.iter().skip(old.len()) {
+                patch.push(PatchOp::Add {
+                    path: format!("{}/-", path),
+                    value: new.clone(),
+                });
+            }
+            // from the end, so that the indexes still to remove do not move
+            for i in (new.len()..old.len()).rev() {
+                patch.push(PatchOp::Remove {
+                    path: format!("{}/{}", path, i),
+                });
+            }
+        }
+        _ => patch.push(PatchOp::Replace {
+            path: path.to_string(),
+            value: b.clone(),
+        }),
+    }
+}
+
+/// JSON Merge Patch: the members of an object `patch` are merged into `doc`, recursively,
+/// `null` members are removed, and any other `patch` replaces `doc`.
+pub fn merge_patch(doc: &mut Json, patch: &Json) {
+    let Json::Object(patch_members) = patch else {
+        *doc = patch.clone();
+        return;
+    };
+    if !matches!(doc, Json::Object(_)) {
+        *doc = Json::Object(Box::default());
+    }
+    if let Json::Object(members) = doc {
+        for (key, value) in patch_members.iter() {
+            if *value == Json::Null {
+                members.remove(key);
+            } else {
+                merge_patch(members.entry(key.clone()).or_insert(Json::Null), value);
+            }
+        }
+    }
+}
+
+/// The differences between `a` and `b`, one per line: `-` removed in red, `+` added in green,
+/// `~` changed in yellow, each with the pointer to where it is.
+pub fn diff_text(a: &Json, b: &Json) -> String {
+    let mut out = String::new();
+    for op in diff(a, b) {
+        let line = match op {
+            PatchOp::Remove { path } => {
+                let old = a.get(&path).map(Json::to_string).unwrap_or_default();
+                format!("- {}: {}", path, old).red()
+            }
+            PatchOp::Add { path, value } => format!("+ {}: {}", path, value).green(),
+            PatchOp::Replace { path, value } => {
+                let old = a.get(&path).map(Json::to_string).unwrap_or_default();
+                format!("~ {}: {} -> {}", path, old, value).yellow()
+            }
+            other => format!("  {}", other.to_json()).normal(),
+        };
+        out += &format!("{}\n", line);
+    }
+    out
+}
+
+#[test]
+fn test_rfc6902_examples() {
+    let cases = [
+        // A.1 adding an object member
+        (
+            r#"{"foo": "bar"}"#,
+            r#"[{"op": "add", "path": "/baz", "value": "qux"}]"#,
+            r#"{"baz":"qux","foo":"bar"}"#,
+        ),
+        // A.2 adding an array element
+        (
+            r#"{"foo": ["bar", "baz"]}"#,
+            r#"[{"op": "add", "path": "/foo/1", "value": "qux"}]"#,
+            r#"{"foo":["bar","qux","baz"]}"#,
+        ),
+        // A.4 removing an array element
+        (
+            r#"{"foo": ["bar", "qux", "baz"]}"#,
+            r#"[{"op": "remove", "path": "/foo/1"}]"#,
+            r#"{"foo":["bar","baz"]}"#,
+        ),
+        // A.6 moving a value
+        (
+            r#"{"foo": {"bar": "baz", "waldo": "fred"}, "qux": {"corge": "grault"}}"#,
+            r#"[{"op": "move", "from": "/foo/waldo", "path": "/qux/thud"}]"#,
+            r#"{"foo":{"bar":"baz"},"qux":{"corge":"grault","thud":"fred"}}"#,
+        ),
+        // A.7 moving an array element
+        (
+            r#"{"foo": ["all", "grass", "cows", "eat"]}"#,
+            r#"[{"op": "move", "from": "/foo/1", "path": "/foo/3"}]"#,
+            r#"{"foo":["all","cows","eat","grass"]}"#,
+        ),
+        // A.8 testing a value, success
+        (
+            r#"{"baz": "qux", "foo": ["a", 2, "c"]}"#,
+            r#"[{"op": "test", "path": "/baz", "value": "qux"},
+                {"op": "test", "path": "/foo/1", "value": 2}]"#,
+            r#"{"baz":"qux","foo":["a",2,"c"]}"#,
+        ),
+        // A.16 adding an array value
+        (
+            r#"{"foo": ["bar"]}"#,
+            r#"[{"op": "add", "path": "/foo/-", "value": ["abc", "def"]}]"#,
+            r#"{"foo":["bar",["abc","def"]]}"#,
+        ),
+        (
+            r#"{"a": {"b": 1}}"#,
+            r#"[{"op": "copy", "from": "/a", "path": "/c"},
+                {"op": "replace", "path": "/a/b", "value": 2}]"#,
+            r#"{"a":{"b":2},"c":{"b":1}}"#,
+        ),
+    ];
+    for (doc, patch, expected) in cases {
+        let mut doc = Json::parse(doc).unwrap();
+        let patch = parse_patch(&Json::parse(patch).unwrap()).unwrap();
+        apply(&mut doc, &patch).unwrap();
+        assert_eq!(doc.to_string(), expected);
+    }
+}
+
+#[test]
+fn test_failed_patch_changes_nothing() {
+    let original = Json::parse(r#"{"baz": "qux", "foo": ["a", 2, "c"]}"#).unwrap();
+    let mut doc = original.clone();
+    // A.9 testing a value, error, after an operation that did work
+    let patch = parse_patch(
+        &Json::parse(
+            r#"[{"op": "remove", "path": "/foo"},
+                {"op": "test", "path": "/baz", "value": "bar"}]"#,
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    let error = apply(&mut doc, &patch).unwrap_err();
+    assert_eq!(error.op, 1);
+    assert_eq!(doc, original);
+
+    for bad in [
+        r#"[{"op": "add", "path": "/baz/bat", "value": "qux"}]"#,
+        r#"[{"op": "replace", "path": "/nothing", "value": 1}]"#,
+        r#"[{"op": "move", "from": "/foo", "path": "/foo/0"}]"#,
+        r#"[{"op": "copy", "from": "/none", "path": "/x"}]"#,
+    ] {
+        let patch = parse_patch(&Json::parse(bad).unwrap()).unwrap();
+        assert!(apply(&mut doc, &patch).is_err(), "{}", bad);
+    }
+
+    for (bad, message) in [
+        (
+            r#"[{"op": "jump", "path": "/a"}]"#,
+            "unknown operation \"jump\"",
+        ),
+        (r#"[{"op": "add", "path": "/a"}]"#, "missing \"value\""),
+        (r#"[{"path": "/a"}]"#, "missing \"op\""),
+        (
+            r#"[{"op": "move", "path": 1}]"#,
+            "\"path\" must be a string, found a number",
+        ),
+    ] {
+        let error = parse_patch(&Json::parse(bad).unwrap()).unwrap_err();
+        assert_eq!(error.message, message);
+    }
+    // `"value": null` is a value
+    assert!(
+        parse_patch(&Json::parse(r#"[{"op": "add", "path": "/a", "value": null}]"#).unwrap())
+            .is_ok()
+    );
+}
+
+#[test]
+fn test_diff() {
+    let a =
+        Json::parse(r#"{"keep": 1, "old": true, "list": [1, 2, 3], "deep": {"x": 1}, "a/b": 0}"#)
+            .unwrap();
+    let b =
+        Json::parse(r#"{"keep": 1, "new": null, "list": [1, 5], "deep": {"x": 2}, "a/b": "0"}"#)
+            .unwrap();
+    let patch = diff(&a, &b);
+    assert_eq!(
+        patch_to_json(&patch).to_string(),
+        concat!(
+            r#"[{"op":"replace","path":"/a~1b","value":"0"},"#,
+            r#"{"op":"replace","path":"/deep/x","value":2},"#,
+            r#"{"op":"replace","path":"/list/1","value":5},"#,
+            r#"{"op":"remove","path":"/list/2"},"#,
+            r#"{"op":"add","path":"/new","value":null},"#,
+            r#"{"op":"remove","path":"/old"}]"#
+        )
+    );
+    assert!(diff(&a, &a).is_empty());
+    assert_eq!(
+        diff(&a, &Json::Null),
+        vec![PatchOp::Replace {
+            path: String::new(),
+            value: Json::Null
+        }]
+    );
+}
+
+/// A small xorshift generator, enough to make up documents.
+#[cfg(test)]
+struct Random(u64);
+
+#[cfg(test)]
+impl Random {
+    fn below(&mut self, n: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % n
+    }
+
+    fn document(&mut self, depth: u32) -> Json {
+        let kinds = if depth == 0 { 4 } else { 6 };
+        match self.below(kinds) {
+            0 => Json::Null,
+            1 => Json::Bool(self.below(2) == 0),
+            2 => Json::Number(self.below(5) as f64),
+            3 => Json::String(["a", "b", "~", "/"][self.below(4) as usize].to_string()),
+            4 => Json::Array(
+                (0..self.below(4))
+                    .map(|_| self.document(depth - 1))
+                    .collect(),
+            ),
+            _ => Json::Object(Box::new(
+                (0..self.below(4))
+                    .map(|_| {
+                        let key = ["x", "y", "z/", "~w"][self.below(4) as usize].to_string();
+                        (key, self.document(depth - 1))
+                    })
+                    .collect(),
+            )),
+        }
+    }
+}
+
+#[test]
+fn test_apply_diff_gives_the_target() {
+    let mut random = Random(0x2545_f491_4f6c_dd1d);
+    for _ in 0..500 {
+        let a = random.document(4);
+        let b = random.document(4);
+        let patch = diff(&a, &b);
+        // through its JSON form too
+        let patch = parse_patch(&patch_to_json(&patch)).unwrap();
+        let mut patched = a.clone();
+        apply(&mut patched, &patch).unwrap();
+        assert_eq!(patched, b, "{} -> {}", a, b);
+    }
+}
+
+#[test]
+fn test_merge_patch_rfc7396_example() {
+    let mut doc = Json::parse(
+        r#"{"title": "Goodbye!",
+            "author": {"givenName": "John", "familyName": "Doe"},
+            "tags": ["example", "sample"],
+            "content": "This will be unchanged"}"#,
+    )
+    .unwrap();
+    let patch = Json::parse(
+        r#"{"title": "Hello!", "phoneNumber": "+01-123-456-7890",
+            "author": {"familyName": null}, "tags": ["example"]}"#,
+    )
+    .unwrap();
+    merge_patch(&mut doc, &patch);
+    assert_eq!(
+        doc,
+        Json::parse(
+            r#"{"title": "Hello!", "author": {"givenName": "John"}, "tags": ["example"],
+                "content": "This will be unchanged", "phoneNumber": "+01-123-456-7890"}"#
+        )
+        .unwrap()
+    );
+
+    // the cases of the RFC's appendix
+    for (original, patch, result) in [
+        (r#"{"a":"b"}"#, r#"{"a":"c"}"#, r#"{"a":"c"}"#),
+        (r#"{"a":"b"}"#, r#"{"a":null}"#, r#"{}"#),
+        (r#"{"a":[{"b":"c"}]}"#, r#"{"a":[1]}"#, r#"{"a":[1]}"#),
+        (r#"["a","b"]"#, r#"["c","d"]"#, r#"["c","d"]"#),
+        (r#"{"a":"foo"}"#, "null", "null"),
+        (r#"{"e":null}"#, r#"{"a":1}"#, r#"{"a":1,"e":null}"#),
+        (r#"[1,2]"#, r#"{"a":"b","c":null}"#, r#"{"a":"b"}"#),
+        (
+            r#"{}"#,
+            r#"{"a":{"bb":{"ccc":null}}}"#,
+            r#"{"a":{"bb":{}}}"#,
+        ),
+    ] {
+        let mut doc = Json::parse(original).unwrap();
+        merge_patch(&mut doc, &Json::parse(patch).unwrap());
+        assert_eq!(doc.to_string(), result, "{} + {}", original, patch);
+    }
+}
+
+#[test]
+fn test_diff_text() {
+    colored::control::set_override(false);
+    let a = Json::parse(r#"{"name": "Mars", "moons": 2, "rings": false}"#).unwrap();
+    let b = Json::parse(r#"{"name": "Mars", "moons": 3, "color": "red"}"#).unwrap();
+    assert_eq!(
+        diff_text(&a, &b),
+        "+ /color: \"red\"\n~ /moons: 2 -> 3\n- /rings: false\n"
+    );
+}
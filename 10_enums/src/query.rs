@@ -0,0 +1,465 @@
+// Finding and changing values inside a Json document:
+// JSON Pointer (RFC 6901), a small JSONPath, `json["key"]` and `json[0]`
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+use crate::json::Json;
+
+/// A pointer or a path that does not lead anywhere, or could not be read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathError {
+    pub path: String,
+    pub message: String,
+}
+
+impl PathError {
+    fn new(path: &str, message: impl Into<String>) -> Self {
+        PathError {
+            path: path.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} in '{}'", self.message, self.path)
+    }
+}
+
+impl Error for PathError {}
+
+/// The reference tokens of a pointer: `/a~1b/0` is `["a/b", "0"]`, `""` is the whole document.
+fn pointer_tokens(pointer: &str) -> Result<Vec<String>, PathError> {
+    if pointer.is_empty() {
+        return Ok(vec![]);
+    }
+    match pointer.strip_prefix('/') {
+        // ~1 first, so that "~01" is "~1" and not "/"
+        Some(rest) => Ok(rest
+            .split('/')
+            .map(|token| token.replace("~1", "/").replace("~0", "~"))
+            .collect()),
+        None => Err(PathError::new(
+            pointer,
+            "a JSON pointer must start with '/'",
+        )),
+    }
+}
+
+/// `0`, `1`, `12`... but not `01` or `-1`.
+fn array_index(token: &str) -> Option<usize> {
+    if token.is_empty() || (token.len() > 1 && token.starts_with('0')) {
+        return None;
+    }
+    if !token.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+/// The pointer to the parent of what `pointer` points to, and the last token.
+fn split_last(pointer: &str) -> Result<(&str, String), PathError> {
+    let mut tokens = pointer_tokens(pointer)?;
+    match (pointer.rfind('/'), tokens.pop()) {
+        (Some(slash), Some(last)) => Ok((&pointer[..slash], last)),
+        _ => Err(PathError::new(pointer, "the document itself has no parent")),
+    }
+}
+
+impl Json {
+    /// The value `pointer` points to, e.g. `/books/0/title`, or `None`.
+    pub fn get(&self, pointer: &str) -> Option<&Json> {
+        let mut current = self;
+        for token in pointer_tokens(pointer).ok()? {
+            current = match current {
+                Json::Object(members) => members.get(&token)?,
+                Json::Array(elements) => elements.get(array_index(&token)?)?,
+                _ => return None,
+            };
+        }
+        Some(current)
+    }
+
+    pub fn get_mut(&mut self, pointer: &str) -> Option<&mut Json> {
+        let mut current = self;
+        for token in pointer_tokens(pointer).ok()? {
+            current = match current {
+                Json::Object(members) => members.get_mut(&token)?,
+                Json::Array(elements) => elements.get_mut(array_index(&token)?)?,
+                _ => return None,
+            };
+        }
+        Some(current)
+    }
+
+    /// Add `value` where `pointer` points, as the `add` operation of JSON Patch does:
+    /// an object member is set (the previous value is returned), an array element is inserted
+    /// before the one at that index, and `-` appends to an array. The parent must exist.
+    pub fn insert(&mut self, pointer: &str, value: Json) -> Result<Option<Json>, PathError> {
+        if pointer.is_empty() {
+            return Ok(Some(std::mem::replace(self, value)));
+        }
+        let (parent, last) = split_last(pointer)?;
+        match self.get_mut(parent) {
+            Some(Json::Object(members)) => Ok(members.insert(last, value)),
+            Some(Json::Array(elements)) => {
+                let index = if last == "-" {
+                    elements.len()
+                } else {
+                    array_index(&last)
+                        .filter(|&index| index <= elements.len())
+                        .ok_or_else(|| PathError::new(pointer, "array index out of bounds"))?
+                };
+                elements.insert(index, value);
+                Ok(None)
+            }
+            Some(_) => Err(PathError::new(
+                pointer,
+                "only objects and arrays have members",
+            )),
+            None => Err(PathError::new(pointer, "the parent does not exist")),
+        }
+    }
+
+    /// Take out the value `pointer` points to, later array elements move down by one.
+    pub fn remove(&mut self, pointer: &str) -> Result<Json, PathError> {
+        let (parent, last) = split_last(pointer)?;
+        let removed = match self.get_mut(parent) {
+            Some(Json::Object(members)) => members.remove(&last),
+            Some(Json::Array(elements)) => array_index(&last)
+                .filter(|&index| index < elements.len())
+                .map(|index| elements.remove(index)),
+            _ => None,
+        };
+        removed.ok_or_else(|| PathError::new(pointer, "nothing to remove"))
+    }
+
+    /// Every value matching a JSONPath-like `path`, in document order (object keys sorted):
+    ///
+    /// - `$` the whole document, every path starts with it
+    /// - `.name` or `['name']` a member of an object
+    /// - `[2]` an array element, `[-1]` the last one
+    /// - `.*` or `[*]` every member or element
+    /// - `..name` (also `..*` and `..[0]`) the same, at any depth
+    ///
+    /// e.g. `$.store.books[*].title` or `$..price`.
+    pub fn query(&self, path: &str) -> Result<Vec<&Json>, PathError> {
+        let mut current = vec![self];
+        for (descendants, selector) in parse_path(path)? {
+            if descendants {
+                let mut all = vec![];
+                for value in current {
+                    collect_descendants(value, &mut all);
+                }
+                current = all;
+            }
+            current = current
+                .into_iter()
+                .flat_map(|value| select(value, &selector))
+                .collect();
+        }
+        Ok(current)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Selector {
+    Key(String),
+    Index(i64),
+    Wildcard,
+}
+
+fn sorted_members(members: &HashMap<String, Json>) -> Vec<&Json> {
+    let mut keys: Vec<&String> = members.keys().collect();
+    keys.sort();
+    keys.into_iter().map(|key| &members[key]).collect()
+}
+
+fn select<'a>(value: &'a Json, selector: &Selector) -> Vec<&'a Json> {
+    match (value, selector) {
+        (Json::Object(members), Selector::Key(key)) => members.get(key).into_iter().collect(),
+        (Json::Array(elements), &Selector::Index(index)) => {
+            let index = if index < 0 {
+                elements.len() as i64 + index
+            } else {
+                index
+            };
+            usize::try_from(index)
+                .ok()
+                .and_then(|index| elements.get(index))
+                .into_iter()
+                .collect()
+        }
+        (Json::Object(members), Selector::Wildcard) => sorted_members(members),
+        (Json::Array(elements), Selector::Wildcard) => elements.iter().collect(),
+        _ => vec![],
+    }
+}
+
+/// `value` and everything inside it, parents before their children.
+fn collect_descendants<'a>(value: &'a Json, all: &mut Vec<&'a Json>) {
+    all.push(value);
+    for child in select(value, &Selector::Wildcard) {
+        collect_descendants(child, all);
+    }
+}
+
+/// A path as a list of selectors, each one applied to descendants at any depth or only to children.
+fn parse_path(path: &str) -> Result<Vec<(bool, Selector)>, PathError> {
+    let error = |message: &str| Err(PathError::new(path, message));
+    let mut rest = match path.strip_prefix('$') {
+        Some(rest) => rest,
+        None => return error("a path must start with '$'"),
+    };
+    let mut segments = vec![];
+    while !rest.is_empty() {
+        let descendants = rest.starts_with("..");
+        if descendants {
+            rest = &rest[2..];
+        } else if rest.starts_with('.') {
+            rest = &rest[1..];
+        } else if !rest.starts_with('[') {
+            return error("expected '.' or '['");
+        }
+
+        if let Some(bracket) = rest.strip_prefix('[') {
+            let close = match bracket.find(']') {
+                Some(close) => close,
+                None => return error("unclosed '['"),
+            };
+            let inside = bracket[..close].trim();
+            let quoted = inside.len() >= 2
+                && (inside.starts_with('\'') && inside.ends_with('\'')
+                    || inside.starts_with('"') && inside.ends_with('"'));
+            let selector = if inside == "*" {
+                Selector::Wildcard
+            } else if quoted {
+                Selector::Key(inside[1..inside.len() - 1].to_string())
+            } else {
+                match inside.parse() {
+                    Ok(index) => Selector::Index(index),
+                    Err(_) => return error("expected an index, '*' or a quoted name in '[]'"),
+                }
+            };
+            segments.push((descendants, selector));
+            rest = &bracket[close + 1..];
+        } else {
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            let name = &rest[..end];
+            let selector = match name {
+                "" => return error("expected a name"),
+                "*" => Selector::Wildcard,
+                name => Selector::Key(name.to_string()),
+            };
+            segments.push((descendants, selector));
+            rest = &rest[end..];
+        }
+    }
+    Ok(segments)
+}
+
+static NULL: Json = Json::Null;
+
+/// `json["key"]`, `Json::Null` if this is not an object or has no such member.
+impl Index<&str> for Json {
+    type Output = Json;
+    fn index(&self, key: &str) -> &Json {
+        match self {
+            Json::Object(members) => members.get(key).unwrap_or(&NULL),
+            _ => &NULL,
+        }
+    }
+}
+
+/// `json[0]`, `Json::Null` if this is not an array or is too short.
+impl Index<usize> for Json {
+    type Output = Json;
+    fn index(&self, index: usize) -> &Json {
+        match self {
+            Json::Array(elements) => elements.get(index).unwrap_or(&NULL),
+            _ => &NULL,
+        }
+    }
+}
+
+/// `json["key"] = value` adds the member if needed, and turns `Json::Null` into an object.
+/// Panics on anything else than an object or null.
+impl IndexMut<&str> for Json {
+    fn index_mut(&mut self, key: &str) -> &mut Json {
+        if let Json::Null = self {
+            *self = Json::Object(Box::default());
+        }
+        match self {
+            Json::Object(members) => members.entry(key.to_string()).or_insert(Json::Null),
+            other => panic!("cannot index {} with \"{}\"", other, key),
+        }
+    }
+}
+
+/// Panics if this is not an array or `index` is out of bounds, like indexing a `Vec`.
+impl IndexMut<usize> for Json {
+    fn index_mut(&mut self, index: usize) -> &mut Json {
+        match self {
+            Json::Array(elements) => &mut elements[index],
+            other => panic!("cannot index {} with {}", other, index),
+        }
+    }
+}
+
+#[test]
+fn test_pointer_rfc6901_examples() {
+    let doc = Json::parse(
+        r#"{"foo": ["bar", "baz"], "": 0, "a/b": 1, "c%d": 2, "e^f": 3,
+            "g|h": 4, "i\\j": 5, "k\"l": 6, " ": 7, "m~n": 8}"#,
+    )
+    .unwrap();
+    assert_eq!(doc.get(""), Some(&doc));
+    assert_eq!(doc.get("/foo"), Some(&doc["foo"]));
+    assert_eq!(doc.get("/foo/0"), Some(&Json::String("bar".to_string())));
+    for (pointer, n) in [
+        ("/", 0.0),
+        ("/a~1b", 1.0),
+        ("/c%d", 2.0),
+        ("/e^f", 3.0),
+        ("/g|h", 4.0),
+        ("/i\\j", 5.0),
+        ("/k\"l", 6.0),
+        ("/ ", 7.0),
+        ("/m~0n", 8.0),
+    ] {
+        assert_eq!(doc.get(pointer), Some(&Json::Number(n)), "{}", pointer);
+    }
+    for missing in [
+        "foo", "/bar", "/foo/2", "/foo/01", "/foo/-", "/foo/0/x", "/a~1b/0",
+    ] {
+        assert_eq!(doc.get(missing), None, "{}", missing);
+    }
+}
+
+#[test]
+fn test_get_mut_insert_remove() {
+    let mut doc = Json::parse(r#"{"list": [1, 3], "m~x": {}}"#).unwrap();
+    *doc.get_mut("/list/1").unwrap() = Json::Number(2.0);
+    assert_eq!(doc.insert("/list/0", Json::Number(0.0)), Ok(None));
+    assert_eq!(doc.insert("/list/-", Json::Number(3.0)), Ok(None));
+    assert_eq!(doc["list"].to_string(), "[0,1,2,3]");
+    assert_eq!(doc.insert("/m~0x/k", Json::Bool(true)), Ok(None));
+    assert_eq!(
+        doc.insert("/m~0x/k", Json::Null),
+        Ok(Some(Json::Bool(true)))
+    );
+
+    assert_eq!(doc.remove("/list/1"), Ok(Json::Number(1.0)));
+    assert_eq!(
+        doc.remove("/m~0x"),
+        Ok(Json::parse(r#"{"k":null}"#).unwrap())
+    );
+    assert_eq!(doc.to_string(), r#"{"list":[0,2,3]}"#);
+
+    let error = doc.insert("/list/9", Json::Null).unwrap_err();
+    assert_eq!(error.to_string(), "array index out of bounds in '/list/9'");
+    assert!(doc.insert("/none/x", Json::Null).is_err());
+    assert!(doc.insert("/list/0/x", Json::Null).is_err());
+    assert!(doc.remove("/list/3").is_err());
+    assert!(doc.remove("").is_err());
+    assert!(doc.get_mut("list").is_none());
+
+    assert_eq!(
+        doc.insert("", Json::Null),
+        Ok(Some(Json::parse(r#"{"list":[0,2,3]}"#).unwrap()))
+    );
+    assert_eq!(doc, Json::Null);
+}
+
+#[test]
+fn test_index() {
+    let mut doc = Json::parse(r#"{"a": [10, {"b": true}]}"#).unwrap();
+    assert_eq!(doc["a"][0], Json::Number(10.0));
+    assert_eq!(doc["a"][1]["b"], Json::Bool(true));
+    assert_eq!(doc["missing"][3]["deeper"], Json::Null);
+    assert_eq!(doc["a"]["not an object"], Json::Null);
+
+    doc["a"][0] = Json::String("ten".to_string());
+    doc["new"]["nested"] = Json::Number(1.0);
+    assert_eq!(
+        doc.to_string(),
+        r#"{"a":["ten",{"b":true}],"new":{"nested":1}}"#
+    );
+}
+
+#[test]
+#[should_panic(expected = "cannot index")]
+fn test_index_mut_on_a_number_panics() {
+    let mut doc = Json::Number(1.0);
+    doc["key"] = Json::Null;
+}
+
+#[cfg(test)]
+fn store() -> Json {
+    Json::parse(
+        r#"{"store": {
+            "books": [
+                {"title": "Sayings of the Century", "price": 8.95},
+                {"title": "Sword of Honour", "price": 12.99},
+                {"title": "Moby Dick", "price": 8.99, "isbn": "0-553-21311-3"}
+            ],
+            "bicycle": {"color": "red", "price": 19.95}
+        }}"#,
+    )
+    .unwrap()
+}
+
+#[cfg(test)]
+fn query_text(doc: &Json, path: &str) -> Vec<String> {
+    doc.query(path)
+        .unwrap()
+        .iter()
+        .map(|value| value.to_string())
+        .collect()
+}
+
+#[test]
+fn test_query() {
+    let doc = store();
+    assert_eq!(
+        query_text(&doc, "$.store.books[*].title"),
+        vec![
+            "\"Sayings of the Century\"",
+            "\"Sword of Honour\"",
+            "\"Moby Dick\""
+        ]
+    );
+    assert_eq!(
+        query_text(&doc, "$['store'].books[-1].isbn"),
+        vec!["\"0-553-21311-3\""]
+    );
+    assert_eq!(query_text(&doc, "$.store.books[1].price"), vec!["12.99"]);
+    // bicycle comes before books in key order
+    assert_eq!(
+        query_text(&doc, "$..price"),
+        vec!["19.95", "8.95", "12.99", "8.99"]
+    );
+    assert_eq!(query_text(&doc, "$.store.*").len(), 2);
+    assert_eq!(
+        query_text(&doc, "$..[0].title"),
+        vec!["\"Sayings of the Century\""]
+    );
+    assert_eq!(query_text(&doc, "$"), vec![doc.to_string()]);
+    assert!(query_text(&doc, "$.store.books[7]").is_empty());
+    assert!(query_text(&doc, "$.nothing.here").is_empty());
+}
+
+#[test]
+fn test_query_errors() {
+    let doc = store();
+    for path in ["store", "$.", "$.store.books[", "$[x]", "$store"] {
+        assert!(doc.query(path).is_err(), "{}", path);
+    }
+    assert_eq!(
+        doc.query("$[x]").unwrap_err().message,
+        "expected an index, '*' or a quoted name in '[]'"
+    );
+}
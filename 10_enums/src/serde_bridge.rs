@@ -0,0 +1,769 @@
+// Json as a serde data format: any `Serialize` type becomes a Json value and any
+// `Deserialize` type can be read back from one, without going through text
+use std::collections::{hash_map, HashMap};
+use std::fmt;
+use std::vec;
+
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::ser;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::convert::ConvertError;
+use crate::json::Json;
+
+impl ser::Error for ConvertError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        ConvertError::new(message.to_string())
+    }
+}
+
+impl de::Error for ConvertError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        ConvertError::new(message.to_string())
+    }
+}
+
+/// Any serializable value as Json, e.g. `to_json(&player)`.
+/// Integers above 2^53 lose precision, JSON numbers are `f64` here.
+pub fn to_json<T: Serialize + ?Sized>(value: &T) -> Result<Json, ConvertError> {
+    value.serialize(JsonSerializer)
+}
+
+/// The value of type `T` that `json` describes, e.g. `from_json::<Player>(json)`.
+pub fn from_json<T: DeserializeOwned>(json: Json) -> Result<T, ConvertError> {
+    T::deserialize(json)
+}
+
+/// Serializes a Json value like any other, so it can be written by every serde format.
+/// Object keys are written sorted, as in `Json::to_string_compact`.
+impl Serialize for Json {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Json::Null => serializer.serialize_unit(),
+            Json::Bool(b) => serializer.serialize_bool(*b),
+            Json::Number(n) => serializer.serialize_f64(*n),
+            Json::String(s) => serializer.serialize_str(s),
+            Json::Array(elements) => serializer.collect_seq(elements),
+            Json::Object(members) => {
+                let mut keys: Vec<&String> = members.keys().collect();
+                keys.sort();
+                serializer.collect_map(keys.into_iter().map(|key| (key, &members[key])))
+            }
+        }
+    }
+}
+
+struct JsonVisitor;
+
+impl<'de> Visitor<'de> for JsonVisitor {
+    type Value = Json;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any JSON value")
+    }
+
+    fn visit_bool<E>(self, b: bool) -> Result<Json, E> {
+        Ok(Json::Bool(b))
+    }
+
+    fn visit_i64<E>(self, n: i64) -> Result<Json, E> {
+        Ok(Json::Number(n as f64))
+    }
+
+    fn visit_u64<E>(self, n: u64) -> Result<Json, E> {
+        Ok(Json::Number(n as f64))
+    }
+
+    fn visit_f64<E>(self, n: f64) -> Result<Json, E> {
+        Ok(Json::Number(n))
+    }
+
+    fn visit_str<E>(self, s: &str) -> Result<Json, E> {
+        Ok(Json::String(s.to_string()))
+    }
+
+    fn visit_string<E>(self, s: String) -> Result<Json, E> {
+        Ok(Json::String(s))
+    }
+
+    fn visit_unit<E>(self) -> Result<Json, E> {
+        Ok(Json::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<Json, E> {
+        Ok(Json::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Json, D::Error> {
+        Json::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Json, A::Error> {
+        let mut elements = vec![];
+        while let Some(element) = seq.next_element()? {
+            elements.push(element);
+        }
+        Ok(Json::Array(elements))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Json, A::Error> {
+        let mut members = HashMap::new();
+        while let Some((key, value)) = map.next_entry()? {
+            members.insert(key, value);
+        }
+        Ok(Json::Object(Box::new(members)))
+    }
+}
+
+/// Reads a Json value from any serde format.
+impl<'de> Deserialize<'de> for Json {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Json, D::Error> {
+        deserializer.deserialize_any(JsonVisitor)
+    }
+}
+
+struct JsonSerializer;
+
+/// Elements of a sequence or tuple, kept under the variant name for tuple variants.
+struct SeqSerializer {
+    elements: Vec<Json>,
+    variant: Option<&'static str>,
+}
+
+/// Members of a map or struct, kept under the variant name for struct variants.
+struct MapSerializer {
+    members: HashMap<String, Json>,
+    next_key: Option<String>,
+    variant: Option<&'static str>,
+}
+
+/// Enum variants with data are `{"Variant": data}`, as serde_json writes them.
+fn tagged(variant: &'static str, value: Json) -> Json {
+    let mut members = HashMap::new();
+    members.insert(variant.to_string(), value);
+    Json::Object(Box::new(members))
+}
+
+impl Serializer for JsonSerializer {
+    type Ok = Json;
+    type Error = ConvertError;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = MapSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Json, ConvertError> {
+        Ok(Json::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Json, ConvertError> {
+        Ok(Json::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Json, ConvertError> {
+        Ok(Json::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Json, ConvertError> {
+        Ok(Json::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Json, ConvertError> {
+        Ok(Json::from(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Json, ConvertError> {
+        Ok(Json::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Json, ConvertError> {
+        Ok(Json::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Json, ConvertError> {
+        Ok(Json::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Json, ConvertError> {
+        Ok(Json::from(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Json, ConvertError> {
+        Ok(Json::from(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Json, ConvertError> {
+        Ok(Json::from(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Json, ConvertError> {
+        Ok(Json::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Json, ConvertError> {
+        Ok(Json::from(v))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Json, ConvertError> {
+        Ok(Json::from(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<Json, ConvertError> {
+        Ok(Json::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Json, ConvertError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Json, ConvertError> {
+        Ok(Json::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Json, ConvertError> {
+        Ok(Json::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Json, ConvertError> {
+        Ok(Json::from(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Json, ConvertError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Json, ConvertError> {
+        Ok(tagged(variant, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, ConvertError> {
+        Ok(SeqSerializer {
+            elements: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, ConvertError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, ConvertError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, ConvertError> {
+        Ok(SeqSerializer {
+            elements: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer, ConvertError> {
+        Ok(MapSerializer {
+            members: HashMap::new(),
+            next_key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<MapSerializer, ConvertError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<MapSerializer, ConvertError> {
+        Ok(MapSerializer {
+            members: HashMap::new(),
+            next_key: None,
+            variant: Some(variant),
+        })
+    }
+}
+
+impl SeqSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConvertError> {
+        self.elements.push(value.serialize(JsonSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Json, ConvertError> {
+        let array = Json::Array(self.elements);
+        Ok(match self.variant {
+            Some(variant) => tagged(variant, array),
+            None => array,
+        })
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Json;
+    type Error = ConvertError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConvertError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Json, ConvertError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Json;
+    type Error = ConvertError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConvertError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Json, ConvertError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Json;
+    type Error = ConvertError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConvertError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Json, ConvertError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+    type Ok = Json;
+    type Error = ConvertError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConvertError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Json, ConvertError> {
+        self.finish()
+    }
+}
+
+impl MapSerializer {
+    fn finish(self) -> Result<Json, ConvertError> {
+        let object = Json::Object(Box::new(self.members));
+        Ok(match self.variant {
+            Some(variant) => tagged(variant, object),
+            None => object,
+        })
+    }
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Json;
+    type Error = ConvertError;
+
+    /// JSON keys are strings, numbers and booleans are written as text like serde_json does.
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), ConvertError> {
+        self.next_key = Some(match key.serialize(JsonSerializer)? {
+            Json::String(s) => s,
+            key @ (Json::Number(_) | Json::Bool(_)) => key.to_string(),
+            other => {
+                return Err(ConvertError::new(format!(
+                    "object keys must be strings, found {}",
+                    other.kind()
+                )))
+            }
+        });
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConvertError> {
+        let key = self.next_key.take().expect("serialize_key is called first");
+        self.members.insert(key, value.serialize(JsonSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Json, ConvertError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = Json;
+    type Error = ConvertError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ConvertError> {
+        self.members
+            .insert(key.to_string(), value.serialize(JsonSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Json, ConvertError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for MapSerializer {
+    type Ok = Json;
+    type Error = ConvertError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), ConvertError> {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<Json, ConvertError> {
+        self.finish()
+    }
+}
+
+/// A Json value is its own deserializer: `Player::deserialize(json)`.
+impl<'de> Deserializer<'de> for Json {
+    type Error = ConvertError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ConvertError> {
+        match self {
+            Json::Null => visitor.visit_unit(),
+            Json::Bool(b) => visitor.visit_bool(b),
+            // whole numbers are offered as integers, so they can fill integer fields
+            Json::Number(n) if n.fract() == 0.0 && n >= 0.0 && n < u64::MAX as f64 => {
+                visitor.visit_u64(n as u64)
+            }
+            Json::Number(n) if n.fract() == 0.0 && n >= i64::MIN as f64 && n < 0.0 => {
+                visitor.visit_i64(n as i64)
+            }
+            Json::Number(n) => visitor.visit_f64(n),
+            Json::String(s) => visitor.visit_string(s),
+            Json::Array(elements) => visitor.visit_seq(SeqDeserializer {
+                elements: elements.into_iter(),
+                index: 0,
+            }),
+            Json::Object(members) => visitor.visit_map(MapDeserializer {
+                members: members.into_iter(),
+                value: None,
+            }),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ConvertError> {
+        match self {
+            Json::Null => visitor.visit_none(),
+            other => visitor.visit_some(other),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, ConvertError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ConvertError> {
+        let (variant, value) = match self {
+            Json::String(variant) => (variant, None),
+            Json::Object(members) if members.len() == 1 => {
+                let (variant, value) = members.into_iter().next().expect("one member");
+                (variant, Some(value))
+            }
+            other => {
+                return Err(ConvertError::new(format!(
+                    "expected an enum variant, a string or an object with one member, found {}",
+                    other.kind()
+                )))
+            }
+        };
+        visitor.visit_enum(EnumDeserializer { variant, value })
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, ConvertError> for Json {
+    type Deserializer = Json;
+
+    fn into_deserializer(self) -> Json {
+        self
+    }
+}
+
+struct SeqDeserializer {
+    elements: vec::IntoIter<Json>,
+    index: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqDeserializer {
+    type Error = ConvertError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, ConvertError> {
+        match self.elements.next() {
+            Some(element) => {
+                let index = self.index;
+                self.index += 1;
+                seed.deserialize(element)
+                    .map(Some)
+                    .map_err(|e| e.inside(&index.to_string()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.elements.len())
+    }
+}
+
+struct MapDeserializer {
+    members: hash_map::IntoIter<String, Json>,
+    // the value of the key just read, with the key for error paths
+    value: Option<(String, Json)>,
+}
+
+impl<'de> de::MapAccess<'de> for MapDeserializer {
+    type Error = ConvertError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, ConvertError> {
+        match self.members.next() {
+            Some((key, value)) => {
+                let deserialized = seed.deserialize(key.clone().into_deserializer())?;
+                self.value = Some((key, value));
+                Ok(Some(deserialized))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, ConvertError> {
+        let (key, value) = self.value.take().expect("next_key_seed is called first");
+        seed.deserialize(value).map_err(|e| e.inside(&key))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.members.len())
+    }
+}
+
+struct EnumDeserializer {
+    variant: String,
+    value: Option<Json>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer {
+    type Error = ConvertError;
+    type Variant = VariantDeserializer;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, VariantDeserializer), ConvertError> {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((variant, VariantDeserializer { value: self.value }))
+    }
+}
+
+struct VariantDeserializer {
+    value: Option<Json>,
+}
+
+impl<'de> de::VariantAccess<'de> for VariantDeserializer {
+    type Error = ConvertError;
+
+    fn unit_variant(self) -> Result<(), ConvertError> {
+        match self.value {
+            None | Some(Json::Null) => Ok(()),
+            Some(other) => Err(ConvertError::new(format!(
+                "expected a unit variant, found {}",
+                other.kind()
+            ))),
+        }
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, ConvertError> {
+        seed.deserialize(self.value.unwrap_or(Json::Null))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, ConvertError> {
+        match self.value {
+            Some(array @ Json::Array(_)) => array.deserialize_any(visitor),
+            other => Err(ConvertError::new(format!(
+                "expected a tuple variant, found {}",
+                other.unwrap_or(Json::Null).kind()
+            ))),
+        }
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ConvertError> {
+        match self.value {
+            Some(object @ Json::Object(_)) => object.deserialize_any(visitor),
+            other => Err(ConvertError::new(format!(
+                "expected a struct variant, found {}",
+                other.unwrap_or(Json::Null).kind()
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Player {
+    name: String,
+    items: Vec<String>,
+    health: u64,
+}
+
+#[cfg(test)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+enum Event {
+    Quit,
+    Moved(i32, i32),
+    Renamed(String),
+    Hit { by: Player, damage: Option<u8> },
+}
+
+#[test]
+fn test_player_round_trip() {
+    let player = Player {
+        name: "Ferris".to_string(),
+        items: vec!["sword".to_string(), "shell".to_string()],
+        health: 100,
+    };
+    let json = to_json(&player).unwrap();
+    assert_eq!(
+        json.to_string(),
+        r#"{"health":100,"items":["sword","shell"],"name":"Ferris"}"#
+    );
+    assert_eq!(from_json::<Player>(json).unwrap(), player);
+
+    let parsed = Json::parse(r#"{"name": "Crab", "items": [], "health": 7}"#).unwrap();
+    assert_eq!(from_json::<Player>(parsed).unwrap().name, "Crab");
+}
+
+#[test]
+fn test_enums_round_trip() {
+    let player = Player {
+        name: "P".to_string(),
+        items: vec![],
+        health: 1,
+    };
+    let events = vec![
+        Event::Quit,
+        Event::Moved(-1, 2),
+        Event::Renamed("Q".to_string()),
+        Event::Hit {
+            by: player,
+            damage: None,
+        },
+    ];
+    let json = to_json(&events).unwrap();
+    assert_eq!(json[0], Json::from("Quit"));
+    assert_eq!(json[1].to_string(), r#"{"Moved":[-1,2]}"#);
+    assert_eq!(json[3]["Hit"]["damage"], Json::Null);
+    assert_eq!(from_json::<Vec<Event>>(json).unwrap(), events);
+}
+
+#[test]
+fn test_deserialize_errors_have_a_path() {
+    let json = Json::parse(r#"[{"name": "A", "items": ["x", 3], "health": 1}]"#).unwrap();
+    let error = from_json::<Vec<Player>>(json).unwrap_err();
+    assert_eq!(error.path, "/0/items/1");
+    assert!(error.message.contains("expected a string"), "{}", error);
+
+    let json = Json::parse(r#"{"name": "A", "items": [], "health": -5}"#).unwrap();
+    let error = from_json::<Player>(json).unwrap_err();
+    assert_eq!(error.path, "/health");
+    let json = Json::parse(r#"{"name": "A", "items": []}"#).unwrap();
+    assert_eq!(
+        from_json::<Player>(json).unwrap_err().message,
+        "missing field `health`"
+    );
+}
+
+#[test]
+fn test_json_through_serde() {
+    let doc = Json::parse(r#"{"a": [1, 2.5, null, true, "s"], "b": {}}"#).unwrap();
+    // Json serialized into Json, and deserialized from Json, is itself
+    assert_eq!(to_json(&doc).unwrap(), doc);
+    assert_eq!(from_json::<Json>(doc.clone()).unwrap(), doc);
+
+    let mut map = std::collections::BTreeMap::new();
+    map.insert(1, 'x');
+    assert_eq!(to_json(&map).unwrap().to_string(), r#"{"1":"x"}"#);
+    assert!(to_json(&HashMap::from([(vec![1], 0)])).is_err());
+    assert_eq!(
+        from_json::<(u8, Option<bool>)>(json!([255, null])).unwrap(),
+        (255, None)
+    );
+}
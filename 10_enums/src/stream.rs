@@ -0,0 +1,684 @@
+// Reading JSON as a stream of events, for documents too large to hold as one Json
+use std::io::{self, BufRead};
+
+use crate::json::{Json, JsonError, MAX_DEPTH};
+
+/// What the reader found, in document order. Strings, numbers, booleans and null come whole
+/// as a `Value`, the members of an object as a `Key` followed by the events of its value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    StartObject,
+    EndObject,
+    StartArray,
+    EndArray,
+    Key(String),
+    Value(Json),
+}
+
+/// A container the reader is in, with how far it got in it.
+#[derive(Debug)]
+enum Frame {
+    /// the number of elements started so far
+    Array(usize),
+    /// the last key read
+    Object(Option<String>),
+}
+
+/// What the grammar allows next.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Expect {
+    Value,
+    /// a value or `]`
+    FirstElement,
+    /// a key or `}`
+    FirstKey,
+    Key,
+    Colon,
+    /// `,` or the end of the container, or another document at the top
+    AfterValue,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Position {
+    offset: u64,
+    line: usize,
+    column: usize,
+}
+
+impl Position {
+    fn advance(&mut self, bytes: &[u8]) {
+        self.offset += bytes.len() as u64;
+        for &b in bytes {
+            if b == b'\n' {
+                self.line += 1;
+                self.column = 1;
+            } else if b & 0xC0 != 0x80 {
+                // columns count characters, not the continuation bytes of UTF-8
+                self.column += 1;
+            }
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> JsonError {
+        JsonError {
+            message: message.into(),
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn read_error(&self, error: io::Error) -> JsonError {
+        self.error(format!("could not read the input: {}", error))
+    }
+}
+
+/// A pull parser over any `BufRead`. It only holds the containers it is in and the scalar it is
+/// reading, never the document, so the size of the input does not matter. The input may hold
+/// several documents one after the other, as JSON Lines logs do.
+///
+/// After an error the reader is lost and should be dropped.
+pub struct JsonReader<R> {
+    input: R,
+    position: Position,
+    stack: Vec<Frame>,
+    expect: Expect,
+    failed: bool,
+}
+
+impl<R: BufRead> JsonReader<R> {
+    pub fn new(input: R) -> Self {
+        JsonReader {
+            input,
+            position: Position {
+                offset: 0,
+                line: 1,
+                column: 1,
+            },
+            stack: vec![],
+            expect: Expect::AfterValue,
+            failed: false,
+        }
+    }
+
+    /// How many bytes have been read.
+    pub fn offset(&self) -> u64 {
+        self.position.offset
+    }
+
+    /// How many arrays and objects the reader is in.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// A JSON pointer to the value of the last event, `""` for the document itself.
+    pub fn path(&self) -> String {
+        let mut path = String::new();
+        for frame in &self.stack {
+            match frame {
+                Frame::Array(0) | Frame::Object(None) => {}
+                Frame::Array(started) => path += &format!("/{}", started - 1),
+                Frame::Object(Some(key)) => {
+                    path.push('/');
+                    path += &key.replace('~', "~0").replace('/', "~1");
+                }
+            }
+        }
+        path
+    }
+
+    /// The next event with the byte offset where it starts, `None` at the end of the input.
+    pub fn next_event(&mut self) -> Result<Option<(u64, Event)>, JsonError> {
+        let Some(byte) = self.advance()? else {
+            if self.stack.is_empty() && self.expect == Expect::AfterValue {
+                return Ok(None);
+            }
+            return Err(self.position.error("unexpected end of input"));
+        };
+        let start = self.position;
+        let in_array = matches!(self.stack.last(), Some(Frame::Array(_)));
+        let event = match (self.expect, byte) {
+            (Expect::FirstElement | Expect::AfterValue, b']') if in_array => {
+                self.end_container(byte);
+                Event::EndArray
+            }
+            (Expect::FirstKey | Expect::AfterValue, b'}') if !in_array => {
+                self.end_container(byte);
+                Event::EndObject
+            }
+            (Expect::FirstKey | Expect::Key, b'"') => {
+                let key = self.string()?;
+                if let Some(Frame::Object(last)) = self.stack.last_mut() {
+                    *last = Some(key.clone());
+                }
+                self.expect = Expect::Colon;
+                Event::Key(key)
+            }
+            (Expect::FirstKey | Expect::Key, _) => {
+                return Err(start.error(format!("expected a key, found {}", self.found())))
+            }
+            (Expect::AfterValue, _) => {
+                let close = if in_array { ']' } else { '}' };
+                return Err(start.error(format!(
+                    "expected ',' or '{}', found {}",
+                    close,
+                    self.found()
+                )));
+            }
+            _ => self.value_event(byte)?,
+        };
+        Ok(Some((start.offset, event)))
+    }
+
+    /// The next value read whole, or `None` at the end of an array, an object or the input.
+    /// Call it where a value can start: after a `Key`, inside an array, or between documents.
+    pub fn read_value(&mut self) -> Result<Option<Json>, JsonError> {
+        match self.advance()? {
+            None | Some(b']' | b'}') => Ok(None),
+            Some(_) if matches!(self.expect, Expect::FirstKey | Expect::Key) => {
+                Err(self.position.error("expected a value, found a key"))
+            }
+            Some(_) => match self.next_event()? {
+                Some((_, event)) => self.build(event).map(Some),
+                None => Ok(None),
+            },
+        }
+    }
+
+    /// Read on to the value at `pointer` in the current document and return it whole.
+    /// Everything before it is skipped without being built, `None` if the document ends first.
+    pub fn select(&mut self, pointer: &str) -> Result<Option<Json>, JsonError> {
+        while let Some((_, event)) = self.next_event()? {
+            if let Event::StartObject | Event::StartArray | Event::Value(_) = event {
+                let path = self.path();
+                if path == pointer {
+                    return self.build(event).map(Some);
+                }
+                let is_container = !matches!(event, Event::Value(_));
+                if is_container && !pointer.starts_with(&format!("{}/", path)) {
+                    self.skip_container()?;
+                }
+            }
+            if self.stack.is_empty() {
+                break;
+            }
+        }
+        Ok(None)
+    }
+
+    /// The value that `first` starts, with the events that follow it.
+    fn build(&mut self, first: Event) -> Result<Json, JsonError> {
+        // the containers being built, with the key waiting for its value in objects
+        let mut open: Vec<(Json, Option<String>)> = vec![];
+        let mut event = first;
+        loop {
+            let value = match event {
+                Event::StartObject => {
+                    open.push((Json::Object(Box::default()), None));
+                    None
+                }
+                Event::StartArray => {
+                    open.push((Json::Array(vec![]), None));
+                    None
+                }
+                Event::Key(key) => {
+                    if let Some((_, waiting)) = open.last_mut() {
+                        *waiting = Some(key);
+                    }
+                    None
+                }
+                Event::Value(value) => Some(value),
+                Event::EndObject | Event::EndArray => open.pop().map(|(value, _)| value),
+            };
+            if let Some(value) = value {
+                match open.last_mut() {
+                    None => return Ok(value),
+                    Some((Json::Array(elements), _)) => elements.push(value),
+                    Some((Json::Object(members), key)) => {
+                        let key = key.take().expect("a key comes before a member's value");
+                        members.insert(key, value);
+                    }
+                    Some(_) => unreachable!("only arrays and objects are open"),
+                }
+            }
+            event = match self.next_event()? {
+                Some((_, event)) => event,
+                None => return Err(self.position.error("unexpected end of input")),
+            };
+        }
+    }
+
+    /// Read up to the end of the container just started.
+    fn skip_container(&mut self) -> Result<(), JsonError> {
+        let depth = self.stack.len();
+        while self.stack.len() >= depth {
+            if self.next_event()?.is_none() {
+                return Err(self.position.error("unexpected end of input"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Skip whitespace and separators up to the first byte of the next event, without taking it.
+    fn advance(&mut self) -> Result<Option<u8>, JsonError> {
+        if self.position.offset == 0 {
+            self.skip_byte_order_mark()?;
+        }
+        loop {
+            self.take_while(None, |b| matches!(b, b' ' | b'\t' | b'\n' | b'\r'))?;
+            let byte = self.peek()?;
+            match (self.expect, byte) {
+                (Expect::Colon, Some(b':')) => {
+                    self.bump(b':');
+                    self.expect = Expect::Value;
+                }
+                (Expect::Colon, _) => return Err(self.unexpected("':'")),
+                (Expect::AfterValue, Some(b',')) if !self.stack.is_empty() => {
+                    self.bump(b',');
+                    self.expect = match self.stack.last() {
+                        Some(Frame::Object(_)) => Expect::Key,
+                        _ => Expect::Value,
+                    };
+                }
+                // the next document
+                (Expect::AfterValue, Some(_)) if self.stack.is_empty() => {
+                    self.expect = Expect::Value
+                }
+                _ => return Ok(byte),
+            }
+        }
+    }
+
+    fn value_event(&mut self, byte: u8) -> Result<Event, JsonError> {
+        if let Some(Frame::Array(started)) = self.stack.last_mut() {
+            *started += 1;
+        }
+        if let b'{' | b'[' = byte {
+            if self.stack.len() == MAX_DEPTH {
+                return Err(self
+                    .position
+                    .error(format!("nested deeper than {} levels", MAX_DEPTH)));
+            }
+            self.bump(byte);
+            return Ok(if byte == b'{' {
+                self.stack.push(Frame::Object(None));
+                self.expect = Expect::FirstKey;
+                Event::StartObject
+            } else {
+                self.stack.push(Frame::Array(0));
+                self.expect = Expect::FirstElement;
+                Event::StartArray
+            });
+        }
+        let value = match byte {
+            b'"' => Json::String(self.string()?),
+            b'-' | b'0'..=b'9' => self.number()?,
+            b'a'..=b'z' => self.literal()?,
+            _ => return Err(self.unexpected("a value")),
+        };
+        self.expect = Expect::AfterValue;
+        Ok(Event::Value(value))
+    }
+
+    fn end_container(&mut self, byte: u8) {
+        self.bump(byte);
+        self.stack.pop();
+        self.expect = Expect::AfterValue;
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        let start = self.position;
+        let mut raw = vec![b'"'];
+        self.bump(b'"');
+        loop {
+            self.take_while(Some(&mut raw), |b| b != b'"' && b != b'\\')?;
+            match self.peek()? {
+                Some(b'"') => {
+                    self.bump(b'"');
+                    raw.push(b'"');
+                    break;
+                }
+                Some(b'\\') => {
+                    // the escaped byte too, so that \" does not end the string
+                    self.bump(b'\\');
+                    raw.push(b'\\');
+                    if let Some(escaped) = self.peek()? {
+                        self.bump(escaped);
+                        raw.push(escaped);
+                    }
+                }
+                _ => return Err(self.position.error("unterminated string")),
+            }
+        }
+        match scalar(start, raw)? {
+            Json::String(s) => Ok(s),
+            _ => unreachable!("a quoted token is a string"),
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, JsonError> {
+        let start = self.position;
+        let mut raw = vec![];
+        let digit = |b: u8| b.is_ascii_digit();
+        self.take_if(&mut raw, |b| b == b'-')?;
+        self.take_while(Some(&mut raw), digit)?;
+        if self.take_if(&mut raw, |b| b == b'.')? {
+            self.take_while(Some(&mut raw), digit)?;
+        }
+        if self.take_if(&mut raw, |b| b == b'e' || b == b'E')? {
+            self.take_if(&mut raw, |b| b == b'+' || b == b'-')?;
+            self.take_while(Some(&mut raw), digit)?;
+        }
+        scalar(start, raw)
+    }
+
+    fn literal(&mut self) -> Result<Json, JsonError> {
+        let start = self.position;
+        let mut word = vec![];
+        self.take_while(Some(&mut word), |b| b.is_ascii_alphanumeric())?;
+        match &word[..] {
+            b"null" => Ok(Json::Null),
+            b"true" => Ok(Json::Bool(true)),
+            b"false" => Ok(Json::Bool(false)),
+            _ => Err(start.error(format!(
+                "expected a value, found '{}'",
+                String::from_utf8_lossy(&word)
+            ))),
+        }
+    }
+
+    fn skip_byte_order_mark(&mut self) -> Result<(), JsonError> {
+        let buf = self
+            .input
+            .fill_buf()
+            .map_err(|e| self.position.read_error(e))?;
+        if buf.starts_with("\u{feff}".as_bytes()) {
+            self.input.consume(3);
+            self.position.offset = 3;
+        }
+        Ok(())
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>, JsonError> {
+        match self.input.fill_buf() {
+            Ok(buf) => Ok(buf.first().copied()),
+            Err(e) => Err(self.position.read_error(e)),
+        }
+    }
+
+    /// Take the byte just peeked.
+    fn bump(&mut self, byte: u8) {
+        self.input.consume(1);
+        self.position.advance(&[byte]);
+    }
+
+    /// Take the next byte into `out` if it is `wanted`.
+    fn take_if(
+        &mut self,
+        out: &mut Vec<u8>,
+        wanted: impl Fn(u8) -> bool,
+    ) -> Result<bool, JsonError> {
+        match self.peek()? {
+            Some(byte) if wanted(byte) => {
+                self.bump(byte);
+                out.push(byte);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Take the bytes that are `wanted`, a buffer at a time, into `out` if there is one.
+    fn take_while(
+        &mut self,
+        mut out: Option<&mut Vec<u8>>,
+        wanted: impl Fn(u8) -> bool,
+    ) -> Result<(), JsonError> {
+        loop {
+            let buf = match self.input.fill_buf() {
+                Ok(buf) => buf,
+                Err(e) => return Err(self.position.read_error(e)),
+            };
+            let n = buf.iter().position(|&b| !wanted(b)).unwrap_or(buf.len());
+            if let Some(out) = out.as_deref_mut() {
+                out.extend_from_slice(&buf[..n]);
+            }
+            self.position.advance(&buf[..n]);
+            let done = n < buf.len() || buf.is_empty();
+            self.input.consume(n);
+            if done {
+                return Ok(());
+            }
+        }
+    }
+
+    fn unexpected(&mut self, expected: &str) -> JsonError {
+        let found = self.found();
+        self.position
+            .error(format!("expected {}, found {}", expected, found))
+    }
+
+    /// What was found instead of what was expected, for error messages.
+    fn found(&mut self) -> String {
+        match self.input.fill_buf() {
+            Ok([]) => "end of input".to_string(),
+            Ok(buf) => {
+                let len = match buf[0] {
+                    0..=0x7F => 1,
+                    0xF0.. => 4,
+                    0xE0.. => 3,
+                    _ => 2,
+                };
+                match std::str::from_utf8(&buf[..len.min(buf.len())]) {
+                    Ok(c) => format!("'{}'", c.escape_default()),
+                    Err(_) => "invalid UTF-8".to_string(),
+                }
+            }
+            Err(_) => "unreadable input".to_string(),
+        }
+    }
+}
+
+/// Check and convert the text of one string or number with `Json::parse`,
+/// moving the position of any error to where the token starts.
+fn scalar(start: Position, raw: Vec<u8>) -> Result<Json, JsonError> {
+    let text = String::from_utf8(raw).map_err(|_| start.error("invalid UTF-8 in string"))?;
+    Json::parse(&text).map_err(|error| JsonError {
+        message: error.message,
+        line: start.line + error.line - 1,
+        column: if error.line == 1 {
+            start.column + error.column - 1
+        } else {
+            error.column
+        },
+    })
+}
+
+/// The events stop after the first error.
+impl<R: BufRead> Iterator for JsonReader<R> {
+    type Item = Result<(u64, Event), JsonError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let next = self.next_event().transpose();
+        self.failed = matches!(next, Some(Err(_)));
+        next
+    }
+}
+
+#[cfg(test)]
+fn events(text: &str) -> Result<Vec<(u64, Event)>, JsonError> {
+    JsonReader::new(text.as_bytes()).collect()
+}
+
+#[test]
+fn test_events_and_offsets() {
+    let text = r#"{"a": [1, true], "b": {}, "c": "x"}"#;
+    assert_eq!(
+        events(text).unwrap(),
+        vec![
+            (0, Event::StartObject),
+            (1, Event::Key("a".to_string())),
+            (6, Event::StartArray),
+            (7, Event::Value(Json::Number(1.0))),
+            (10, Event::Value(Json::Bool(true))),
+            (14, Event::EndArray),
+            (17, Event::Key("b".to_string())),
+            (22, Event::StartObject),
+            (23, Event::EndObject),
+            (26, Event::Key("c".to_string())),
+            (31, Event::Value(Json::String("x".to_string()))),
+            (34, Event::EndObject),
+        ]
+    );
+    // offsets count bytes, a byte order mark included
+    let events = events("\u{feff}[\"é\", null]").unwrap();
+    assert_eq!(events[2], (10, Event::Value(Json::Null)));
+    assert!(self::events("").unwrap().is_empty());
+}
+
+#[test]
+fn test_same_values_as_parse() {
+    let documents = [
+        r#"{"name": "Saturn", "moons": 146, "rings": true, "density": 0.687, "symbol": "♄"}"#,
+        r#"[[], {}, [[{"a": [null]}]], -0.5e-3, "tab\tquote\"back\\slash", "𝄞 𝄞"]"#,
+        "  \"lonely\"\n",
+        "1e300",
+    ];
+    for text in documents {
+        // a one byte buffer splits every token
+        let input = io::BufReader::with_capacity(1, text.as_bytes());
+        let value = JsonReader::new(input).read_value().unwrap();
+        assert_eq!(value, Some(Json::parse(text).unwrap()), "{}", text);
+    }
+}
+
+#[test]
+fn test_read_values_one_at_a_time() {
+    // JSON Lines, then the elements of a large array without holding the array
+    let log = "{\"level\": \"info\", \"n\": 1}\n{\"level\": \"warn\", \"n\": 2}\n\n3 [4]\n";
+    let mut reader = JsonReader::new(log.as_bytes());
+    let mut documents = vec![];
+    while let Some(document) = reader.read_value().unwrap() {
+        documents.push(document.to_string());
+        if documents.len() == 1 {
+            // nothing past the end of the first line was read
+            assert_eq!(reader.offset(), log.find('\n').unwrap() as u64);
+        }
+    }
+    assert_eq!(
+        documents,
+        [
+            r#"{"level":"info","n":1}"#,
+            r#"{"level":"warn","n":2}"#,
+            "3",
+            "[4]"
+        ]
+    );
+
+    let mut reader = JsonReader::new(&b"{\"entries\": [1, {\"x\": 2}, [3]], \"after\": 4}"[..]);
+    assert_eq!(reader.next_event().unwrap().unwrap().1, Event::StartObject);
+    assert_eq!(
+        reader.next_event().unwrap().unwrap().1,
+        Event::Key("entries".to_string())
+    );
+    assert_eq!(reader.next_event().unwrap().unwrap().1, Event::StartArray);
+    let mut entries = vec![];
+    while let Some(entry) = reader.read_value().unwrap() {
+        assert_eq!(reader.depth(), 2);
+        entries.push(entry.to_string());
+    }
+    assert_eq!(entries, ["1", r#"{"x":2}"#, "[3]"]);
+    assert_eq!(reader.next_event().unwrap().unwrap().1, Event::EndArray);
+    assert_eq!(
+        reader.next_event().unwrap().unwrap().1,
+        Event::Key("after".to_string())
+    );
+    assert_eq!(reader.read_value().unwrap(), Some(Json::Number(4.0)));
+    assert_eq!(reader.read_value().unwrap(), None);
+    assert_eq!(reader.next_event().unwrap().unwrap().1, Event::EndObject);
+    assert_eq!(reader.next_event().unwrap(), None);
+}
+
+#[test]
+fn test_path() {
+    let mut reader = JsonReader::new(&br#"{"a": [10, {"b/c": 20}], "d": 30}"#[..]);
+    let mut paths = vec![];
+    while let Some((_, event)) = reader.next_event().unwrap() {
+        paths.push(format!("{:?} {}", event, reader.path()));
+    }
+    assert_eq!(
+        paths,
+        [
+            "StartObject ",
+            "Key(\"a\") /a",
+            "StartArray /a",
+            "Value(Number(10.0)) /a/0",
+            "StartObject /a/1",
+            "Key(\"b/c\") /a/1/b~1c",
+            "Value(Number(20.0)) /a/1/b~1c",
+            "EndObject /a/1",
+            "EndArray /a",
+            "Key(\"d\") /d",
+            "Value(Number(30.0)) /d",
+            "EndObject ",
+        ]
+    );
+}
+
+#[test]
+fn test_select() {
+    let text = r#"{"skip": [[1, 2], {"deep": 3}], "data": {"rows": [{"id": 1}, {"id": 2}]}}
+                  {"data": {"rows": []}}"#;
+    let mut reader = JsonReader::new(text.as_bytes());
+    assert_eq!(
+        reader.select("/data/rows/1").unwrap(),
+        Some(Json::parse(r#"{"id": 2}"#).unwrap())
+    );
+    // the rest of the first document has no /data/rows/1 so it stops at its end
+    assert_eq!(reader.select("/data/rows/1").unwrap(), None);
+    assert_eq!(
+        reader.select("/data/rows").unwrap(),
+        Some(Json::Array(vec![]))
+    );
+    assert_eq!(reader.select("").unwrap(), None);
+
+    let mut reader = JsonReader::new(&b"[true]"[..]);
+    assert_eq!(
+        reader.select("").unwrap(),
+        Some(Json::parse("[true]").unwrap())
+    );
+}
+
+#[test]
+fn test_errors() {
+    for (text, message, line, column) in [
+        ("[1 2]", "expected ',' or ']', found '2'", 1, 4),
+        ("{\"a\" 1}", "expected ':', found '1'", 1, 6),
+        ("{\"a\": 1,}", "expected a key, found '}'", 1, 9),
+        ("[1,]", "expected a value, found ']'", 1, 4),
+        ("[\n  nul]", "expected a value, found 'nul'", 2, 3),
+        ("[01]", "leading zeros are not allowed", 1, 3),
+        ("[1.]", "expected a digit after the decimal point", 1, 4),
+        ("[\"a\\x\"]", "invalid escape '\\x'", 1, 4),
+        ("{\"a\": [1, ", "unexpected end of input", 1, 11),
+        ("[\"abc", "unterminated string", 1, 6),
+        ("}", "expected a value, found '}'", 1, 1),
+    ] {
+        let error = events(text).unwrap_err();
+        assert_eq!(
+            (error.message.as_str(), error.line, error.column),
+            (message, line, column),
+            "{}",
+            text
+        );
+    }
+    let deep = "[".repeat(MAX_DEPTH + 1);
+    assert_eq!(
+        events(&deep).unwrap_err().message,
+        format!("nested deeper than {} levels", MAX_DEPTH)
+    );
+    // the iterator stops at the first error
+    assert_eq!(JsonReader::new(&b"[1 2] [3]"[..]).count(), 3);
+}
@@ -0,0 +1,495 @@
+// An ordered map on the nodes of a binary tree, each element a (key, value) pair ordered by key
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::mem;
+use std::ops::{Bound, RangeBounds};
+
+use crate::binary_tree::{self, Tree, TreeNode};
+
+/// Like `BTreeMap` but nothing hidden: a plain, unbalanced `Tree<(K, V)>`.
+/// Lookups take anything the key borrows as, a `&str` for `String` keys, as `HashMap` does.
+pub struct TreeMap<K, V> {
+    tree: Tree<(K, V)>,
+    len: usize,
+}
+
+/// As `{key: value, ...}` in key order, like the standard maps.
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for TreeMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// A node at a time, the tree can be as deep as the map is long.
+impl<K, V> Drop for TreeMap<K, V> {
+    fn drop(&mut self) {
+        self.tree.clear();
+    }
+}
+
+impl<K, V> Default for TreeMap<K, V> {
+    fn default() -> Self {
+        TreeMap::new()
+    }
+}
+
+/// The subtree where `key` is, or the empty one where it would go.
+fn find<'a, K, V, Q>(mut tree: &'a mut Tree<(K, V)>, key: &Q) -> &'a mut Tree<(K, V)>
+where
+    K: Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    loop {
+        let direction = match tree {
+            Tree::NonEmpty(node) => key.cmp(node.element.0.borrow()),
+            Tree::Empty => Ordering::Equal,
+        };
+        tree = match (direction, tree) {
+            (Ordering::Less, Tree::NonEmpty(node)) => &mut node.left,
+            (Ordering::Greater, Tree::NonEmpty(node)) => &mut node.right,
+            (_, tree) => return tree,
+        };
+    }
+}
+
+impl<K, V> TreeMap<K, V> {
+    pub fn new() -> Self {
+        TreeMap {
+            tree: Tree::Empty,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The pairs in key order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter(self.tree.iter())
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+}
+
+impl<K: Ord, V> TreeMap<K, V> {
+    /// Returns the value that was there before.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => Some(entry.insert(value)),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                None
+            }
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut tree = &self.tree;
+        while let Tree::NonEmpty(node) = tree {
+            tree = match key.cmp(node.element.0.borrow()) {
+                Ordering::Less => &node.left,
+                Ordering::Greater => &node.right,
+                Ordering::Equal => return Some(&node.element.1),
+            };
+        }
+        None
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match find(&mut self.tree, key) {
+            Tree::NonEmpty(node) => Some(&mut node.element.1),
+            Tree::Empty => None,
+        }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (_, value) = find(&mut self.tree, key).pop_root()?;
+        self.len -= 1;
+        Some(value)
+    }
+
+    /// The place of `key` in the map, to look at, change or fill with a single search.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        let len = &mut self.len;
+        match find(&mut self.tree, &key) {
+            Tree::NonEmpty(node) => Entry::Occupied(OccupiedEntry {
+                element: &mut node.element,
+            }),
+            slot => Entry::Vacant(VacantEntry { key, slot, len }),
+        }
+    }
+
+    /// The pairs whose keys are in `range`, in key order, as in `map.range(2..5)`.
+    /// For `str` use a pair of `Bound`s, `(Bound::Included("b"), Bound::Excluded("d"))`.
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        // the path to the first key in range, each node that is in it left to visit
+        let mut pending = vec![];
+        let mut tree = &self.tree;
+        while let Tree::NonEmpty(node) = tree {
+            let key = node.element.0.borrow();
+            let above_start = match range.start_bound() {
+                Bound::Included(start) => key >= start,
+                Bound::Excluded(start) => key > start,
+                Bound::Unbounded => true,
+            };
+            tree = if above_start {
+                pending.push(&**node);
+                &node.left
+            } else {
+                &node.right
+            };
+        }
+        // the last key in range, the iteration stops after it
+        let mut last = None;
+        let mut tree = &self.tree;
+        while let Tree::NonEmpty(node) = tree {
+            let key = node.element.0.borrow();
+            let below_end = match range.end_bound() {
+                Bound::Included(end) => key <= end,
+                Bound::Excluded(end) => key < end,
+                Bound::Unbounded => true,
+            };
+            tree = if below_end {
+                last = Some(&node.element.0);
+                &node.right
+            } else {
+                &node.left
+            };
+        }
+        Range { pending, last }
+    }
+}
+
+pub enum Entry<'a, K, V> {
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+pub struct OccupiedEntry<'a, K, V> {
+    element: &'a mut (K, V),
+}
+
+pub struct VacantEntry<'a, K, V> {
+    key: K,
+    /// the empty subtree the new node goes in
+    slot: &'a mut Tree<(K, V)>,
+    len: &'a mut usize,
+}
+
+impl<'a, K, V> Entry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => &entry.element.0,
+            Entry::Vacant(entry) => &entry.key,
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with(self, default: impl FnOnce() -> V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn and_modify(mut self, modify: impl FnOnce(&mut V)) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            modify(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V> {
+    pub fn get(&self) -> &V {
+        &self.element.1
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.element.1
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.element.1
+    }
+
+    /// Returns the value it replaces.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(&mut self.element.1, value)
+    }
+}
+
+impl<'a, K, V> VacantEntry<'a, K, V> {
+    pub fn insert(self, value: V) -> &'a mut V {
+        *self.slot = Tree::NonEmpty(Box::new(TreeNode {
+            element: (self.key, value),
+            left: Tree::Empty,
+            right: Tree::Empty,
+        }));
+        *self.len += 1;
+        match self.slot {
+            Tree::NonEmpty(node) => &mut node.element.1,
+            Tree::Empty => unreachable!("the node was just added"),
+        }
+    }
+}
+
+/// The pairs of a `&TreeMap` in key order.
+pub struct Iter<'a, K, V>(binary_tree::Iter<'a, (K, V)>);
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(key, value)| (key, value))
+    }
+}
+
+/// The pairs of a `&TreeMap` between two bounds, in key order.
+pub struct Range<'a, K, V> {
+    /// nodes whose pair and right subtree are still to visit, the next one on top
+    pending: Vec<&'a TreeNode<(K, V)>>,
+    last: Option<&'a K>,
+}
+
+impl<'a, K: Ord, V> Iterator for Range<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.pending.pop()?;
+        let (key, value) = &node.element;
+        if self.last.is_none_or(|last| key > last) {
+            self.pending.clear();
+            return None;
+        }
+        let mut tree = &node.right;
+        while let Tree::NonEmpty(node) = tree {
+            self.pending.push(node);
+            tree = &node.left;
+        }
+        Some((key, value))
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a TreeMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for TreeMap<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for TreeMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = TreeMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+#[test]
+fn test_map_operations() {
+    let mut moons = TreeMap::new();
+    assert!(moons.is_empty());
+    assert_eq!(moons.insert("Mars".to_string(), 2), None);
+    assert_eq!(moons.insert("Earth".to_string(), 0), None);
+    assert_eq!(moons.insert("Jupiter".to_string(), 79), None);
+    assert_eq!(moons.insert("Jupiter".to_string(), 95), Some(79));
+    assert_eq!(moons.len(), 3);
+    // String keys, &str lookups
+    assert_eq!(moons.get("Jupiter"), Some(&95));
+    assert!(!moons.contains_key("Venus"));
+    *moons.get_mut("Earth").unwrap() += 1;
+    assert_eq!(moons.get("Earth"), Some(&1));
+    assert_eq!(moons.get_mut("Venus"), None);
+    assert_eq!(moons.remove("Mars"), Some(2));
+    assert_eq!(moons.remove("Mars"), None);
+    assert_eq!(
+        moons.iter().collect::<Vec<_>>(),
+        [(&"Earth".to_string(), &1), (&"Jupiter".to_string(), &95)]
+    );
+    assert_eq!(moons.keys().count(), 2);
+    assert_eq!(moons.values().sum::<i32>(), 96);
+    assert_eq!(format!("{:?}", moons), r#"{"Earth": 1, "Jupiter": 95}"#);
+}
+
+#[test]
+fn test_entry() {
+    let mut counts: TreeMap<char, usize> = TreeMap::new();
+    for c in "mississippi".chars() {
+        *counts.entry(c).or_insert(0) += 1;
+    }
+    assert_eq!(
+        counts
+            .iter()
+            .map(|(c, n)| format!("{}{}", c, n))
+            .collect::<String>(),
+        "i4m1p2s4"
+    );
+    counts.entry('m').and_modify(|n| *n *= 10).or_default();
+    counts.entry('z').and_modify(|n| *n *= 10).or_default();
+    assert_eq!(counts.get(&'m'), Some(&10));
+    assert_eq!(counts.get(&'z'), Some(&0));
+    assert_eq!(counts.entry('q').key(), &'q');
+    if let Entry::Occupied(mut entry) = counts.entry('p') {
+        assert_eq!(*entry.get(), 2);
+        assert_eq!(entry.insert(7), 2);
+    }
+}
+
+#[test]
+fn test_range() {
+    let map: TreeMap<String, usize> = ["d", "b", "f", "a", "c", "e", "g"]
+        .iter()
+        .map(|s| (s.to_string(), s.len()))
+        .collect();
+    let keys = |range: Range<String, usize>| range.map(|(k, _)| k.as_str()).collect::<String>();
+    assert_eq!(
+        keys(map.range::<str, _>((Bound::Included("b"), Bound::Excluded("e")))),
+        "bcd"
+    );
+    assert_eq!(
+        keys(map.range::<str, _>((Bound::Included("b"), Bound::Included("e")))),
+        "bcde"
+    );
+    assert_eq!(
+        keys(map.range::<str, _>((Bound::Included("bb"), Bound::Unbounded))),
+        "cdefg"
+    );
+    assert_eq!(
+        keys(map.range::<str, _>((Bound::Unbounded, Bound::Excluded("c")))),
+        "ab"
+    );
+    assert_eq!(
+        keys(map.range::<str, _>((Bound::Unbounded, Bound::Unbounded))),
+        "abcdefg"
+    );
+    assert_eq!(
+        keys(map.range::<str, _>((Bound::Excluded("a"), Bound::Excluded("g")))),
+        "bcdef"
+    );
+    assert_eq!(
+        keys(map.range::<str, _>((Bound::Included("x"), Bound::Unbounded))),
+        ""
+    );
+    assert_eq!(
+        keys(map.range::<str, _>((Bound::Included("e"), Bound::Excluded("b")))),
+        ""
+    );
+}
+
+#[test]
+fn test_same_as_btreemap() {
+    use std::collections::BTreeMap;
+
+    let mut state = 0x0123_4567_89ab_cdef_u64;
+    let mut random = |n: u64| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state % n
+    };
+    for _ in 0..30 {
+        let mut map = TreeMap::new();
+        let mut expected = BTreeMap::new();
+        for step in 0..300 {
+            let key = random(80);
+            match random(4) {
+                0 => assert_eq!(map.remove(&key), expected.remove(&key)),
+                1 => assert_eq!(map.get(&key), expected.get(&key)),
+                2 => {
+                    *map.entry(key).or_insert(0) += step;
+                    *expected.entry(key).or_insert(0) += step;
+                }
+                _ => assert_eq!(map.insert(key, step), expected.insert(key, step)),
+            }
+            assert_eq!(map.len(), expected.len());
+        }
+        assert!(map.iter().eq(expected.iter()));
+        for _ in 0..20 {
+            let (a, b) = (random(90), random(90));
+            let (low, high) = (a.min(b), a.max(b));
+            assert!(map.range(low..high).eq(expected.range(low..high)));
+            assert!(map.range(low..=high).eq(expected.range(low..=high)));
+            assert!(map.range(low..).eq(expected.range(low..)));
+            assert!(map.range(..high).eq(expected.range(..high)));
+        }
+    }
+}
+
+#[test]
+fn test_sorted_keys_do_not_overflow_the_stack() {
+    // the tree sorted keys make, each key right of the last, built directly
+    // since inserting them one by one takes quadratic time
+    let tree = (0..50_000).rev().fold(Tree::Empty, |right, key| {
+        Tree::NonEmpty(Box::new(TreeNode {
+            element: (key, key * 2),
+            left: Tree::Empty,
+            right,
+        }))
+    });
+    let mut map = TreeMap { tree, len: 50_000 };
+    assert_eq!(map.get(&49_999), Some(&99_998));
+    *map.get_mut(&49_999).unwrap() = 0;
+    assert_eq!(map.insert(50_000, 1), None);
+    assert_eq!(map.remove(&49_999), Some(0));
+    assert_eq!(map.entry(50_000).or_default(), &1);
+    assert_eq!(map.range(49_990..).count(), 10);
+    assert_eq!(map.len(), 50_000);
+}
@@ -0,0 +1,417 @@
+// Complex numbers over any num::Num, with the fields and operators of num::Complex
+use std::iter::{Product, Sum};
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+use num::{Float, Num, One, Zero};
+
+/// `re + im * i`. Equality is exact; there is no `PartialOrd`, complex numbers have no order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Complex<T> {
+    pub re: T,
+    pub im: T,
+}
+
+impl<T> Complex<T> {
+    pub const fn new(re: T, im: T) -> Self {
+        Complex { re, im }
+    }
+}
+
+impl<T: Num + Copy> Complex<T> {
+    pub fn i() -> Self {
+        Complex::new(T::zero(), T::one())
+    }
+
+    /// `|z|²`, cheaper than the norm and enough to compare sizes.
+    pub fn norm_sqr(&self) -> T {
+        self.re * self.re + self.im * self.im
+    }
+
+    pub fn conj(&self) -> Self {
+        Complex::new(self.re, T::zero() - self.im)
+    }
+
+    pub fn scale(&self, t: T) -> Self {
+        Complex::new(self.re * t, self.im * t)
+    }
+
+    /// `z` to the power `n` by squaring.
+    pub fn powu(&self, mut n: u32) -> Self {
+        let (mut base, mut result) = (*self, Self::one());
+        while n > 0 {
+            if n & 1 == 1 {
+                result *= base;
+            }
+            n >>= 1;
+            // past the last bit the square is not needed, and for integers it may overflow
+            if n > 0 {
+                base *= base;
+            }
+        }
+        result
+    }
+}
+
+impl<T: Float> Complex<T> {
+    pub fn norm(&self) -> T {
+        self.re.hypot(self.im)
+    }
+
+    /// The angle from the positive real axis, in `(-π, π]`.
+    pub fn arg(&self) -> T {
+        self.im.atan2(self.re)
+    }
+}
+
+impl<T: Num + Copy> Add for Complex<T> {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl<T: Num + Copy> Sub for Complex<T> {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+// (a + bi)(c + di) = (ac - bd) + (ad + bc)i
+impl<T: Num + Copy> Mul for Complex<T> {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+// multiply above and below by the conjugate of the divisor, which makes it real
+impl<T: Num + Copy> Div for Complex<T> {
+    type Output = Self;
+    fn div(self, other: Self) -> Self {
+        let norm = other.norm_sqr();
+        let above = self * other.conj();
+        Complex::new(above.re / norm, above.im / norm)
+    }
+}
+
+impl<T: Num + Copy + Neg<Output = T>> Neg for Complex<T> {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Complex::new(-self.re, -self.im)
+    }
+}
+
+impl<T: Num + Copy + Neg<Output = T>> Neg for &Complex<T> {
+    type Output = Complex<T>;
+    fn neg(self) -> Complex<T> {
+        -*self
+    }
+}
+
+// `&a + &b`, `a + &b` and `&a + b` do what `a + b` does, for values in iterators and closures
+macro_rules! forward_ref_binop {
+    ($type:ident $rhs:ty, $($trait:ident $method:ident)*) => {
+        $(
+            impl<T: Num + Copy> $trait<&$rhs> for $type<T> {
+                type Output = $type<T>;
+                fn $method(self, other: &$rhs) -> $type<T> {
+                    self.$method(*other)
+                }
+            }
+
+            impl<T: Num + Copy> $trait<$rhs> for &$type<T> {
+                type Output = $type<T>;
+                fn $method(self, other: $rhs) -> $type<T> {
+                    (*self).$method(other)
+                }
+            }
+
+            impl<T: Num + Copy> $trait<&$rhs> for &$type<T> {
+                type Output = $type<T>;
+                fn $method(self, other: &$rhs) -> $type<T> {
+                    (*self).$method(*other)
+                }
+            }
+        )*
+    };
+}
+
+// `a += b` is `a = a + b`, for a value or a reference on the right
+macro_rules! assign_op {
+    ($type:ident $rhs:ty, $($trait:ident $method:ident $op:ident)*) => {
+        $(
+            impl<T: Num + Copy> $trait<$rhs> for $type<T> {
+                fn $method(&mut self, other: $rhs) {
+                    *self = (*self).$op(other);
+                }
+            }
+
+            impl<T: Num + Copy> $trait<&$rhs> for $type<T> {
+                fn $method(&mut self, other: &$rhs) {
+                    *self = (*self).$op(*other);
+                }
+            }
+        )*
+    };
+}
+
+forward_ref_binop!(Complex Complex<T>, Add add Sub sub Mul mul Div div);
+assign_op!(Complex Complex<T>, AddAssign add_assign add SubAssign sub_assign sub MulAssign mul_assign mul DivAssign div_assign div);
+
+// A real scalar is `t + 0i`: it adds to the real part and scales both parts
+impl<T: Num + Copy> Add<T> for Complex<T> {
+    type Output = Self;
+    fn add(self, t: T) -> Self {
+        Complex::new(self.re + t, self.im)
+    }
+}
+
+impl<T: Num + Copy> Sub<T> for Complex<T> {
+    type Output = Self;
+    fn sub(self, t: T) -> Self {
+        Complex::new(self.re - t, self.im)
+    }
+}
+
+impl<T: Num + Copy> Mul<T> for Complex<T> {
+    type Output = Self;
+    fn mul(self, t: T) -> Self {
+        self.scale(t)
+    }
+}
+
+impl<T: Num + Copy> Div<T> for Complex<T> {
+    type Output = Self;
+    fn div(self, t: T) -> Self {
+        Complex::new(self.re / t, self.im / t)
+    }
+}
+
+forward_ref_binop!(Complex T, Add add Sub sub Mul mul Div div);
+assign_op!(Complex T, AddAssign add_assign add SubAssign sub_assign sub MulAssign mul_assign mul DivAssign div_assign div);
+
+// The scalar on the left: a generic `impl Mul<Complex<T>> for T` is not allowed, so one per type
+macro_rules! scalar_lhs {
+    ($($t:ty)*) => {
+        $(
+            impl Add<Complex<$t>> for $t {
+                type Output = Complex<$t>;
+                fn add(self, z: Complex<$t>) -> Complex<$t> {
+                    z + self
+                }
+            }
+
+            impl Sub<Complex<$t>> for $t {
+                type Output = Complex<$t>;
+                fn sub(self, z: Complex<$t>) -> Complex<$t> {
+                    Complex::new(self, 0 as $t) - z
+                }
+            }
+
+            impl Mul<Complex<$t>> for $t {
+                type Output = Complex<$t>;
+                fn mul(self, z: Complex<$t>) -> Complex<$t> {
+                    z * self
+                }
+            }
+
+            impl Div<Complex<$t>> for $t {
+                type Output = Complex<$t>;
+                fn div(self, z: Complex<$t>) -> Complex<$t> {
+                    Complex::new(self, 0 as $t) / z
+                }
+            }
+        )*
+    };
+}
+
+scalar_lhs!(f32 f64 i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize);
+
+impl<T: Num + Copy> Zero for Complex<T> {
+    fn zero() -> Self {
+        Complex::new(T::zero(), T::zero())
+    }
+
+    fn is_zero(&self) -> bool {
+        self.re.is_zero() && self.im.is_zero()
+    }
+}
+
+impl<T: Num + Copy> One for Complex<T> {
+    fn one() -> Self {
+        Complex::new(T::one(), T::zero())
+    }
+}
+
+impl<T: Num + Copy> Sum for Complex<T> {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::zero(), Add::add)
+    }
+}
+
+impl<'a, T: Num + Copy + 'a> Sum<&'a Complex<T>> for Complex<T> {
+    fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        iter.fold(Self::zero(), Add::add)
+    }
+}
+
+impl<T: Num + Copy> Product for Complex<T> {
+    fn product<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::one(), Mul::mul)
+    }
+}
+
+impl<'a, T: Num + Copy + 'a> Product<&'a Complex<T>> for Complex<T> {
+    fn product<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        iter.fold(Self::one(), Mul::mul)
+    }
+}
+
+#[test]
+#[allow(clippy::op_ref)] // the reference forms are under test
+fn test_arithmetic() {
+    let a = Complex::new(3, 4);
+    let b = Complex::new(1, -2);
+    assert_eq!(a + b, Complex::new(4, 2));
+    assert_eq!(a - b, Complex::new(2, 6));
+    assert_eq!(a * b, Complex::new(11, -2));
+    assert_eq!((a * b) / b, a);
+    assert_eq!(-a, Complex::new(-3, -4));
+    assert_eq!(-&a, -a);
+    assert_eq!(&a + &b, a + b);
+    assert_eq!(&a * b, a * &b);
+    assert_eq!(Complex::<i32>::i() * Complex::i(), Complex::new(-1, 0));
+    assert_eq!(a.conj(), Complex::new(3, -4));
+    assert_eq!(a * a.conj(), Complex::new(a.norm_sqr(), 0));
+    assert_eq!(a.powu(3), a * a * a);
+    assert_eq!(a.powu(0), Complex::one());
+    // as large as i32 goes: no squaring beyond what the result needs
+    assert_eq!(Complex::new(256i32, 0).powu(2), Complex::new(65536, 0));
+    assert_eq!(
+        Complex::new(46340i32, 0).powu(2),
+        Complex::new(2147395600, 0)
+    );
+    assert_eq!(Complex::new(2i32, 0).powu(30), Complex::new(1 << 30, 0));
+    assert_eq!(Complex::new(0i32, 2).powu(29), Complex::new(0, 1 << 29));
+
+    let mut z = a;
+    z += b;
+    z -= &a;
+    assert_eq!(z, b);
+    z *= a;
+    z /= a;
+    assert_eq!(z, b);
+    z *= 2;
+    z += 1;
+    z -= &3;
+    z /= 2;
+    assert_eq!(z, Complex::new(0, -2));
+}
+
+#[test]
+fn test_scalars() {
+    let z = Complex::new(1.5, -2.0);
+    assert_eq!(z * 2.0, Complex::new(3.0, -4.0));
+    assert_eq!(2.0 * z, z * 2.0);
+    assert_eq!(z + 1.0, Complex::new(2.5, -2.0));
+    assert_eq!(1.0 + z, z + 1.0);
+    assert_eq!(1.0 - z, Complex::new(-0.5, 2.0));
+    assert_eq!(z - 1.0, -(1.0f64 - z));
+    assert_eq!(z / 2.0, Complex::new(0.75, -1.0));
+    assert_eq!(1.0 / Complex::new(0.0, 1.0), Complex::new(0.0, -1.0));
+    assert_eq!(3u8 * Complex::new(1u8, 2), Complex::new(3, 6));
+    assert_eq!(Complex::new(3.0, 4.0).norm(), 5.0);
+    assert_eq!(Complex::new(0.0, 2.0).arg(), std::f64::consts::FRAC_PI_2);
+}
+
+#[test]
+fn test_sum_and_product() {
+    let roots: Vec<Complex<f64>> = (0..4).map(|k| Complex::<f64>::i().powu(k)).collect();
+    assert_eq!(roots.iter().sum::<Complex<f64>>(), Complex::zero());
+    assert_eq!(
+        roots.iter().product::<Complex<f64>>(),
+        Complex::new(-1.0, 0.0)
+    );
+    assert_eq!(
+        roots.into_iter().map(|z| z * 2.0).sum::<Complex<f64>>(),
+        Complex::zero()
+    );
+    let empty: [Complex<i64>; 0] = [];
+    assert_eq!(empty.into_iter().product::<Complex<i64>>(), Complex::one());
+    assert!(Complex::<u8>::zero().is_zero());
+}
+
+// The escape time functions of the mandelbrot program in chapter 2, unchanged but for the type
+#[cfg(test)]
+fn escape_time(c: Complex<f64>, limit: u32) -> Option<u32> {
+    let mut z = Complex::<f64> { re: 0.0, im: 0.0 };
+    for i in 0..limit {
+        z = z * z + c;
+        if z.norm_sqr() > 4.0 {
+            return Some(i);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+fn escape_time_iter(c: Complex<f64>, limit: u32) -> Option<u32> {
+    let zero = Complex::<f64> { re: 0.0, im: 0.0 };
+    std::iter::successors(Some(zero), |z| Some(z * z + c))
+        .take(limit as usize)
+        .enumerate()
+        .find(|(_, z)| z.norm_sqr() > 4.0)
+        .map(|(i, _z)| (i - 1) as u32)
+}
+
+#[test]
+fn test_escape_time() {
+    {
+        let c = Complex::<f64> { re: 2., im: 0.0 };
+        assert_eq!(escape_time(c, 10), Some(1));
+        assert_eq!(escape_time_iter(c, 10), Some(1));
+    }
+    {
+        let c = Complex::<f64> {
+            re: 0.00011,
+            im: 0.0,
+        };
+        assert_eq!(escape_time(c, 10), None);
+        assert_eq!(escape_time_iter(c, 10), None);
+    }
+}
+
+#[test]
+fn test_same_as_num_complex() {
+    let escape_time_num = |c: num::Complex<f64>, limit: u32| {
+        let mut z = num::Complex::<f64> { re: 0.0, im: 0.0 };
+        for i in 0..limit {
+            z = z * z + c;
+            if z.norm_sqr() > 4.0 {
+                return Some(i);
+            }
+        }
+        None
+    };
+    for row in 0..60 {
+        for column in 0..90 {
+            let (re, im) = (-2.2 + column as f64 / 30.0, -1.0 + row as f64 / 30.0);
+            assert_eq!(
+                escape_time(Complex { re, im }, 255),
+                escape_time_num(num::Complex { re, im }, 255),
+                "{} {}",
+                re,
+                im
+            );
+        }
+    }
+    let (a, b) = (Complex::new(0.3, -1.7), Complex::new(-2.5, 0.25));
+    let (x, y) = (num::Complex::new(0.3, -1.7), num::Complex::new(-2.5, 0.25));
+    for (ours, theirs) in [(a / b, x / y), (a.powu(5), x.powu(5)), (2.0 - a, 2.0 - x)] {
+        assert!((ours.re - theirs.re).abs() < 1e-12 && (ours.im - theirs.im).abs() < 1e-12);
+    }
+}
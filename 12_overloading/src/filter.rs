@@ -0,0 +1,422 @@
+// Image processing on Image<P>: convolution, blurs, edges, median and resizing.
+// Pixels are weighed in f32, a grey f32 sample or a Vec3<f32> colour; from_bytes and to_bytes
+// go between the u8 samples of files and f32. Every filter builds its result in bands of rows
+// on the rayon threads
+use std::cmp::Ordering;
+use std::ops::{Add, Mul};
+
+use rayon::prelude::*;
+
+use crate::raster::Image;
+
+/// What a filter sees past the edge of the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Border {
+    /// the nearest pixel on the edge
+    Clamp,
+    /// the other side of the image, as if it was tiled
+    Wrap,
+    /// zero, the default of the pixel type
+    Zero,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sampling {
+    Nearest,
+    Bilinear,
+}
+
+/// A pixel that filters can weigh and add up, like `f32` or `Vec3<f32>`.
+pub trait Sample:
+    Copy + Default + Send + Sync + Add<Output = Self> + Mul<f32, Output = Self>
+{
+}
+
+impl<P> Sample for P where P: Copy + Default + Send + Sync + Add<Output = P> + Mul<f32, Output = P> {}
+
+/// An image made row by row. The rows are split in bands, one per thread, the way the mandelbrot
+/// renderer of chapter 2 splits its pixels, and rayon runs the bands.
+fn by_rows<Q: Default + Clone + Send>(
+    width: usize,
+    height: usize,
+    row: impl Fn(usize, &mut [Q]) + Sync,
+) -> Image<Q> {
+    let mut image = Image::new(width, height);
+    if width == 0 || height == 0 {
+        return image;
+    }
+    let rows_per_band = height / rayon::current_num_threads() + 1;
+    image
+        .as_mut_slice()
+        .par_chunks_mut(rows_per_band * width)
+        .enumerate()
+        .for_each(|(band, pixels)| {
+            for (i, pixels) in pixels.chunks_mut(width).enumerate() {
+                row(band * rows_per_band + i, pixels);
+            }
+        });
+    image
+}
+
+/// Pixel `(x, y)`, which may be outside of the image.
+fn pixel<P: Copy + Default>(image: &Image<P>, x: isize, y: isize, border: Border) -> P {
+    let (width, height) = (image.width() as isize, image.height() as isize);
+    let (x, y) = match border {
+        Border::Clamp => (x.clamp(0, width - 1), y.clamp(0, height - 1)),
+        Border::Wrap => (x.rem_euclid(width), y.rem_euclid(height)),
+        Border::Zero if x < 0 || y < 0 || x >= width || y >= height => return P::default(),
+        Border::Zero => (x, y),
+    };
+    image[(x as usize, y as usize)]
+}
+
+/// Convolve with `kernel`, centred on pixel `(width / 2, height / 2)` of the kernel. This is a
+/// true convolution: the kernel is flipped, so a kernel `[1, 0, 0]` moves the image left.
+pub fn convolve<P: Sample>(image: &Image<P>, kernel: &Image<f32>, border: Border) -> Image<P> {
+    let (cx, cy) = (
+        (kernel.width() / 2) as isize,
+        (kernel.height() / 2) as isize,
+    );
+    by_rows(image.width(), image.height(), |y, row| {
+        for (x, out) in row.iter_mut().enumerate() {
+            *out = kernel
+                .enumerate_pixels()
+                .fold(P::default(), |total, (kx, ky, &weight)| {
+                    let sx = x as isize + cx - kx as isize;
+                    let sy = y as isize + cy - ky as isize;
+                    total + pixel(image, sx, sy, border) * weight
+                });
+        }
+    })
+}
+
+/// Convolve with a row kernel, then with the same kernel as a column: the same as convolving
+/// with their product, for 2n instead of n² multiplications a pixel.
+pub fn separable<P: Sample>(image: &Image<P>, weights: &[f32], border: Border) -> Image<P> {
+    let row = Image::from_vec(weights.len(), 1, weights.to_vec()).unwrap();
+    let column = Image::from_vec(1, weights.len(), weights.to_vec()).unwrap();
+    convolve(&convolve(image, &row, border), &column, border)
+}
+
+/// Weights of a Gaussian out to three standard deviations, adding up to 1.
+pub fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    let radius = (3.0 * sigma).ceil().max(0.0) as isize;
+    let weights: Vec<f32> = (-radius..=radius)
+        .map(|i| (-((i * i) as f32) / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total: f32 = weights.iter().sum();
+    weights.into_iter().map(|w| w / total).collect()
+}
+
+pub fn gaussian_blur<P: Sample>(image: &Image<P>, sigma: f32, border: Border) -> Image<P> {
+    if sigma <= 0.0 {
+        return image.clone();
+    }
+    separable(image, &gaussian_kernel(sigma), border)
+}
+
+/// The mean of the `2 * radius + 1` pixels square around each pixel.
+pub fn box_blur<P: Sample>(image: &Image<P>, radius: usize, border: Border) -> Image<P> {
+    let size = 2 * radius + 1;
+    separable(image, &vec![1.0 / size as f32; size], border)
+}
+
+/// The gradient magnitude: large across edges, zero where the image is flat. A magnitude is a
+/// single number, so edges are found on grey samples.
+pub fn sobel(image: &Image<f32>, border: Border) -> Image<f32> {
+    let gx = Image::from_vec(3, 3, vec![1.0, 0.0, -1.0, 2.0, 0.0, -2.0, 1.0, 0.0, -1.0]).unwrap();
+    let gy = Image::from_vec(3, 3, vec![1.0, 2.0, 1.0, 0.0, 0.0, 0.0, -1.0, -2.0, -1.0]).unwrap();
+    convolve(image, &gx, border).zip_map(&convolve(image, &gy, border), |x, y| x.hypot(*y))
+}
+
+/// The median of the `2 * radius + 1` pixels square around each pixel: removes specks that a
+/// blur would only spread. Works on any pixel that can be ordered, `u8` for one; `f32` is not
+/// `Ord` because of NaN, see `median_f32`.
+pub fn median<P>(image: &Image<P>, radius: usize, border: Border) -> Image<P>
+where
+    P: Copy + Default + Ord + Send + Sync,
+{
+    median_by(image, radius, border, P::cmp)
+}
+
+/// `median` for `f32`, ordered by `f32::total_cmp`: NaNs count as the largest values, or the
+/// smallest for a negative NaN, so a NaN only comes out where it is the median.
+pub fn median_f32(image: &Image<f32>, radius: usize, border: Border) -> Image<f32> {
+    median_by(image, radius, border, f32::total_cmp)
+}
+
+// select_nth_unstable_by may panic if `cmp` is not a total order
+fn median_by<P>(
+    image: &Image<P>,
+    radius: usize,
+    border: Border,
+    cmp: impl Fn(&P, &P) -> Ordering + Sync,
+) -> Image<P>
+where
+    P: Copy + Default + Send + Sync,
+{
+    let r = radius as isize;
+    by_rows(image.width(), image.height(), |y, row| {
+        let mut window = Vec::with_capacity((2 * radius + 1).pow(2));
+        for (x, out) in row.iter_mut().enumerate() {
+            window.clear();
+            for dy in -r..=r {
+                for dx in -r..=r {
+                    window.push(pixel(image, x as isize + dx, y as isize + dy, border));
+                }
+            }
+            let middle = window.len() / 2;
+            window.select_nth_unstable_by(middle, &cmp);
+            *out = window[middle];
+        }
+    })
+}
+
+/// Where the centre of pixel `i` of `to` falls in `from` pixels, in pixel coordinates.
+fn source(i: usize, from: usize, to: usize) -> f32 {
+    (i as f32 + 0.5) * from as f32 / to as f32 - 0.5
+}
+
+/// Panics if the image is empty but the new size is not.
+pub fn resize<P: Sample>(
+    image: &Image<P>,
+    width: usize,
+    height: usize,
+    sampling: Sampling,
+) -> Image<P> {
+    assert!(
+        width * height == 0 || image.width() * image.height() > 0,
+        "cannot resize an empty image to {}x{}",
+        width,
+        height
+    );
+    let (last_x, last_y) = (
+        image.width().saturating_sub(1),
+        image.height().saturating_sub(1),
+    );
+    by_rows(width, height, |y, row| {
+        let sy = source(y, image.height(), height).clamp(0.0, last_y as f32);
+        for (x, out) in row.iter_mut().enumerate() {
+            let sx = source(x, image.width(), width).clamp(0.0, last_x as f32);
+            *out = match sampling {
+                Sampling::Nearest => image[(sx.round() as usize, sy.round() as usize)],
+                Sampling::Bilinear => {
+                    let (x0, y0) = (sx.floor() as usize, sy.floor() as usize);
+                    let (x1, y1) = ((x0 + 1).min(last_x), (y0 + 1).min(last_y));
+                    let (fx, fy) = (sx - x0 as f32, sy - y0 as f32);
+                    let top = image[(x0, y0)] * (1.0 - fx) + image[(x1, y0)] * fx;
+                    let bottom = image[(x0, y1)] * (1.0 - fx) + image[(x1, y1)] * fx;
+                    top * (1.0 - fy) + bottom * fy
+                }
+            };
+        }
+    })
+}
+
+/// Samples from a file, to filter: every byte is exactly an `f32`.
+pub fn from_bytes(image: &Image<u8>) -> Image<f32> {
+    image.map(|&p| p as f32)
+}
+
+/// Samples as they are stored in files, rounded and clamped to 0..=255.
+pub fn to_bytes(image: &Image<f32>) -> Image<u8> {
+    image.map(|&p| p.round().clamp(0.0, 255.0) as u8)
+}
+
+#[cfg(test)]
+fn noise(width: usize, height: usize) -> Image<f32> {
+    let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+    Image::from_fn(width, height, |_, _| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % 256) as f32
+    })
+}
+
+#[cfg(test)]
+fn assert_close(a: &Image<f32>, b: &Image<f32>) {
+    assert_eq!((a.width(), a.height()), (b.width(), b.height()));
+    for ((x, y, p), q) in a.enumerate_pixels().zip(b.pixels()) {
+        assert!((p - q).abs() < 1e-3, "({}, {}): {} and {}", x, y, p, q);
+    }
+}
+
+#[test]
+fn test_borders() {
+    let image = Image::from_vec(4, 1, vec![1.0, 2.0, 3.0, 4.0]).unwrap();
+    let left = Image::from_vec(3, 1, vec![1.0, 0.0, 0.0]).unwrap();
+    let right = Image::from_vec(3, 1, vec![0.0, 0.0, 1.0]).unwrap();
+    for (border, last, first) in [
+        (Border::Clamp, 4.0, 1.0),
+        (Border::Wrap, 1.0, 4.0),
+        (Border::Zero, 0.0, 0.0),
+    ] {
+        let moved = convolve(&image, &left, border);
+        assert_eq!(moved.as_slice(), [2.0, 3.0, 4.0, last]);
+        let moved = convolve(&image, &right, border);
+        assert_eq!(moved.as_slice(), [first, 1.0, 2.0, 3.0]);
+    }
+    let identity = Image::from_vec(1, 1, vec![1.0]).unwrap();
+    let image = noise(9, 7);
+    assert_eq!(convolve(&image, &identity, Border::Zero), image);
+    assert_eq!(
+        convolve(&Image::<f32>::new(0, 3), &identity, Border::Wrap),
+        Image::new(0, 3)
+    );
+}
+
+#[test]
+fn test_separable_blurs() {
+    let kernel = gaussian_kernel(1.5);
+    assert_eq!(kernel.len(), 11);
+    assert!((kernel.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+    assert_eq!(kernel[0], kernel[10]);
+    assert!(kernel[5] > kernel[4]);
+
+    // two passes give the same as one pass with the outer product
+    let image = noise(17, 13);
+    let square = Image::from_fn(11, 11, |x, y| kernel[x] * kernel[y]);
+    for border in [Border::Clamp, Border::Wrap, Border::Zero] {
+        assert_close(
+            &gaussian_blur(&image, 1.5, border),
+            &convolve(&image, &square, border),
+        );
+    }
+    let mean = Image::from_fn(5, 5, |_, _| 1.0 / 25.0);
+    assert_close(
+        &box_blur(&image, 2, Border::Wrap),
+        &convolve(&image, &mean, Border::Wrap),
+    );
+
+    // a flat image stays flat unless zeros come in from the border
+    let flat = Image::from_fn(8, 8, |_, _| 100.0);
+    assert_close(&gaussian_blur(&flat, 2.0, Border::Clamp), &flat);
+    assert_close(&box_blur(&flat, 3, Border::Wrap), &flat);
+    assert!(box_blur(&flat, 1, Border::Zero)[(0, 0)] < 50.0);
+    assert_eq!(gaussian_blur(&image, 0.0, Border::Zero), image);
+}
+
+#[test]
+fn test_colour_pixels() {
+    use crate::vector::Vec3;
+
+    // each channel of a colour is filtered as its own grey image
+    let (red, green, blue) = (
+        noise(9, 7),
+        noise(9, 7).map(|p| 255.0 - p),
+        Image::new(9, 7),
+    );
+    let colour = Image::from_fn(9, 7, |x, y| {
+        Vec3::new(red[(x, y)], green[(x, y)], blue[(x, y)])
+    });
+    let channels =
+        |image: &Image<Vec3<f32>>| (image.map(|p| p.x), image.map(|p| p.y), image.map(|p| p.z));
+    let blurred = gaussian_blur(&colour, 1.0, Border::Wrap);
+    let (r, g, b) = channels(&blurred);
+    assert_close(&r, &gaussian_blur(&red, 1.0, Border::Wrap));
+    assert_close(&g, &gaussian_blur(&green, 1.0, Border::Wrap));
+    assert_eq!(b, blue);
+    let resized = resize(&colour, 4, 11, Sampling::Bilinear);
+    let (r, _, _) = channels(&resized);
+    assert_close(&r, &resize(&red, 4, 11, Sampling::Bilinear));
+    assert_eq!(box_blur(&colour, 2, Border::Zero).width(), 9);
+}
+
+#[test]
+fn test_sobel() {
+    // dark on the left, bright from column 4
+    let image = Image::from_fn(8, 5, |x, _| if x < 4 { 0.0 } else { 10.0 });
+    let edges = sobel(&image, Border::Clamp);
+    for y in 0..5 {
+        assert_eq!(edges[y][..3], [0.0; 3]);
+        assert_eq!(edges[(3, y)], 40.0);
+        assert_eq!(edges[(4, y)], 40.0);
+        assert_eq!(edges[y][5..], [0.0; 3]);
+    }
+    // the same edge turned round is found the same
+    let turned = Image::from_fn(5, 8, |x, y| image[(y, x)]);
+    let turned_edges = sobel(&turned, Border::Clamp);
+    assert_eq!(turned_edges[(2, 3)], 40.0);
+    assert_eq!(turned_edges[(2, 0)], 0.0);
+}
+
+#[test]
+fn test_median() {
+    let mut specks = Image::from_fn(6, 6, |_, _| 10u8);
+    specks[(2, 3)] = 255;
+    specks[(0, 0)] = 0;
+    let cleaned = median(&specks, 1, Border::Clamp);
+    assert!(cleaned.pixels().all(|&p| p == 10));
+    // with zeros past the edge the corners go dark
+    assert_eq!(median(&specks, 1, Border::Zero)[(5, 5)], 0);
+
+    let image = Image::from_vec(3, 1, vec![3.0, -1.0, 2.0]).unwrap();
+    assert_eq!(
+        median_f32(&image, 1, Border::Wrap).as_slice(),
+        [2.0, 2.0, 2.0]
+    );
+    assert_eq!(median_f32(&image, 0, Border::Wrap), image);
+
+    // NaNs sort past the numbers, so a lone NaN is taken out like any speck
+    let image = Image::from_vec(5, 1, vec![1.0, f32::NAN, 2.0, -f32::NAN, 3.0]).unwrap();
+    let cleaned = median_f32(&image, 1, Border::Clamp);
+    assert_eq!(cleaned.as_slice()[1..4], [2.0; 3]);
+    assert!(cleaned[(0, 0)] == 1.0 && cleaned[(4, 0)] == 3.0);
+    // but where most of the window is NaN, so is the median
+    let nans = Image::from_fn(4, 4, |x, _| if x < 3 { f32::NAN } else { 0.0 });
+    assert!(median_f32(&nans, 2, Border::Wrap)
+        .pixels()
+        .all(|p| p.is_nan()));
+}
+
+#[test]
+fn test_resize() {
+    let image = Image::from_vec(2, 2, vec![0.0, 10.0, 20.0, 30.0]).unwrap();
+    let nearest = resize(&image, 4, 4, Sampling::Nearest);
+    assert_eq!(nearest[0], [0.0, 0.0, 10.0, 10.0]);
+    assert_eq!(nearest[3], [20.0, 20.0, 30.0, 30.0]);
+    let bilinear = resize(&image, 4, 1, Sampling::Bilinear);
+    assert_eq!(bilinear.as_slice(), [10.0, 12.5, 17.5, 20.0]);
+    assert_eq!(resize(&image, 1, 1, Sampling::Bilinear).as_slice(), [15.0]);
+
+    let image = noise(7, 5);
+    for sampling in [Sampling::Nearest, Sampling::Bilinear] {
+        assert_eq!(resize(&image, 7, 5, sampling), image);
+        let flat = Image::from_fn(3, 3, |_, _| 7.0);
+        assert_close(
+            &resize(&flat, 11, 2, sampling),
+            &Image::from_fn(11, 2, |_, _| 7.0),
+        );
+    }
+    assert_eq!(
+        resize(&Image::<f32>::new(0, 0), 0, 5, Sampling::Nearest),
+        Image::new(0, 5)
+    );
+}
+
+#[test]
+fn test_threads_agree() {
+    // one band on one thread gives the same pixels as many bands
+    let image = noise(61, 47);
+    let run = || {
+        (
+            gaussian_blur(&image, 2.0, Border::Wrap),
+            sobel(&image, Border::Zero),
+            median_f32(&image, 2, Border::Clamp),
+            resize(&image, 100, 33, Sampling::Bilinear),
+        )
+    };
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap();
+    assert_eq!(pool.install(run), run());
+    assert_eq!(from_bytes(&to_bytes(&image)), image);
+    let bytes = Image::from_fn(16, 16, |x, y| (x * 16 + y) as u8);
+    assert_eq!(to_bytes(&from_bytes(&bytes)), bytes);
+    assert_eq!(
+        to_bytes(&Image::from_vec(3, 1, vec![-3.0, 127.5, 300.0]).unwrap()).as_slice(),
+        [0, 128, 255]
+    );
+}
@@ -1,37 +1,18 @@
-#[derive(Debug, Clone, Default)]
-struct Image<P> {
-    pixels: Vec<P>,
-    width: usize,
-    height: usize,
-}
-
-impl<P: Default + Copy> Image<P> {
-    fn new(width: usize, height: usize) -> Self {
-        Image {
-            pixels: vec![P::default(); width * height],
-            width,
-            height,
-        }
-    }
-}
-// Returns a slice of the pixels in the row
-// so we can access the individual pixels in the row
-impl<P> std::ops::Index<usize> for Image<P> {
-    type Output = [P];
-    fn index(&self, row: usize) -> &[P] {
-        let start = row * self.width;
-        &self.pixels[start..start + self.width]
-    }
-}
+#[macro_use]
+mod complex;
+mod filter;
+mod netpbm;
+mod raster;
+mod vector;
 
-// Returns a slice of the pixels in the row
-// so we can access the individual pixels in the row
-impl<P> std::ops::IndexMut<usize> for Image<P> {
-    fn index_mut(&mut self, row: usize) -> &mut [P] {
-        let start = row * self.width;
-        &mut self.pixels[start..start + self.width]
-    }
-}
+use complex::Complex;
+use filter::{Border, Sampling};
+use image::{GrayImage, RgbImage};
+use netpbm::Encoding;
+use num::{One, Zero};
+use raster::Image;
+use std::time::Instant;
+use vector::{Vec2, Vec3};
 
 fn main() {
     let mut image = Image::new(10, 10);
@@ -48,7 +29,351 @@ fn main() {
     let row: usize = 0;
     let col: usize = 10;
     println!("row: {}, val: {:?}", row, &image[row]);
-    println!("row: {}, col: {}, val: {:?}", row, col, &image[row][col]);
+    // image[row][col] would panic, col is past the end of the row
+    println!("row: {}, col: {}, val: {:?}", row, col, image.get(col, row));
     let row: usize = 2;
-    println!("row: {}, col: {}, val: {:?}", row, col, &image[row][col]);
+    println!("row: {}, col: {}, val: {:?}", row, col, image.get(col, row));
+    image[(9, 2)] = 42;
+    println!("x: 9, y: 2, val: {:?}", image[(9, 2)]);
+    if let Some(p) = image.get_mut(8, 2) {
+        *p = 41;
+    }
+
+    println!("Size: {}x{}", image.width(), image.height());
+    let sums: Vec<i32> = image.rows().map(|row| row.iter().sum()).collect();
+    println!("Row sums: {:?}", sums);
+    let last: Vec<i32> = image.column(9).copied().collect();
+    println!("Last column: {:?}", last);
+    let tallest = image
+        .columns()
+        .map(|column| column.max().copied().unwrap_or_default())
+        .collect::<Vec<_>>();
+    println!("Column maxima: {:?}", tallest);
+    let (x, y, p) = image
+        .enumerate_pixels()
+        .max_by_key(|&(_, _, p)| *p)
+        .unwrap();
+    println!("Brightest: {} at ({}, {})", p, x, y);
+
+    // a view borrows a rectangle, its rows are still 10 pixels apart in the image
+    let corner = image.view(7, 0, 3, 3).unwrap();
+    println!("Corner {}x{}:", corner.width(), corner.height());
+    for row in corner.rows() {
+        println!("  {:?}", row);
+    }
+    println!(
+        "Corner (1, 2): {}, row 0: {:?}",
+        corner[(1, 2)],
+        corner.row(0)
+    );
+    println!("Corner (3, 0): {:?}", corner.get(3, 0));
+    println!(
+        "Corner column 2: {:?}",
+        corner.column(2).collect::<Vec<_>>()
+    );
+    println!(
+        "Corner columns: {}, pixels: {:?}",
+        corner.columns().count(),
+        corner.pixels().collect::<Vec<_>>()
+    );
+    let centre = corner.view(1, 1, 2, 2).unwrap();
+    println!("Centre of the corner: {:?}", centre.to_image().as_slice());
+    println!("Doubled: {:?}", centre.map(|p| p * 2).as_slice());
+    println!(
+        "Off the edge: {:?}",
+        image
+            .view(8, 8, 3, 3)
+            .map(|view| view.enumerate_pixels().count())
+    );
+    println!(
+        "Whole image: {} pixels",
+        image.as_view().enumerate_pixels().count()
+    );
+
+    // operators work pixel by pixel, or with a scalar on every pixel
+    let ramp = Image::from_fn(4, 2, |x, y| (x + y * 4) as f32);
+    let ones = ramp.map(|_| 1.0);
+    let scaled = &ramp * 0.5 + 1.0;
+    let difference = &scaled - &ramp;
+    let product = (difference.clone() * ones) * &scaled;
+    println!("Ramp: {:?}", ramp.as_slice());
+    println!("Scaled: {:?}", scaled.as_slice());
+    println!("Difference: {:?}", difference.as_slice());
+    println!("Product: {:?}", product.as_slice());
+    let mixed = difference.zip_map(&ramp, |d, r| if d > &0.0 { *r } else { -r });
+    println!("Mixed: {:?}", mixed.as_slice());
+    let mut bytes = Image::from_vec(2, 2, vec![10u8, 20, 30, 40]).unwrap();
+    for row in bytes.rows_mut() {
+        row.swap(0, 1);
+    }
+    for p in bytes.pixels_mut() {
+        *p /= 10;
+    }
+    bytes.as_mut_slice()[0] = 9;
+    println!(
+        "Bytes: {:?}, {:?}",
+        bytes.pixels().collect::<Vec<_>>(),
+        bytes.clone().into_vec()
+    );
+    let sum = &bytes + &bytes - 1;
+    println!("Sum less one: {:?}", sum);
+
+    // the mandelbrot set rendered into a plain buffer as in chapter 2, with our own Complex in
+    // place of num's, then wrapped as an image
+    let bounds = (48, 24);
+    let mut pixels = vec![0u8; bounds.0 * bounds.1];
+    for row in 0..bounds.1 {
+        for column in 0..bounds.0 {
+            let c = Complex {
+                re: -2.0 + column as f64 * 3.0 / bounds.0 as f64,
+                im: 1.2 - row as f64 * 2.4 / bounds.1 as f64,
+            };
+            let mut z = Complex::<f64> { re: 0.0, im: 0.0 };
+            let mut escaped = None;
+            for i in 0..255 {
+                z = z * z + c;
+                if z.norm_sqr() > 4.0 {
+                    escaped = Some(i);
+                    break;
+                }
+            }
+            pixels[row * bounds.0 + column] = match escaped {
+                None => 0,
+                Some(count) => 255 - count as u8,
+            };
+        }
+    }
+    let mandelbrot = Image::from_vec(bounds.0, bounds.1, pixels).unwrap();
+    for row in mandelbrot.rows().step_by(2) {
+        let line: String = row
+            .iter()
+            .map(|&p| {
+                if p == 0 {
+                    '#'
+                } else if p < 250 {
+                    '+'
+                } else {
+                    ' '
+                }
+            })
+            .collect();
+        println!("{}", line);
+    }
+    let mut pgm = vec![];
+    netpbm::write(&mandelbrot, &mut pgm, Encoding::Ascii).unwrap();
+    let text = String::from_utf8_lossy(&pgm);
+    println!(
+        "PGM: {} bytes, header {:?}",
+        pgm.len(),
+        text.lines().take(3).collect::<Vec<_>>()
+    );
+    println!(
+        "Read back the same: {}",
+        netpbm::read::<u8>(&pgm[..]).unwrap() == mandelbrot
+    );
+    match netpbm::read::<[u8; 3]>(&pgm[..]) {
+        Ok(_) => println!("Read a PGM as colour?"),
+        Err(error) => println!("Reading it as colour: {}", error),
+    }
+
+    let dir = std::env::temp_dir();
+    let colour = mandelbrot.map(|&p| [p, p / 2, 255 - p]);
+    netpbm::save(&colour, dir.join("mandelbrot.ppm"), Encoding::Binary).unwrap();
+    let loaded: Image<[u8; 3]> = netpbm::load(dir.join("mandelbrot.ppm")).unwrap();
+    println!("PPM saved and loaded back the same: {}", loaded == colour);
+    let png = dir.join("mandelbrot.png");
+    RgbImage::from(loaded).save(&png).unwrap();
+    let gray = GrayImage::from(mandelbrot.clone());
+    println!("As an ImageBuffer: {:?}", gray.dimensions());
+    let back = Image::from(gray);
+    let opened = Image::from(image::open(&png).unwrap().to_rgb8());
+    println!(
+        "Back from the buffer: {}, PNG at {}: {}",
+        back == mandelbrot,
+        png.display(),
+        opened == colour
+    );
+
+    // filters work on f32 samples, the bytes go back to u8 for saving
+    let samples = filter::from_bytes(&mandelbrot);
+    let big = filter::resize(&samples, 960, 480, Sampling::Bilinear);
+    let blocky = filter::resize(&samples, 96, 48, Sampling::Nearest);
+    println!(
+        "Resized to {}x{} and {}x{}",
+        big.width(),
+        big.height(),
+        blocky.width(),
+        blocky.height()
+    );
+    println!(
+        "Gaussian weights for sigma 1: {:.3?}",
+        filter::gaussian_kernel(1.0)
+    );
+    let sharpen =
+        Image::from_vec(3, 3, vec![0.0, -1.0, 0.0, -1.0, 5.0, -1.0, 0.0, -1.0, 0.0]).unwrap();
+    let sharp = filter::convolve(&big, &sharpen, Border::Clamp);
+    let smeared = filter::separable(&big, &[0.25, 0.5, 0.25], Border::Wrap);
+    let speckled = Image::from_fn(big.width(), big.height(), |x, y| {
+        if (x * 31 + y * 17).is_multiple_of(97) {
+            -1000.0
+        } else {
+            big[(x, y)]
+        }
+    });
+    let cleaned = filter::median_f32(&speckled, 1, Border::Clamp);
+    let specks = |image: &Image<f32>| image.pixels().filter(|&&p| p < 0.0).count();
+    println!(
+        "Sharpened range {:?}, smeared mean {:.1}, specks {} before the median, {} after",
+        sharp
+            .pixels()
+            .fold((f32::MAX, f32::MIN), |(lo, hi), &p| (lo.min(p), hi.max(p))),
+        smeared.pixels().sum::<f32>() / smeared.as_slice().len() as f32,
+        specks(&speckled),
+        specks(&cleaned)
+    );
+    // bytes are Ord, the median takes them as they are
+    let smoothed = filter::median(&mandelbrot, 1, Border::Clamp);
+    println!(
+        "The median of the bytes changed {} of {} pixels",
+        smoothed
+            .pixels()
+            .zip(mandelbrot.pixels())
+            .filter(|(a, b)| a != b)
+            .count(),
+        mandelbrot.as_slice().len()
+    );
+    let start = Instant::now();
+    let blurred = filter::gaussian_blur(&big, 3.0, Border::Clamp);
+    let edges = filter::sobel(&blurred, Border::Zero);
+    let boxed = filter::box_blur(&big, 4, Border::Wrap);
+    let threaded = start.elapsed();
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .unwrap();
+    let start = Instant::now();
+    let single = pool.install(|| {
+        let blurred = filter::gaussian_blur(&big, 3.0, Border::Clamp);
+        filter::sobel(&blurred, Border::Zero)
+    });
+    let _ = pool.install(|| filter::box_blur(&big, 4, Border::Wrap));
+    println!(
+        "Blur, edges and box on {} threads: {:?}ms, on one thread: {:?}ms, same edges: {}",
+        rayon::current_num_threads(),
+        threaded.as_millis(),
+        start.elapsed().as_millis(),
+        single == edges
+    );
+    for (name, image) in [("blurred", &blurred), ("edges", &edges), ("boxed", &boxed)] {
+        let path = dir.join(format!("mandelbrot-{}.pgm", name));
+        netpbm::save(&filter::to_bytes(image), &path, Encoding::Binary).unwrap();
+        println!("Saved {}", path.display());
+    }
+    // a colour pixel is a Vec3 of samples, each channel blurred as a grey image would be
+    let samples = colour.map(|&[r, g, b]| Vec3::new(r as f32, g as f32, b as f32));
+    let soft = filter::gaussian_blur(&samples, 2.0, Border::Clamp)
+        .map(|p| [p.x, p.y, p.z].map(|c| c.round().clamp(0.0, 255.0) as u8));
+    let path = dir.join("mandelbrot-soft.ppm");
+    netpbm::save(&soft, &path, Encoding::Binary).unwrap();
+    println!("Saved {}", path.display());
+
+    println!("-------------------------");
+    println!("-- Complex and vectors --");
+    println!("-------------------------");
+    let a = Complex::new(3.0, 4.0);
+    let b = Complex::new(1.0, -2.0);
+    println!("a = {:?}, b = {:?}", a, b);
+    println!("a + b = {:?}, a - b = {:?}", a + b, a - b);
+    println!("a * b = {:?}, a / b = {:?}", a * b, a / b);
+    println!("-a = {:?}, -&b = {:?}", -a, -&b);
+    println!(
+        "|a| = {}, |a|² = {}, arg b = {:.3}",
+        a.norm(),
+        a.norm_sqr(),
+        b.arg()
+    );
+    println!("i² = {:?}", Complex::<f64>::i() * Complex::i());
+    println!("conj a = {:?}, a³ = {:?}", a.conj(), a.powu(3));
+    println!(
+        "2a = {:?}, a·2 = {:?}, a/2 = {:?}",
+        2.0 * a,
+        a.scale(2.0),
+        a / 2.0
+    );
+    println!(
+        "1 + a = {:?}, a - 1 = {:?}, 1 - a = {:?}, 1 / a = {:?}",
+        1.0 + a,
+        a - 1.0,
+        1.0 - a,
+        1.0 / a
+    );
+    let mut z = Complex::<f64>::zero();
+    z += a;
+    z -= &b;
+    z *= b;
+    z /= &a;
+    z += 1.0;
+    z *= 2.0;
+    z -= 0.5;
+    z /= 3.0;
+    println!(
+        "Compound: {:?}, zero: {}",
+        z,
+        Complex::<i32>::zero().is_zero()
+    );
+    let roots: Vec<Complex<f64>> = (0..4).map(|k| Complex::i().powu(k)).collect();
+    println!(
+        "Powers of i: {:?}, sum {:?}, product {:?}, one {:?}",
+        roots,
+        roots.iter().sum::<Complex<f64>>(),
+        roots.iter().product::<Complex<f64>>(),
+        Complex::<f64>::one()
+    );
+    println!(
+        "Sum of owned: {:?}, product of owned: {:?}",
+        roots.iter().map(|z| z * 2.0).sum::<Complex<f64>>(),
+        roots.into_iter().map(|z| z + 1.0).product::<Complex<f64>>()
+    );
+
+    let u = Vec3::new(1.0, 2.0, 2.0);
+    let v = Vec3::new(0.0, -1.0, 4.0);
+    println!("u + v = {:?}, u - v = {:?}, -u = {:?}", u + v, u - v, -&u);
+    println!("3u = {:?}, u/2 = {:?}", 3.0 * u, u / 2.0);
+    println!("u · v = {}, u × v = {:?}", u.dot(&v), u.cross(&v));
+    println!(
+        "|u| = {}, |u|² = {}, u/|u| = {:?}",
+        u.length(),
+        u.length_sqr(),
+        u.normalized()
+    );
+    let mut w = u;
+    w += v;
+    w -= &u;
+    w *= 2.0;
+    w /= 4.0;
+    println!("Compound: {:?}, zero: {}", w, Vec3::<f64>::zero().is_zero());
+    let corners = [
+        Vec2::new(0, 0),
+        Vec2::new(4, 0),
+        Vec2::new(4, 3),
+        Vec2::new(0, 3),
+    ];
+    let centre = corners.iter().sum::<Vec2<i32>>() / corners.len() as i32;
+    let doubled: Vec2<i32> = corners.into_iter().map(|c| 2 * c).sum();
+    println!(
+        "Centre of the rectangle: {:?}, doubled sum: {:?}",
+        centre, doubled
+    );
+    let turn = (corners[1] - corners[0]).cross(&(corners[2] - corners[1]));
+    println!(
+        "Turning {} at the second corner",
+        if turn > 0 { "left" } else { "right" }
+    );
+    for (p, q) in [
+        (corners[0], corners[2]),
+        (corners[1], corners[3]),
+        (centre, centre),
+    ] {
+        println!("{:?} against {:?}: {:?}", p, q, p.partial_cmp(&q));
+    }
 }
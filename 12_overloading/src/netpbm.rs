@@ -0,0 +1,418 @@
+// Netpbm images: PGM (P2 text, P5 binary) for grey and PPM (P3 text, P6 binary) for colour
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use image::{GrayImage, Rgb, RgbImage};
+use thiserror::Error;
+
+use crate::raster::Image;
+
+#[derive(Debug, Error)]
+pub enum NetpbmError {
+    #[error("not a {expected} file, found magic number {found:?}")]
+    Magic {
+        expected: &'static str,
+        found: String,
+    },
+    #[error("bad header, expected {0}")]
+    Header(&'static str),
+    #[error("maximum value {0} not supported, only 1 to 255")]
+    MaxValue(u32),
+    #[error("bad sample {0:?}")]
+    Sample(String),
+    #[error("pixel value {value} above the maximum of {max}")]
+    Value { value: u32, max: u32 },
+    #[error("{width}x{height} image is too large")]
+    TooLarge { width: usize, height: usize },
+    #[error("{width}x{height} image needs {expected} samples, found {found}")]
+    Truncated {
+        width: usize,
+        height: usize,
+        expected: usize,
+        found: usize,
+    },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// How samples are written: as decimal text (P2, P3) or one byte each (P5, P6).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Ascii,
+    Binary,
+}
+
+/// A pixel that Netpbm can store, with a magic number for each encoding.
+pub trait NetpbmPixel: Copy {
+    const KIND: &'static str;
+    const ASCII: &'static str;
+    const BINARY: &'static str;
+    const CHANNELS: usize;
+
+    fn from_samples(samples: &[u8]) -> Self;
+    fn samples(&self) -> &[u8];
+}
+
+impl NetpbmPixel for u8 {
+    const KIND: &'static str = "PGM";
+    const ASCII: &'static str = "P2";
+    const BINARY: &'static str = "P5";
+    const CHANNELS: usize = 1;
+
+    fn from_samples(samples: &[u8]) -> u8 {
+        samples[0]
+    }
+
+    fn samples(&self) -> &[u8] {
+        std::slice::from_ref(self)
+    }
+}
+
+impl NetpbmPixel for [u8; 3] {
+    const KIND: &'static str = "PPM";
+    const ASCII: &'static str = "P3";
+    const BINARY: &'static str = "P6";
+    const CHANNELS: usize = 3;
+
+    fn from_samples(samples: &[u8]) -> [u8; 3] {
+        [samples[0], samples[1], samples[2]]
+    }
+
+    fn samples(&self) -> &[u8] {
+        self
+    }
+}
+
+/// The header fields, separated by whitespace and `#` comments running to the end of the line.
+struct Header<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Header<'a> {
+    fn skip_space(&mut self) {
+        while let Some(&b) = self.data.get(self.pos) {
+            if b == b'#' {
+                while self.data.get(self.pos).is_some_and(|&b| b != b'\n') {
+                    self.pos += 1;
+                }
+            } else if b.is_ascii_whitespace() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn token(&mut self) -> &'a [u8] {
+        self.skip_space();
+        let start = self.pos;
+        while self
+            .data
+            .get(self.pos)
+            .is_some_and(|b| !b.is_ascii_whitespace() && *b != b'#')
+        {
+            self.pos += 1;
+        }
+        &self.data[start..self.pos]
+    }
+
+    fn number(&mut self, what: &'static str) -> Result<u32, NetpbmError> {
+        let token = self.token();
+        std::str::from_utf8(token)
+            .ok()
+            .filter(|text| text.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|text| text.parse().ok())
+            .ok_or(NetpbmError::Header(what))
+    }
+}
+
+/// Read a whole Netpbm image of either encoding. Samples are scaled to 0..=255 when the file
+/// has another maximum value.
+pub fn read<P: NetpbmPixel>(mut input: impl Read) -> Result<Image<P>, NetpbmError> {
+    let mut data = vec![];
+    input.read_to_end(&mut data)?;
+    let mut header = Header {
+        data: &data,
+        pos: 0,
+    };
+    let magic = header.token();
+    let encoding = if magic == P::ASCII.as_bytes() {
+        Encoding::Ascii
+    } else if magic == P::BINARY.as_bytes() {
+        Encoding::Binary
+    } else {
+        return Err(NetpbmError::Magic {
+            expected: P::KIND,
+            found: String::from_utf8_lossy(magic).into_owned(),
+        });
+    };
+    let width = header.number("a width")? as usize;
+    let height = header.number("a height")? as usize;
+    let max = header.number("a maximum value")?;
+    if !(1..=255).contains(&max) {
+        return Err(NetpbmError::MaxValue(max));
+    }
+
+    // The size comes from the file: a header can ask for more than the file could ever hold,
+    // so never reserve more samples than there are bytes
+    let expected = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(P::CHANNELS))
+        .ok_or(NetpbmError::TooLarge { width, height })?;
+    let mut samples = Vec::with_capacity(expected.min(data.len()));
+    match encoding {
+        Encoding::Ascii => loop {
+            let token = header.token();
+            if token.is_empty() {
+                break;
+            }
+            let value = std::str::from_utf8(token)
+                .ok()
+                .and_then(|text| text.parse::<u32>().ok())
+                .ok_or_else(|| NetpbmError::Sample(String::from_utf8_lossy(token).into_owned()))?;
+            samples.push(value);
+        },
+        Encoding::Binary => {
+            // a single whitespace byte separates the header from the samples
+            let pos = header.pos;
+            if !data.get(pos).is_some_and(u8::is_ascii_whitespace) {
+                return Err(NetpbmError::Header("whitespace after the maximum value"));
+            }
+            samples.extend(data[pos + 1..].iter().map(|&b| b as u32));
+        }
+    }
+    if samples.len() < expected {
+        return Err(NetpbmError::Truncated {
+            width,
+            height,
+            expected,
+            found: samples.len(),
+        });
+    }
+    samples.truncate(expected);
+
+    let bytes = samples
+        .iter()
+        .map(|&value| match value {
+            value if value > max => Err(NetpbmError::Value { value, max }),
+            value => Ok(((value * 255 + max / 2) / max) as u8),
+        })
+        .collect::<Result<Vec<u8>, _>>()?;
+    let pixels = bytes
+        .chunks_exact(P::CHANNELS)
+        .map(P::from_samples)
+        .collect();
+    Ok(Image::from_vec(width, height, pixels).expect("one pixel per sample group"))
+}
+
+/// Write with a maximum value of 255. Text lines stay under 70 characters as the format asks.
+pub fn write<P: NetpbmPixel>(
+    image: &Image<P>,
+    mut out: impl Write,
+    encoding: Encoding,
+) -> io::Result<()> {
+    let magic = match encoding {
+        Encoding::Ascii => P::ASCII,
+        Encoding::Binary => P::BINARY,
+    };
+    writeln!(out, "{}\n{} {}\n255", magic, image.width(), image.height())?;
+    match encoding {
+        Encoding::Binary => {
+            for pixel in image.pixels() {
+                out.write_all(pixel.samples())?;
+            }
+        }
+        Encoding::Ascii => {
+            for row in image.rows() {
+                let mut line = String::new();
+                for sample in row.iter().flat_map(|pixel| pixel.samples()) {
+                    if line.len() + 4 > 70 {
+                        writeln!(out, "{}", line)?;
+                        line.clear();
+                    }
+                    if !line.is_empty() {
+                        line.push(' ');
+                    }
+                    line.push_str(&sample.to_string());
+                }
+                writeln!(out, "{}", line)?;
+            }
+        }
+    }
+    out.flush()
+}
+
+pub fn load<P: NetpbmPixel>(path: impl AsRef<Path>) -> Result<Image<P>, NetpbmError> {
+    read(BufReader::new(File::open(path)?))
+}
+
+pub fn save<P: NetpbmPixel>(
+    image: &Image<P>,
+    path: impl AsRef<Path>,
+    encoding: Encoding,
+) -> io::Result<()> {
+    write(image, BufWriter::new(File::create(path)?), encoding)
+}
+
+// Both keep their grey pixels row after row, so converting only moves the buffer
+impl From<Image<u8>> for GrayImage {
+    fn from(image: Image<u8>) -> GrayImage {
+        let (width, height) = (image.width() as u32, image.height() as u32);
+        GrayImage::from_raw(width, height, image.into_vec()).expect("buffer of the right size")
+    }
+}
+
+impl From<GrayImage> for Image<u8> {
+    fn from(buffer: GrayImage) -> Image<u8> {
+        let (width, height) = buffer.dimensions();
+        Image::from_vec(width as usize, height as usize, buffer.into_raw())
+            .expect("buffer of the right size")
+    }
+}
+
+// An RgbImage keeps its samples flat, three bytes a pixel, so the pixels are flattened first
+impl From<Image<[u8; 3]>> for RgbImage {
+    fn from(image: Image<[u8; 3]>) -> RgbImage {
+        let (width, height) = (image.width() as u32, image.height() as u32);
+        let samples = image.into_vec().into_iter().flatten().collect();
+        RgbImage::from_raw(width, height, samples).expect("buffer of the right size")
+    }
+}
+
+impl From<RgbImage> for Image<[u8; 3]> {
+    fn from(buffer: RgbImage) -> Image<[u8; 3]> {
+        let (width, height) = buffer.dimensions();
+        Image::from_vec(
+            width as usize,
+            height as usize,
+            buffer.pixels().map(|&Rgb(pixel)| pixel).collect(),
+        )
+        .expect("buffer of the right size")
+    }
+}
+
+#[cfg(test)]
+fn gradient() -> Image<u8> {
+    Image::from_fn(31, 7, |x, y| (x * 8 + y) as u8)
+}
+
+#[cfg(test)]
+fn colours() -> Image<[u8; 3]> {
+    Image::from_fn(5, 4, |x, y| {
+        [(x * 60) as u8, (y * 80) as u8, 255 - (x * y) as u8]
+    })
+}
+
+#[test]
+fn test_round_trips() {
+    for encoding in [Encoding::Ascii, Encoding::Binary] {
+        let mut file = vec![];
+        write(&gradient(), &mut file, encoding).unwrap();
+        assert_eq!(read::<u8>(&file[..]).unwrap(), gradient());
+
+        let mut file = vec![];
+        write(&colours(), &mut file, encoding).unwrap();
+        assert_eq!(read::<[u8; 3]>(&file[..]).unwrap(), colours());
+    }
+    let mut file = vec![];
+    write(&Image::<u8>::new(0, 0), &mut file, Encoding::Binary).unwrap();
+    assert_eq!(read::<u8>(&file[..]).unwrap(), Image::new(0, 0));
+}
+
+#[test]
+fn test_written_layout() {
+    let image = Image::from_vec(2, 2, vec![0u8, 1, 254, 255]).unwrap();
+    let mut file = vec![];
+    write(&image, &mut file, Encoding::Binary).unwrap();
+    assert_eq!(file, b"P5\n2 2\n255\n\x00\x01\xfe\xff");
+    let mut file = vec![];
+    write(&image, &mut file, Encoding::Ascii).unwrap();
+    assert_eq!(file, b"P2\n2 2\n255\n0 1\n254 255\n");
+
+    let mut file = vec![];
+    write(&gradient(), &mut file, Encoding::Ascii).unwrap();
+    assert!(file.split(|&b| b == b'\n').all(|line| line.len() <= 70));
+}
+
+#[test]
+fn test_reading_other_files() {
+    // comments, odd spacing and a maximum value other than 255
+    let pgm = b"P2 # grey\n# made by hand\n3\t2 # size\n 4\n0 1 2\n3 4\n\n0";
+    let image = read::<u8>(&pgm[..]).unwrap();
+    assert_eq!(image.as_slice(), [0, 64, 128, 191, 255, 0]);
+    // a binary sample may be a whitespace or '#' byte
+    let ppm = b"P6 1 1 255\n#\n ";
+    assert_eq!(
+        read::<[u8; 3]>(&ppm[..]).unwrap()[(0, 0)],
+        [b'#', b'\n', b' ']
+    );
+
+    let error = |file: &[u8]| read::<u8>(file).unwrap_err().to_string();
+    assert_eq!(
+        error(b"P6 1 1 255\n..."),
+        "not a PGM file, found magic number \"P6\""
+    );
+    assert_eq!(error(b"P2 1 x 255"), "bad header, expected a height");
+    assert_eq!(
+        error(b"P5 1 1 65535\n\0\0"),
+        "maximum value 65535 not supported, only 1 to 255"
+    );
+    assert_eq!(error(b"P2 2 1 9 3 -1"), "bad sample \"-1\"");
+    assert_eq!(
+        error(b"P2 2 1 9 3 10"),
+        "pixel value 10 above the maximum of 9"
+    );
+    assert_eq!(
+        error(b"P5 2 2 255\n\0\0\0"),
+        "2x2 image needs 4 samples, found 3"
+    );
+    assert_eq!(
+        error(b"P5 1 1 255"),
+        "bad header, expected whitespace after the maximum value"
+    );
+}
+
+#[test]
+fn test_oversized_header() {
+    let error = |file: &[u8]| read::<[u8; 3]>(file).unwrap_err().to_string();
+    assert_eq!(
+        error(b"P6 4294967295 4294967295 255\n"),
+        "4294967295x4294967295 image is too large"
+    );
+    let error = |file: &[u8]| read::<u8>(file).unwrap_err().to_string();
+    assert_eq!(
+        error(b"P5 4294967295 4294967295 255\n\0"),
+        "4294967295x4294967295 image needs 18446744065119617025 samples, found 1"
+    );
+    assert_eq!(
+        error(b"P5 60000 60000 255\n\0\0"),
+        "60000x60000 image needs 3600000000 samples, found 2"
+    );
+    assert_eq!(
+        error(b"P2 60000 60000 255 1 2 3"),
+        "60000x60000 image needs 3600000000 samples, found 3"
+    );
+}
+
+#[test]
+fn test_image_buffers() {
+    let buffer = GrayImage::from(gradient());
+    assert_eq!(buffer.dimensions(), (31, 7));
+    assert_eq!(buffer.get_pixel(3, 2), &image::Luma([26]));
+    assert_eq!(Image::from(buffer), gradient());
+
+    let buffer = RgbImage::from(colours());
+    assert_eq!(buffer.get_pixel(4, 3), &Rgb([240, 240, 243]));
+    assert_eq!(Image::from(buffer), colours());
+}
+
+#[test]
+fn test_files() {
+    let path = std::env::temp_dir().join(format!("netpbm-test-{}.ppm", std::process::id()));
+    save(&colours(), &path, Encoding::Binary).unwrap();
+    let loaded = load::<[u8; 3]>(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.unwrap(), colours());
+}
@@ -0,0 +1,494 @@
+// A 2D image of pixels of any type, stored row after row in a single Vec
+use std::ops::{Add, Index, IndexMut, Mul, Sub};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Image<P> {
+    pixels: Vec<P>,
+    width: usize,
+    height: usize,
+}
+
+/// A rectangle of an image, borrowed: rows are `stride` pixels apart in `pixels`, which starts at
+/// the top left corner of the rectangle.
+#[derive(Debug)]
+pub struct View<'a, P> {
+    pixels: &'a [P],
+    width: usize,
+    height: usize,
+    stride: usize,
+}
+
+// derive would ask for P: Copy, a view is only a borrow
+impl<P> Clone for View<'_, P> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<P> Copy for View<'_, P> {}
+
+impl<P: Default + Clone> Image<P> {
+    pub fn new(width: usize, height: usize) -> Self {
+        Image {
+            pixels: vec![P::default(); width * height],
+            width,
+            height,
+        }
+    }
+}
+
+impl<P> Image<P> {
+    /// `None` unless there are exactly `width * height` pixels.
+    pub fn from_vec(width: usize, height: usize, pixels: Vec<P>) -> Option<Self> {
+        if pixels.len() != width * height {
+            return None;
+        }
+        Some(Image {
+            pixels,
+            width,
+            height,
+        })
+    }
+
+    /// Pixel `(x, y)` is `pixel(x, y)`.
+    pub fn from_fn(width: usize, height: usize, mut pixel: impl FnMut(usize, usize) -> P) -> Self {
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| pixel(x, y))
+            .collect();
+        Image {
+            pixels,
+            width,
+            height,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// All the pixels, row after row.
+    pub fn as_slice(&self) -> &[P] {
+        &self.pixels
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [P] {
+        &mut self.pixels
+    }
+
+    pub fn into_vec(self) -> Vec<P> {
+        self.pixels
+    }
+
+    /// The whole image as a view.
+    pub fn as_view(&self) -> View<'_, P> {
+        View {
+            pixels: &self.pixels,
+            width: self.width,
+            height: self.height,
+            stride: self.width,
+        }
+    }
+
+    /// The `width` by `height` rectangle with `(x, y)` at its top left, if it fits in the image.
+    pub fn view(&self, x: usize, y: usize, width: usize, height: usize) -> Option<View<'_, P>> {
+        self.as_view().view(x, y, width, height)
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&P> {
+        if x < self.width && y < self.height {
+            Some(&self.pixels[y * self.width + x])
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut P> {
+        if x < self.width && y < self.height {
+            Some(&mut self.pixels[y * self.width + x])
+        } else {
+            None
+        }
+    }
+
+    /// Rows from top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item = &[P]> + '_ {
+        self.as_view().rows()
+    }
+
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [P]> + '_ {
+        // an empty row would make chunks_mut panic, and there is nothing to iterate anyway
+        let width = self.width.max(1);
+        self.pixels.chunks_mut(width)
+    }
+
+    /// The pixels of column `x`, from top to bottom.
+    pub fn column(&self, x: usize) -> impl Iterator<Item = &P> + '_ {
+        self.as_view().column(x)
+    }
+
+    /// Columns from left to right.
+    pub fn columns(&self) -> impl Iterator<Item = impl Iterator<Item = &P> + '_> + '_ {
+        self.as_view().columns()
+    }
+
+    /// Every pixel, row after row.
+    pub fn pixels(&self) -> std::slice::Iter<'_, P> {
+        self.pixels.iter()
+    }
+
+    pub fn pixels_mut(&mut self) -> std::slice::IterMut<'_, P> {
+        self.pixels.iter_mut()
+    }
+
+    /// Every pixel with its `(x, y)`, row after row.
+    pub fn enumerate_pixels(&self) -> impl Iterator<Item = (usize, usize, &P)> + '_ {
+        self.as_view().enumerate_pixels()
+    }
+
+    /// An image of the same size with `f` applied to every pixel.
+    pub fn map<Q>(&self, f: impl FnMut(&P) -> Q) -> Image<Q> {
+        Image {
+            pixels: self.pixels.iter().map(f).collect(),
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// Combine the pixels of two images of the same size.
+    ///
+    /// Panics if the sizes differ.
+    pub fn zip_map<Q, R>(&self, other: &Image<Q>, mut f: impl FnMut(&P, &Q) -> R) -> Image<R> {
+        self.assert_same_size(other);
+        Image {
+            pixels: self
+                .pixels
+                .iter()
+                .zip(&other.pixels)
+                .map(|(p, q)| f(p, q))
+                .collect(),
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    fn assert_same_size<Q>(&self, other: &Image<Q>) {
+        assert!(
+            self.width == other.width && self.height == other.height,
+            "images of different sizes: {}x{} and {}x{}",
+            self.width,
+            self.height,
+            other.width,
+            other.height
+        );
+    }
+}
+
+impl<'a, P> View<'a, P> {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// A rectangle of this view, `(x, y)` being relative to its top left corner.
+    pub fn view(&self, x: usize, y: usize, width: usize, height: usize) -> Option<View<'a, P>> {
+        if x.checked_add(width)? > self.width || y.checked_add(height)? > self.height {
+            return None;
+        }
+        let start = y * self.stride + x;
+        // an empty view keeps no pixels, it may start past the end of the last row
+        let len = match height {
+            0 => 0,
+            _ => (height - 1) * self.stride + width,
+        };
+        let pixels = if len == 0 {
+            &self.pixels[..0]
+        } else {
+            &self.pixels[start..start + len]
+        };
+        Some(View {
+            pixels,
+            width,
+            height,
+            stride: self.stride,
+        })
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&'a P> {
+        if x < self.width && y < self.height {
+            Some(&self.pixels[y * self.stride + x])
+        } else {
+            None
+        }
+    }
+
+    pub fn row(&self, y: usize) -> Option<&'a [P]> {
+        if y < self.height {
+            let start = y * self.stride;
+            Some(&self.pixels[start..start + self.width])
+        } else {
+            None
+        }
+    }
+
+    pub fn rows(self) -> impl Iterator<Item = &'a [P]> {
+        (0..self.height).map(move |y| &self.pixels[y * self.stride..][..self.width])
+    }
+
+    /// Panics unless `x` is a column of the view.
+    pub fn column(self, x: usize) -> impl Iterator<Item = &'a P> {
+        assert!(x < self.width, "column {} of {}", x, self.width);
+        self.pixels
+            .iter()
+            .skip(x)
+            .step_by(self.stride)
+            .take(self.height)
+    }
+
+    pub fn columns(self) -> impl Iterator<Item = impl Iterator<Item = &'a P>> {
+        (0..self.width).map(move |x| self.column(x))
+    }
+
+    pub fn pixels(self) -> impl Iterator<Item = &'a P> {
+        self.rows().flatten()
+    }
+
+    pub fn enumerate_pixels(self) -> impl Iterator<Item = (usize, usize, &'a P)> {
+        self.rows()
+            .enumerate()
+            .flat_map(|(y, row)| row.iter().enumerate().map(move |(x, p)| (x, y, p)))
+    }
+
+    pub fn map<Q>(&self, f: impl FnMut(&P) -> Q) -> Image<Q> {
+        Image {
+            pixels: self.pixels().map(f).collect(),
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// Copy the pixels out into an image of their own.
+    pub fn to_image(self) -> Image<P>
+    where
+        P: Clone,
+    {
+        self.map(P::clone)
+    }
+}
+
+// Returns a slice of the pixels in the row
+// so we can access the individual pixels in the row
+impl<P> Index<usize> for Image<P> {
+    type Output = [P];
+    fn index(&self, row: usize) -> &[P] {
+        let start = row * self.width;
+        &self.pixels[start..start + self.width]
+    }
+}
+
+// Returns a slice of the pixels in the row
+// so we can access the individual pixels in the row
+impl<P> IndexMut<usize> for Image<P> {
+    fn index_mut(&mut self, row: usize) -> &mut [P] {
+        let start = row * self.width;
+        &mut self.pixels[start..start + self.width]
+    }
+}
+
+// The pixel at column x of row y: image[(x, y)] is image[y][x]. A column past the end of the row
+// panics instead of reading the start of the next row
+impl<P> Index<(usize, usize)> for Image<P> {
+    type Output = P;
+    fn index(&self, (x, y): (usize, usize)) -> &P {
+        let (width, height) = (self.width, self.height);
+        self.get(x, y)
+            .unwrap_or_else(|| panic!("pixel ({}, {}) out of a {}x{} image", x, y, width, height))
+    }
+}
+
+impl<P> IndexMut<(usize, usize)> for Image<P> {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut P {
+        let (width, height) = (self.width, self.height);
+        self.get_mut(x, y)
+            .unwrap_or_else(|| panic!("pixel ({}, {}) out of a {}x{} image", x, y, width, height))
+    }
+}
+
+impl<P> Index<(usize, usize)> for View<'_, P> {
+    type Output = P;
+    fn index(&self, (x, y): (usize, usize)) -> &P {
+        self.get(x, y).unwrap_or_else(|| {
+            panic!(
+                "pixel ({}, {}) out of a {}x{} view",
+                x, y, self.width, self.height
+            )
+        })
+    }
+}
+
+// Pixel by pixel arithmetic between images of the same size (panics if they differ), and between
+// an image and a scalar applied to every pixel. Owned images are reused for the result
+macro_rules! pixel_op {
+    ($($trait:ident $method:ident)*) => {
+        $(
+            impl<P: $trait<Output = P> + Copy> $trait for &Image<P> {
+                type Output = Image<P>;
+                fn $method(self, other: &Image<P>) -> Image<P> {
+                    self.zip_map(other, |&p, &q| p.$method(q))
+                }
+            }
+
+            impl<P: $trait<Output = P> + Copy> $trait<&Image<P>> for Image<P> {
+                type Output = Image<P>;
+                fn $method(mut self, other: &Image<P>) -> Image<P> {
+                    self.assert_same_size(other);
+                    for (p, &q) in self.pixels.iter_mut().zip(&other.pixels) {
+                        *p = p.$method(q);
+                    }
+                    self
+                }
+            }
+
+            impl<P: $trait<Output = P> + Copy> $trait for Image<P> {
+                type Output = Image<P>;
+                fn $method(self, other: Image<P>) -> Image<P> {
+                    self.$method(&other)
+                }
+            }
+
+            impl<P: $trait<Output = P> + Copy> $trait<P> for &Image<P> {
+                type Output = Image<P>;
+                fn $method(self, scalar: P) -> Image<P> {
+                    self.map(|&p| p.$method(scalar))
+                }
+            }
+
+            impl<P: $trait<Output = P> + Copy> $trait<P> for Image<P> {
+                type Output = Image<P>;
+                fn $method(mut self, scalar: P) -> Image<P> {
+                    for p in &mut self.pixels {
+                        *p = p.$method(scalar);
+                    }
+                    self
+                }
+            }
+        )*
+    };
+}
+
+pixel_op!(Add add Sub sub Mul mul);
+
+#[cfg(test)]
+fn numbered(width: usize, height: usize) -> Image<usize> {
+    Image::from_fn(width, height, |x, y| y * 10 + x)
+}
+
+#[test]
+fn test_index_and_get() {
+    let mut image = numbered(4, 3);
+    assert_eq!(image[(3, 2)], 23);
+    assert_eq!(image[2][3], 23);
+    image[(1, 2)] = 99;
+    assert_eq!(image.get(1, 2), Some(&99));
+    assert_eq!(image.get(4, 0), None);
+    assert_eq!(image.get(0, 3), None);
+    *image.get_mut(0, 0).unwrap() = 7;
+    assert_eq!(image[0], [7, 1, 2, 3]);
+    assert!(image.get_mut(9, 9).is_none());
+    assert_eq!(Image::from_vec(2, 2, vec![1, 2, 3]), None);
+    assert_eq!(
+        Image::from_vec(2, 1, vec![1, 2]).unwrap().into_vec(),
+        [1, 2]
+    );
+}
+
+#[test]
+#[should_panic(expected = "pixel (4, 0) out of a 4x3 image")]
+fn test_index_past_the_row() {
+    // would be pixel (0, 1) if the column was not checked
+    let _ = numbered(4, 3)[(4, 0)];
+}
+
+#[test]
+fn test_iterators() {
+    let mut image = numbered(3, 2);
+    let rows: Vec<&[usize]> = image.rows().collect();
+    assert_eq!(rows, [&[0, 1, 2][..], &[10, 11, 12]]);
+    assert_eq!(image.column(1).copied().collect::<Vec<_>>(), [1, 11]);
+    let columns: Vec<Vec<usize>> = image.columns().map(|c| c.copied().collect()).collect();
+    assert_eq!(columns, [[0, 10], [1, 11], [2, 12]]);
+    assert_eq!(image.pixels().sum::<usize>(), 36);
+    assert_eq!(image.enumerate_pixels().nth(4), Some((1, 1, &11)),);
+    for row in image.rows_mut() {
+        row.reverse();
+    }
+    for p in image.pixels_mut() {
+        *p += 100;
+    }
+    assert_eq!(image.as_slice(), [102, 101, 100, 112, 111, 110]);
+    assert_eq!(Image::<u8>::new(0, 5).rows_mut().count(), 0);
+}
+
+#[test]
+fn test_views() {
+    let image = numbered(5, 4);
+    let view = image.view(1, 1, 3, 2).unwrap();
+    assert_eq!((view.width(), view.height()), (3, 2));
+    assert_eq!(view[(0, 0)], 11);
+    assert_eq!(view.get(2, 1), Some(&23));
+    assert_eq!(view.get(3, 0), None);
+    assert_eq!(view.row(1), Some(&[21, 22, 23][..]));
+    assert_eq!(view.row(2), None);
+    assert_eq!(
+        view.pixels().copied().collect::<Vec<_>>(),
+        [11, 12, 13, 21, 22, 23]
+    );
+    assert_eq!(view.column(2).copied().collect::<Vec<_>>(), [13, 23]);
+    assert_eq!(view.columns().count(), 3);
+    assert_eq!(view.enumerate_pixels().last(), Some((2, 1, &23)));
+
+    // a view of a view stays in the coordinates of the first
+    let inner = view.view(1, 1, 2, 1).unwrap();
+    assert_eq!(
+        inner.to_image(),
+        Image::from_vec(2, 1, vec![22, 23]).unwrap()
+    );
+    assert_eq!(view.map(|p| p % 10).as_slice(), [1, 2, 3, 1, 2, 3]);
+
+    // the bottom right corner fits, one more does not
+    assert_eq!(image.view(4, 3, 1, 1).unwrap()[(0, 0)], 34);
+    assert!(image.view(4, 3, 2, 1).is_none());
+    assert!(image.view(0, 0, 5, 5).is_none());
+    assert!(image.view(usize::MAX, 0, 2, 1).is_none());
+    let empty = image.view(5, 4, 0, 0).unwrap();
+    assert_eq!(empty.pixels().count(), 0);
+    assert_eq!(image.as_view().to_image(), image);
+}
+
+#[test]
+fn test_arithmetic() {
+    let a = Image::from_vec(2, 2, vec![1, 2, 3, 4]).unwrap();
+    let b = Image::from_vec(2, 2, vec![10, 20, 30, 40]).unwrap();
+    assert_eq!((&a + &b).as_slice(), [11, 22, 33, 44]);
+    assert_eq!((&b - &a).as_slice(), [9, 18, 27, 36]);
+    assert_eq!((&a * &b).as_slice(), [10, 40, 90, 160]);
+    assert_eq!((&a * 3).as_slice(), [3, 6, 9, 12]);
+    assert_eq!((b.clone() - 5).as_slice(), [5, 15, 25, 35]);
+    assert_eq!((a.clone() + &b).as_slice(), [11, 22, 33, 44]);
+    assert_eq!((a.clone() * a.clone() + 1).as_slice(), [2, 5, 10, 17]);
+    let brighter = a.map(|&p| p as f32) * 1.5;
+    assert_eq!(brighter.as_slice(), [1.5, 3.0, 4.5, 6.0]);
+}
+
+#[test]
+#[should_panic(expected = "images of different sizes: 2x1 and 1x2")]
+fn test_arithmetic_sizes_differ() {
+    let _ = Image::<u8>::new(2, 1) + Image::new(1, 2);
+}
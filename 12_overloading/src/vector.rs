@@ -0,0 +1,246 @@
+// 2D and 3D vectors over any num::Num, written once by a macro for both
+use std::cmp::Ordering;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+use num::{Float, Num, Zero};
+
+// The struct and everything that is the same for every dimension. Vectors add and subtract,
+// scalars multiply and divide them; the products of two vectors are the methods dot and cross
+macro_rules! vector {
+    ($(#[$doc:meta])* $name:ident { $($field:ident),* }) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+        pub struct $name<T> {
+            $(pub $field: T),*
+        }
+
+        impl<T> $name<T> {
+            pub const fn new($($field: T),*) -> Self {
+                $name { $($field),* }
+            }
+        }
+
+        impl<T: Num + Copy> $name<T> {
+            pub fn dot(&self, other: &Self) -> T {
+                T::zero() $(+ self.$field * other.$field)*
+            }
+
+            /// The squared length, `v · v`.
+            pub fn length_sqr(&self) -> T {
+                self.dot(self)
+            }
+        }
+
+        impl<T: Float> $name<T> {
+            pub fn length(&self) -> T {
+                self.length_sqr().sqrt()
+            }
+
+            /// The vector of length 1 going the same way, `None` for the zero vector.
+            pub fn normalized(&self) -> Option<Self> {
+                let length = self.length();
+                (length > T::zero()).then(|| *self / length)
+            }
+        }
+
+        impl<T: Num + Copy> Add for $name<T> {
+            type Output = Self;
+            fn add(self, other: Self) -> Self {
+                $name { $($field: self.$field + other.$field),* }
+            }
+        }
+
+        impl<T: Num + Copy> Sub for $name<T> {
+            type Output = Self;
+            fn sub(self, other: Self) -> Self {
+                $name { $($field: self.$field - other.$field),* }
+            }
+        }
+
+        impl<T: Num + Copy> Mul<T> for $name<T> {
+            type Output = Self;
+            fn mul(self, t: T) -> Self {
+                $name { $($field: self.$field * t),* }
+            }
+        }
+
+        impl<T: Num + Copy> Div<T> for $name<T> {
+            type Output = Self;
+            fn div(self, t: T) -> Self {
+                $name { $($field: self.$field / t),* }
+            }
+        }
+
+        impl<T: Num + Copy + Neg<Output = T>> Neg for $name<T> {
+            type Output = Self;
+            fn neg(self) -> Self {
+                $name { $($field: -self.$field),* }
+            }
+        }
+
+        impl<T: Num + Copy + Neg<Output = T>> Neg for &$name<T> {
+            type Output = $name<T>;
+            fn neg(self) -> $name<T> {
+                -*self
+            }
+        }
+
+        forward_ref_binop!($name $name<T>, Add add Sub sub);
+        forward_ref_binop!($name T, Mul mul Div div);
+        assign_op!($name $name<T>, AddAssign add_assign add SubAssign sub_assign sub);
+        assign_op!($name T, MulAssign mul_assign mul DivAssign div_assign div);
+
+        impl<T: Num + Copy> Zero for $name<T> {
+            fn zero() -> Self {
+                $name { $($field: T::zero()),* }
+            }
+
+            fn is_zero(&self) -> bool {
+                true $(&& self.$field.is_zero())*
+            }
+        }
+
+        impl<T: Num + Copy> Sum for $name<T> {
+            fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+                iter.fold(Self::zero(), Add::add)
+            }
+        }
+
+        impl<'a, T: Num + Copy + 'a> Sum<&'a $name<T>> for $name<T> {
+            fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+                iter.fold(Self::zero(), Add::add)
+            }
+        }
+
+        // The product order: a <= b when every component is. Vectors with some components
+        // larger and some smaller are not comparable, which is what partial_cmp's None is for
+        impl<T: PartialOrd> PartialOrd for $name<T> {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                let mut order = Ordering::Equal;
+                $(
+                    match (order, self.$field.partial_cmp(&other.$field)?) {
+                        (_, Ordering::Equal) => {}
+                        (Ordering::Equal, next) => order = next,
+                        (order, next) if order != next => return None,
+                        _ => {}
+                    }
+                )*
+                Some(order)
+            }
+        }
+
+        scalar_times_vector!($name, f32 f64 i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize);
+    };
+}
+
+// `2.0 * v` as well as `v * 2.0`, one impl per scalar type
+macro_rules! scalar_times_vector {
+    ($name:ident, $($t:ty)*) => {
+        $(
+            impl Mul<$name<$t>> for $t {
+                type Output = $name<$t>;
+                fn mul(self, v: $name<$t>) -> $name<$t> {
+                    v * self
+                }
+            }
+        )*
+    };
+}
+
+vector!(
+    /// A point or a displacement in the plane.
+    Vec2 { x, y }
+);
+
+vector!(
+    /// A point or a displacement in space.
+    Vec3 { x, y, z }
+);
+
+impl<T: Num + Copy> Vec2<T> {
+    /// The z of the cross product of the vectors in the plane z = 0: positive when `other` is
+    /// counterclockwise from `self`.
+    pub fn cross(&self, other: &Self) -> T {
+        self.x * other.y - self.y * other.x
+    }
+}
+
+impl<T: Num + Copy> Vec3<T> {
+    /// The vector at right angles to both, its length the area of their parallelogram.
+    pub fn cross(&self, other: &Self) -> Self {
+        Vec3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+}
+
+#[test]
+#[allow(clippy::op_ref)] // the reference forms are under test
+fn test_operators() {
+    let a = Vec3::new(1, 2, 3);
+    let b = Vec3::new(4, -5, 6);
+    assert_eq!(a + b, Vec3::new(5, -3, 9));
+    assert_eq!(&a - &b, Vec3::new(-3, 7, -3));
+    assert_eq!(a * 2, Vec3::new(2, 4, 6));
+    assert_eq!(2 * a, a * 2);
+    assert_eq!(b / 2, Vec3::new(2, -2, 3));
+    assert_eq!(-a, Vec3::new(-1, -2, -3));
+    assert_eq!(-&a + a, Vec3::zero());
+    assert_eq!(a.dot(&b), 12);
+    assert_eq!(a.length_sqr(), 14);
+    let mut v = a;
+    v += b;
+    v -= &a;
+    v *= 3;
+    v /= &3;
+    assert_eq!(v, b);
+
+    let p = Vec2::new(1.5, -2.0);
+    assert_eq!(p * 2.0 - Vec2::new(3.0, 0.0), Vec2::new(0.0, -4.0));
+    assert_eq!(Vec2::new(3.0, 4.0).length(), 5.0);
+    assert_eq!(Vec2::new(0.0, 2.0).normalized(), Some(Vec2::new(0.0, 1.0)));
+    assert_eq!(Vec2::<f64>::zero().normalized(), None);
+}
+
+#[test]
+fn test_cross_products() {
+    let (x, y, z) = (Vec3::new(1, 0, 0), Vec3::new(0, 1, 0), Vec3::new(0, 0, 1));
+    assert_eq!(x.cross(&y), z);
+    assert_eq!(y.cross(&x), -z);
+    let (a, b) = (Vec3::new(2, 3, -1), Vec3::new(-4, 1, 5));
+    let c = a.cross(&b);
+    assert_eq!((c.dot(&a), c.dot(&b)), (0, 0));
+    assert_eq!(Vec2::new(1, 0).cross(&Vec2::new(0, 1)), 1);
+    assert_eq!(Vec2::new(0, 1).cross(&Vec2::new(1, 0)), -1);
+}
+
+#[test]
+fn test_sum_and_order() {
+    let points = [
+        Vec2::new(1.0, 2.0),
+        Vec2::new(3.0, -1.0),
+        Vec2::new(-1.0, 5.0),
+    ];
+    let centre = points.iter().sum::<Vec2<f64>>() / points.len() as f64;
+    assert_eq!(centre, Vec2::new(1.0, 2.0));
+    assert_eq!(
+        points.into_iter().map(|p| p - centre).sum::<Vec2<f64>>(),
+        Vec2::zero()
+    );
+
+    let (low, high) = (Vec3::new(0, 0, 0), Vec3::new(1, 2, 0));
+    assert!(low < high);
+    assert!(high >= low);
+    assert_eq!(low.partial_cmp(&low), Some(Ordering::Equal));
+    // one component up, another down: neither is smaller
+    let (a, b) = (Vec2::new(1, 2), Vec2::new(2, 1));
+    assert_eq!(a.partial_cmp(&b), None);
+    assert_eq!((a <= b, a >= b), (false, false));
+    assert_eq!(
+        Vec2::new(f64::NAN, 0.0).partial_cmp(&Vec2::new(0.0, 0.0)),
+        None
+    );
+}
@@ -0,0 +1,464 @@
+// Small Read/Write wrappers that stack on top of each other
+//
+// Each one owns the reader or writer it wraps, is generic over it (like `say_hello<W: Write>`
+// in the traits chapter) and gives it back with `into_inner`.
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Read, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use sha2::Digest;
+
+fn count_lines(buf: &[u8]) -> u64 {
+    buf.iter().filter(|&&b| b == b'\n').count() as u64
+}
+
+/// Writes everything to both writers.
+pub struct TeeWriter<A: Write, B: Write> {
+    first: A,
+    second: B,
+}
+
+impl<A: Write, B: Write> TeeWriter<A, B> {
+    pub fn new(first: A, second: B) -> Self {
+        TeeWriter { first, second }
+    }
+
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+impl<A: Write, B: Write> Write for TeeWriter<A, B> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // the second writer gets exactly what the first one accepted
+        let n = self.first.write(buf)?;
+        self.second.write_all(&buf[..n])?;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.first.flush()?;
+        self.second.flush()
+    }
+}
+
+/// Counts the bytes and the lines (`\n`) read through it.
+pub struct CountingReader<R> {
+    inner: R,
+    bytes: u64,
+    lines: u64,
+    // where the `\n`s are in the buffer `fill_buf` last handed out, `consumed` bytes of its
+    // `buffered` already counted: `consume` gets no buffer and must not read to get one
+    newlines: VecDeque<usize>,
+    buffered: usize,
+    consumed: usize,
+}
+
+impl<R> CountingReader<R> {
+    pub fn new(inner: R) -> Self {
+        CountingReader {
+            inner,
+            bytes: 0,
+            lines: 0,
+            newlines: VecDeque::new(),
+            buffered: 0,
+            consumed: 0,
+        }
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes
+    }
+
+    pub fn lines_read(&self) -> u64 {
+        self.lines
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.bytes += n as u64;
+        self.lines += count_lines(&buf[..n]);
+        // this may have taken from the buffer, the next fill_buf looks at it afresh
+        self.newlines.clear();
+        self.buffered = 0;
+        self.consumed = 0;
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for CountingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let buf = self.inner.fill_buf()?;
+        // a BufRead only refills once its buffer is used up, until then it hands out what is
+        // left of the buffer already scanned
+        if buf.len() != self.buffered - self.consumed {
+            self.newlines.clear();
+            self.newlines.extend(
+                buf.iter()
+                    .enumerate()
+                    .filter(|(_, &b)| b == b'\n')
+                    .map(|(i, _)| i),
+            );
+            self.buffered = buf.len();
+            self.consumed = 0;
+        }
+        Ok(buf)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.consumed += amt.min(self.buffered - self.consumed);
+        self.bytes += amt as u64;
+        while self.newlines.front().is_some_and(|&i| i < self.consumed) {
+            self.newlines.pop_front();
+            self.lines += 1;
+        }
+        self.inner.consume(amt)
+    }
+}
+
+/// Counts the bytes and the lines (`\n`) written through it.
+pub struct CountingWriter<W> {
+    inner: W,
+    bytes: u64,
+    lines: u64,
+}
+
+impl<W> CountingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        CountingWriter {
+            inner,
+            bytes: 0,
+            lines: 0,
+        }
+    }
+
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes
+    }
+
+    pub fn lines_written(&self) -> u64 {
+        self.lines
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.bytes += n as u64;
+        self.lines += count_lines(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Sleeps as needed so that, on average, no more than `bytes_per_second` go through.
+pub struct RateLimitedWriter<W> {
+    inner: W,
+    bytes_per_second: u64,
+    start: Instant,
+    written: u64,
+}
+
+impl<W> RateLimitedWriter<W> {
+    pub fn new(inner: W, bytes_per_second: u64) -> Self {
+        RateLimitedWriter {
+            inner,
+            bytes_per_second: bytes_per_second.max(1),
+            start: Instant::now(),
+            written: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for RateLimitedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // wait until the bytes already written are allowed, then send at most a tenth of a second's worth
+        let due = Duration::from_secs_f64(self.written as f64 / self.bytes_per_second as f64);
+        let elapsed = self.start.elapsed();
+        if due > elapsed {
+            thread::sleep(due - elapsed);
+        }
+        let chunk = (self.bytes_per_second / 10).max(1) as usize;
+        let n = self.inner.write(&buf[..buf.len().min(chunk)])?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Calls `progress` with the total number of bytes read so far, after every read.
+pub struct ProgressReader<R, F: FnMut(u64)> {
+    inner: R,
+    progress: F,
+    total: u64,
+}
+
+impl<R, F: FnMut(u64)> ProgressReader<R, F> {
+    pub fn new(inner: R, progress: F) -> Self {
+        ProgressReader {
+            inner,
+            progress,
+            total: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read, F: FnMut(u64)> Read for ProgressReader<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n > 0 {
+            self.total += n as u64;
+            (self.progress)(self.total);
+        }
+        Ok(n)
+    }
+}
+
+impl<R: BufRead, F: FnMut(u64)> BufRead for ProgressReader<R, F> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+        if amt > 0 {
+            self.total += amt as u64;
+            (self.progress)(self.total);
+        }
+    }
+}
+
+/// Anything that can digest a stream of bytes into a checksum or a hash.
+pub trait Checksum {
+    type Output;
+    fn update(&mut self, data: &[u8]);
+    fn finalize(self) -> Self::Output;
+}
+
+impl Checksum for crc32fast::Hasher {
+    type Output = u32;
+
+    fn update(&mut self, data: &[u8]) {
+        crc32fast::Hasher::update(self, data)
+    }
+
+    fn finalize(self) -> u32 {
+        crc32fast::Hasher::finalize(self)
+    }
+}
+
+impl Checksum for sha2::Sha256 {
+    type Output = [u8; 32];
+
+    fn update(&mut self, data: &[u8]) {
+        Digest::update(self, data)
+    }
+
+    fn finalize(self) -> [u8; 32] {
+        Digest::finalize(self).into()
+    }
+}
+
+/// Hashes everything written through it, e.g. `HashingWriter::new(file, Sha256::new())`.
+pub struct HashingWriter<W, C: Checksum> {
+    inner: W,
+    checksum: C,
+}
+
+impl<W, C: Checksum> HashingWriter<W, C> {
+    pub fn new(inner: W, checksum: C) -> Self {
+        HashingWriter { inner, checksum }
+    }
+
+    /// Give back the writer and the checksum of everything written.
+    pub fn finalize(self) -> (W, C::Output) {
+        (self.inner, self.checksum.finalize())
+    }
+}
+
+impl<W: Write, C: Checksum> Write for HashingWriter<W, C> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.checksum.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn test_tee_writer() {
+    let mut tee = TeeWriter::new(vec![], vec![]);
+    write!(tee, "hello {}", 42).unwrap();
+    let (first, second) = tee.into_inner();
+    assert_eq!(first, b"hello 42");
+    assert_eq!(first, second);
+}
+
+#[test]
+fn test_counting_reader_and_writer() {
+    let text = "one\ntwo\nthree";
+    let mut reader = CountingReader::new(text.as_bytes());
+    let mut copy = String::new();
+    reader.read_to_string(&mut copy).unwrap();
+    assert_eq!((reader.bytes_read(), reader.lines_read()), (13, 2));
+
+    // through `BufRead`, which only calls `fill_buf` and `consume`
+    let mut reader = CountingReader::new(io::BufReader::with_capacity(4, text.as_bytes()));
+    assert_eq!(reader.by_ref().lines().count(), 3);
+    assert_eq!((reader.bytes_read(), reader.lines_read()), (13, 2));
+
+    let mut writer = CountingWriter::new(vec![]);
+    writeln!(writer, "{}", text).unwrap();
+    assert_eq!((writer.bytes_written(), writer.lines_written()), (14, 3));
+    assert_eq!(writer.into_inner().len(), 14);
+}
+
+// A buffered reader for which every refill is an event: the bytes of each chunk, then an error
+#[cfg(test)]
+struct Chunks {
+    chunks: Vec<&'static [u8]>,
+    refills: usize,
+}
+
+#[cfg(test)]
+impl Read for Chunks {
+    fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+        unreachable!("only used through BufRead")
+    }
+}
+
+#[cfg(test)]
+impl BufRead for Chunks {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self.chunks.first() {
+            Some(chunk) if !chunk.is_empty() => Ok(chunk),
+            _ => {
+                self.refills += 1;
+                if self.chunks.len() > 1 {
+                    self.chunks.remove(0);
+                    Ok(self.chunks[0])
+                } else {
+                    Err(io::Error::new(io::ErrorKind::WouldBlock, "no more input"))
+                }
+            }
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.chunks[0] = &self.chunks[0][amt..];
+    }
+}
+
+#[test]
+fn test_counting_reader_does_not_read_in_consume() {
+    let chunks = Chunks {
+        chunks: vec![b"a\nb", b"\nc\n", b"d"],
+        refills: 0,
+    };
+    let mut reader = CountingReader::new(chunks);
+    let mut line = String::new();
+    for expected in ["a\n", "b\n", "c\n"] {
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, expected);
+    }
+    assert_eq!((reader.bytes_read(), reader.lines_read()), (6, 3));
+    // the last line never ends, reading it asks for more input and fails, "d" stays counted
+    assert!(reader.read_line(&mut line).is_err());
+    assert_eq!((reader.bytes_read(), reader.lines_read()), (7, 3));
+    assert_eq!(reader.into_inner().refills, 3);
+}
+
+#[test]
+fn test_rate_limited_writer() {
+    let start = Instant::now();
+    let mut writer = RateLimitedWriter::new(vec![], 1000);
+    writer.write_all(&[0; 300]).unwrap();
+    // the first 100 bytes go at once, the next 200 need 0.2s
+    assert!(start.elapsed() >= Duration::from_millis(190));
+    assert_eq!(writer.into_inner().len(), 300);
+}
+
+#[test]
+fn test_progress_reader() {
+    let mut seen = vec![];
+    let mut reader =
+        ProgressReader::new(io::BufReader::with_capacity(4, &b"0123456789"[..]), |n| {
+            seen.push(n)
+        });
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    drop(reader);
+    assert_eq!(seen, vec![4, 8, 10]);
+}
+
+#[test]
+fn test_hashing_writer() {
+    let mut writer = HashingWriter::new(vec![], sha2::Sha256::new());
+    writer.write_all(b"abc").unwrap();
+    let (buf, hash) = writer.finalize();
+    assert_eq!(buf, b"abc");
+    assert_eq!(
+        to_hex(&hash),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+
+    let mut writer = HashingWriter::new(io::sink(), crc32fast::Hasher::new());
+    writer.write_all(b"123456789").unwrap();
+    assert_eq!(writer.finalize().1, 0xcbf43926);
+}
+
+#[test]
+fn test_adapters_stack() {
+    let source = "line\n".repeat(1000);
+    let mut reader = CountingReader::new(ProgressReader::new(source.as_bytes(), |_| {}));
+    let sha = HashingWriter::new(vec![], sha2::Sha256::new());
+    let mut writer = CountingWriter::new(TeeWriter::new(sha, CountingWriter::new(io::sink())));
+    io::copy(&mut reader, &mut writer).unwrap();
+
+    assert_eq!(reader.lines_read(), 1000);
+    assert_eq!(
+        (writer.bytes_written(), writer.lines_written()),
+        (5000, 1000)
+    );
+    let (sha, sink) = writer.into_inner().into_inner();
+    assert_eq!(sink.bytes_written(), 5000);
+    let (copy, hash) = sha.finalize();
+    assert_eq!(copy, source.as_bytes());
+    assert_eq!(
+        hash,
+        <[u8; 32]>::from(sha2::Sha256::digest(source.as_bytes()))
+    );
+}
@@ -0,0 +1,160 @@
+// Reading and writing gzip or zstd compressed files as if they were plain ones
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use flate2::bufread::MultiGzDecoder;
+use flate2::write::GzEncoder;
+
+/// First bytes of any gzip stream.
+pub(crate) const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Plain,
+    Gzip,
+    Zstd,
+}
+
+impl Codec {
+    /// Recognise the codec from the magic bytes at the start of a stream.
+    pub fn sniff(head: &[u8]) -> Codec {
+        if head.starts_with(GZIP_MAGIC) {
+            Codec::Gzip
+        } else if head.starts_with(ZSTD_MAGIC) {
+            Codec::Zstd
+        } else {
+            Codec::Plain
+        }
+    }
+
+    /// Pick the codec from the extension: `.gz`, `.zst`, anything else is plain.
+    pub fn from_path(path: &Path) -> Codec {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Codec::Gzip,
+            Some("zst") => Codec::Zstd,
+            _ => Codec::Plain,
+        }
+    }
+}
+
+/// Wrap `reader` in the decoder its first bytes ask for, plain input is passed through.
+pub fn decompress<'a, R: BufRead + 'a>(mut reader: R) -> io::Result<Box<dyn BufRead + 'a>> {
+    Ok(match Codec::sniff(reader.fill_buf()?) {
+        Codec::Plain => Box::new(reader),
+        // several gzip members one after the other are one file for `gzip -d`, and for us
+        Codec::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Codec::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+    })
+}
+
+/// Open a file for reading, decompressing it on the fly whatever its name.
+pub fn open_reader(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    decompress(BufReader::new(File::open(path)?))
+}
+
+/// A writer compressing with the codec it was created with.
+/// `finish` must be called to write the end of the compressed stream and see any error doing so.
+pub enum CompressedWriter<W: Write> {
+    Plain(W),
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> CompressedWriter<W> {
+    pub fn new(writer: W, codec: Codec) -> io::Result<Self> {
+        Ok(match codec {
+            Codec::Plain => CompressedWriter::Plain(writer),
+            Codec::Gzip => {
+                CompressedWriter::Gzip(GzEncoder::new(writer, flate2::Compression::default()))
+            }
+            Codec::Zstd => CompressedWriter::Zstd(zstd::Encoder::new(writer, 0)?),
+        })
+    }
+
+    /// Write the end of the compressed stream and give the underlying writer back.
+    pub fn finish(self) -> io::Result<W> {
+        let mut writer = match self {
+            CompressedWriter::Plain(writer) => writer,
+            CompressedWriter::Gzip(encoder) => encoder.finish()?,
+            CompressedWriter::Zstd(encoder) => encoder.finish()?,
+        };
+        writer.flush()?;
+        Ok(writer)
+    }
+}
+
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            CompressedWriter::Plain(writer) => writer.write(buf),
+            CompressedWriter::Gzip(encoder) => encoder.write(buf),
+            CompressedWriter::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            CompressedWriter::Plain(writer) => writer.flush(),
+            CompressedWriter::Gzip(encoder) => encoder.flush(),
+            CompressedWriter::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Create a file compressed according to its extension (see `Codec::from_path`).
+pub fn create_writer(path: &Path) -> io::Result<CompressedWriter<BufWriter<File>>> {
+    CompressedWriter::new(BufWriter::new(File::create(path)?), Codec::from_path(path))
+}
+
+#[cfg(test)]
+fn compress(codec: Codec, data: &[u8]) -> Vec<u8> {
+    let mut writer = CompressedWriter::new(vec![], codec).unwrap();
+    writer.write_all(data).unwrap();
+    writer.finish().unwrap()
+}
+
+#[test]
+fn test_decompress_every_codec() {
+    let text = "first line\nsecond line\n".repeat(100);
+    for codec in [Codec::Plain, Codec::Gzip, Codec::Zstd] {
+        let compressed = compress(codec, text.as_bytes());
+        assert_eq!(Codec::sniff(&compressed), codec);
+        let lines: Vec<String> = decompress(&compressed[..])
+            .unwrap()
+            .lines()
+            .collect::<io::Result<Vec<String>>>()
+            .unwrap();
+        assert_eq!(lines.len(), 200, "{:?}", codec);
+        assert_eq!(lines[1], "second line");
+    }
+    assert!(compress(Codec::Zstd, text.as_bytes()).len() < text.len());
+}
+
+#[test]
+fn test_concatenated_gzip_members() {
+    let mut data = compress(Codec::Gzip, b"one\n");
+    data.extend(compress(Codec::Gzip, b"two\n"));
+    let mut text = String::new();
+    io::Read::read_to_string(&mut decompress(&data[..]).unwrap(), &mut text).unwrap();
+    assert_eq!(text, "one\ntwo\n");
+}
+
+#[test]
+fn test_files_by_extension() {
+    for name in ["lines.txt", "lines.txt.gz", "lines.txt.zst"] {
+        let path =
+            std::env::temp_dir().join(format!("readwriters_{}_{}", std::process::id(), name));
+        let mut writer = create_writer(&path).unwrap();
+        writeln!(writer, "hello {}", name).unwrap();
+        writer.finish().unwrap();
+
+        let on_disk = std::fs::read(&path).unwrap();
+        let mut line = String::new();
+        open_reader(&path).unwrap().read_line(&mut line).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(Codec::sniff(&on_disk), Codec::from_path(&path));
+        assert_eq!(line, format!("hello {}\n", name));
+    }
+}
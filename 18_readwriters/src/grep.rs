@@ -0,0 +1,367 @@
+// Searching readers and files for a pattern, one file at a time or in parallel
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use colored::*;
+use memmap2::Mmap;
+use rayon::prelude::*;
+
+use crate::compress::decompress;
+
+/// Files at least this large are memory-mapped instead of going through a `BufReader`.
+pub const MMAP_THRESHOLD: u64 = 1 << 20;
+
+/// How many bytes at the start of the input we look at to guess what it contains.
+const SNIFF_LEN: usize = 8 * 1024;
+
+/// What a reader contains, guessed from its first bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Content {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Binary,
+}
+
+impl Content {
+    /// Looks for a byte order mark, then for a NUL byte (like GNU grep does) to spot binary data.
+    /// Anything else is read as UTF-8, invalid sequences are replaced when the lines are decoded.
+    pub fn sniff(head: &[u8]) -> (Content, usize) {
+        let head = &head[..head.len().min(SNIFF_LEN)];
+        if head.starts_with(&[0xef, 0xbb, 0xbf]) {
+            (Content::Utf8, 3)
+        } else if head.starts_with(&[0xff, 0xfe]) {
+            (Content::Utf16Le, 2)
+        } else if head.starts_with(&[0xfe, 0xff]) {
+            (Content::Utf16Be, 2)
+        } else if head.contains(&0) {
+            (Content::Binary, 0)
+        } else {
+            (Content::Utf8, 0)
+        }
+    }
+}
+
+/// Like `BufRead::lines()`, but never fails on the content itself:
+/// invalid UTF-8 is replaced by U+FFFD (`String::from_utf8_lossy`), UTF-16 with a BOM is decoded,
+/// and both `\n` and `\r\n` end a line. Only real I/O errors are returned.
+pub struct TextLines<R> {
+    // The bytes read to sniff the content, put back in front of the rest
+    reader: io::Chain<io::Cursor<Vec<u8>>, R>,
+    content: Content,
+    buf: Vec<u8>,
+    // UTF-16 input is decoded in one go, the first time we are asked for a line
+    decoded: Option<std::vec::IntoIter<String>>,
+}
+
+impl<R: BufRead> TextLines<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        // One fill_buf may return only a few bytes, so read until we have enough to judge
+        let mut head = Vec::with_capacity(SNIFF_LEN);
+        (&mut reader)
+            .take(SNIFF_LEN as u64)
+            .read_to_end(&mut head)?;
+        let (content, bom_len) = Content::sniff(&head);
+        let mut head = io::Cursor::new(head);
+        head.set_position(bom_len as u64);
+        Ok(TextLines {
+            reader: head.chain(reader),
+            content,
+            buf: vec![],
+            decoded: None,
+        })
+    }
+
+    pub fn content(&self) -> Content {
+        self.content
+    }
+
+    fn decode_utf16(&mut self) -> io::Result<Vec<String>> {
+        let mut bytes = vec![];
+        self.reader.read_to_end(&mut bytes)?;
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| match self.content {
+                Content::Utf16Be => u16::from_be_bytes([pair[0], pair[1]]),
+                _ => u16::from_le_bytes([pair[0], pair[1]]),
+            })
+            .collect();
+        let mut text = String::from_utf16_lossy(&units);
+        if bytes.len() % 2 == 1 {
+            text.push(char::REPLACEMENT_CHARACTER);
+        }
+        Ok(text.lines().map(str::to_string).collect())
+    }
+}
+
+impl<R: BufRead> Iterator for TextLines<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<io::Result<String>> {
+        if let Content::Utf16Le | Content::Utf16Be = self.content {
+            if self.decoded.is_none() {
+                let lines = match self.decode_utf16() {
+                    Ok(lines) => lines,
+                    Err(e) => {
+                        self.decoded = Some(vec![].into_iter());
+                        return Some(Err(e));
+                    }
+                };
+                self.decoded = Some(lines.into_iter());
+            }
+            return self.decoded.as_mut()?.next().map(Ok);
+        }
+
+        self.buf.clear();
+        match self.reader.read_until(b'\n', &mut self.buf) {
+            Ok(0) => None,
+            Ok(_) => {
+                let line = self.buf.strip_suffix(b"\n").unwrap_or(&self.buf);
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                Some(Ok(String::from_utf8_lossy(line).into_owned()))
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// The lines of one reader that contain the target.
+/// For binary content we stop at the first match, only the fact that it matched is reported.
+#[derive(Debug, Default, PartialEq)]
+pub struct Matches {
+    pub lines: Vec<String>,
+    pub binary: bool,
+}
+
+/// The matches found in one file.
+#[derive(Debug)]
+pub struct FileMatches {
+    pub path: PathBuf,
+    pub matches: Matches,
+}
+
+/// Print every line of `reader` containing `target` as soon as it is found.
+pub fn grep<R>(target: &str, reader: R) -> io::Result<()>
+where
+    R: BufRead,
+{
+    let lines = TextLines::new(reader)?;
+    let binary = lines.content() == Content::Binary;
+    for line in lines {
+        let line = line?;
+        if line.contains(target) {
+            if binary {
+                println!("Binary file matches");
+                break;
+            }
+            println!("Found: {} in '{}'", &target, line);
+        }
+    }
+    Ok(())
+}
+
+/// Collect the lines of `reader` containing `target`.
+pub fn search<R>(target: &str, reader: R) -> io::Result<Matches>
+where
+    R: BufRead,
+{
+    let lines = TextLines::new(reader)?;
+    let binary = lines.content() == Content::Binary;
+    let mut found = vec![];
+    for line in lines {
+        let line = line?;
+        if line.contains(target) {
+            found.push(line);
+            if binary {
+                break;
+            }
+        }
+    }
+    Ok(Matches {
+        lines: found,
+        binary,
+    })
+}
+
+/// Search a single file, memory-mapping it when it is larger than `MMAP_THRESHOLD`.
+/// Compressed files are decompressed on the fly.
+pub fn search_file(target: &str, path: &Path) -> io::Result<FileMatches> {
+    let file = File::open(path)?;
+    let matches = if file.metadata()?.len() >= MMAP_THRESHOLD {
+        // Safety: the map is only read, and dropped before we return.
+        // Another process truncating the file while we search it is not something we guard against.
+        let map = unsafe { Mmap::map(&file)? };
+        // the decoder borrows `map`, it has to be dropped before the end of this block
+        let matches = search(target, decompress(&map[..])?)?;
+        matches
+    } else {
+        search(target, decompress(BufReader::new(file))?)?
+    };
+    Ok(FileMatches {
+        path: path.to_path_buf(),
+        matches,
+    })
+}
+
+/// Search the files one after the other.
+pub fn search_files(target: &str, files: &[PathBuf]) -> Vec<io::Result<FileMatches>> {
+    files.iter().map(|path| search_file(target, path)).collect()
+}
+
+/// Search the files on the rayon thread pool.
+/// The results come back in the same order as `files`, whatever order the searches finish in.
+pub fn search_files_parallel(target: &str, files: &[PathBuf]) -> Vec<io::Result<FileMatches>> {
+    files
+        .par_iter()
+        .map(|path| search_file(target, path))
+        .collect()
+}
+
+pub fn print_matches(target: &str, file_matches: &FileMatches) {
+    let path = file_matches.path.display();
+    let matches = &file_matches.matches;
+    if matches.binary {
+        if !matches.lines.is_empty() {
+            println!("Binary file {} matches", path);
+        }
+        return;
+    }
+    println!("{}", path.to_string().blue().bold());
+    for line in &matches.lines {
+        println!("Found: {} in '{}'", target, line);
+    }
+}
+
+#[cfg(test)]
+fn write_temp_file(name: &str, contents: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("readwriters_{}_{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn test_search() {
+    let text = "rust is fun\nc++ is fun\ntrust me\n";
+    assert_eq!(
+        search("rust", text.as_bytes()).unwrap().lines,
+        vec!["rust is fun", "trust me"]
+    );
+    assert!(search("java", text.as_bytes()).unwrap().lines.is_empty());
+}
+
+#[test]
+fn test_text_lines_like_lines() {
+    for text in ["", "\n", "a", "a\n", "a\r\nb\r\n", "a\n\nb", "\n\n"] {
+        let expected: Vec<String> = text.as_bytes().lines().map(Result::unwrap).collect();
+        let lines: Vec<String> = TextLines::new(text.as_bytes())
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(lines, expected, "{:?}", text);
+    }
+}
+
+#[test]
+fn test_text_lines_invalid_utf8() {
+    let data: &[u8] = b"good rust\nbad \x9f\xf0 rust\nlast\n";
+    // `lines()` gives up on the second line
+    assert!(data.lines().nth(1).unwrap().is_err());
+    let matches = search("rust", data).unwrap();
+    assert!(!matches.binary);
+    assert_eq!(
+        matches.lines,
+        vec!["good rust", "bad \u{fffd}\u{fffd} rust"]
+    );
+}
+
+#[test]
+fn test_text_lines_utf16_and_bom() {
+    let text = "héllo rust\r\nno match\nrust again";
+    let mut le = vec![0xff, 0xfe];
+    let mut be = vec![0xfe, 0xff];
+    for unit in text.encode_utf16() {
+        le.extend(unit.to_le_bytes());
+        be.extend(unit.to_be_bytes());
+    }
+    let expected = vec!["héllo rust", "rust again"];
+    assert_eq!(search("rust", &le[..]).unwrap().lines, expected);
+    assert_eq!(search("rust", &be[..]).unwrap().lines, expected);
+
+    let mut utf8_bom = vec![0xef, 0xbb, 0xbf];
+    utf8_bom.extend(text.as_bytes());
+    let lines: Vec<String> = TextLines::new(&utf8_bom[..])
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(lines, vec!["héllo rust", "no match", "rust again"]);
+}
+
+#[test]
+fn test_text_lines_sniff_across_reads() {
+    // each part of a chain comes out of its own fill_buf
+    let split_bom = (&[0xff][..]).chain(&b"\xfeh\0i\0"[..]);
+    let lines = TextLines::new(split_bom).unwrap();
+    assert_eq!(lines.content(), Content::Utf16Le);
+    assert_eq!(lines.map(Result::unwrap).collect::<Vec<_>>(), vec!["hi"]);
+
+    let late_nul = (&b"text\n"[..]).chain(&b"more\0\n"[..]);
+    let lines = TextLines::new(late_nul).unwrap();
+    assert_eq!(lines.content(), Content::Binary);
+    assert_eq!(lines.count(), 2);
+}
+
+#[test]
+fn test_search_binary() {
+    let data: &[u8] = b"\x7fELF\x00\x00rust\nrust\n";
+    let matches = search("rust", data).unwrap();
+    assert!(matches.binary);
+    assert_eq!(matches.lines.len(), 1);
+    assert!(search("java", data).unwrap().lines.is_empty());
+}
+
+#[test]
+fn test_search_large_file_is_mapped() {
+    let line = "needle in a haystack\n".repeat(64);
+    let filler = "hay\n".repeat(MMAP_THRESHOLD as usize / 4);
+    let path = write_temp_file("large.txt", format!("{}{}", filler, line).as_bytes());
+    let found = search_file("needle", &path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(found.matches.lines.len(), 64);
+}
+
+#[test]
+fn test_search_files_parallel_keeps_order() {
+    let files: Vec<PathBuf> = (0..16)
+        .map(|i| {
+            write_temp_file(
+                &format!("order_{}.txt", i),
+                format!("line {}\n", i).as_bytes(),
+            )
+        })
+        .collect();
+    let sequential = search_files("line", &files);
+    let parallel = search_files_parallel("line", &files);
+    for path in &files {
+        std::fs::remove_file(path).unwrap();
+    }
+    for (i, (s, p)) in sequential.iter().zip(&parallel).enumerate() {
+        let (s, p) = (s.as_ref().unwrap(), p.as_ref().unwrap());
+        assert_eq!(s.path, files[i]);
+        assert_eq!(p.path, files[i]);
+        assert_eq!(p.matches.lines, vec![format!("line {}", i)]);
+        assert_eq!(s.matches, p.matches);
+    }
+}
+
+#[test]
+fn test_search_compressed_file() {
+    use std::io::Write;
+    let path = write_temp_file("compressed.txt.gz", b"");
+    let mut writer = crate::compress::create_writer(&path).unwrap();
+    writer.write_all(b"rust\njava\ntrust\n").unwrap();
+    writer.finish().unwrap();
+    let found = search_file("rust", &path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(found.matches.lines, vec!["rust", "trust"]);
+}
@@ -1,60 +1,72 @@
+mod adapters;
+mod compress;
+mod grep;
+mod pipeline;
+mod record;
+mod save;
+
 // Getting traits for read/write
 use std::fs::File;
-use std::io::prelude::*;
-use std::io::{self, BufRead, BufReader};
-use std::path::PathBuf;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
 
 use colored::*;
+use sha2::{Digest, Sha256};
 use std::error::Error;
 
-use std::process::{Command, Stdio};
+use std::process::Command;
 
 // Binary files
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt};
 //
 // Serialization
 use serde::{Deserialize, Serialize};
-use serde_json;
 
 
 
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Player {
     name: String,
     items: Vec<String>,
     health: u64,
 }
 
-fn grep<R>(target: &str, reader: R) -> io::Result<()>
-where
-    R: BufRead,
-{
-    for line in reader.lines() {
-        let line = line?;
-        if line.contains(target) {
-            println!("Found: {} in '{}'", &target, line);
-        }
-    }
-    Ok(())
-}
-
 fn grep_main() -> Result<(), Box<dyn Error>> {
     // get command line and remove the first argument as it is the name of the program
-    let mut args = std::env::args().skip(1);
+    let mut args = std::env::args().skip(1).peekable();
+    // -p: search the files in parallel, the output is still in command-line order
+    let parallel = args
+        .next_if(|arg| arg == "-p" || arg == "--parallel")
+        .is_some();
     let target = match args.next() {
         Some(s) => s,
-        None => Err("usage: grep PATTERN FILE...")?,
+        None => Err("usage: grep [-p|--parallel] PATTERN FILE...")?,
     };
 
     let files: Vec<PathBuf> = args.map(PathBuf::from).collect();
     if files.is_empty() {
         let stdin = io::stdin();
-        grep(&target, stdin.lock())?;
+        grep::grep(&target, compress::decompress(stdin.lock())?)?;
     } else {
-        for file in files {
-            let f = File::open(file)?;
-            grep(&target, io::BufReader::new(f))?;
+        let results = if parallel {
+            grep::search_files_parallel(&target, &files)
+        } else {
+            grep::search_files(&target, &files)
+        };
+        // one unreadable file should not stop the search of the others
+        let mut failed = 0;
+        for (file, result) in files.iter().zip(results) {
+            match result {
+                Ok(matches) => grep::print_matches(&target, &matches),
+                Err(e) => {
+                    eprintln!("{}: {}", file.display(), e);
+                    failed += 1;
+                }
+            }
+        }
+        if failed > 0 {
+            Err(format!("{} file(s) could not be searched", failed))?;
         }
     }
 
@@ -69,9 +81,13 @@ fn main_fn() {
 }
 
 fn main() {
+    // with arguments we behave as grep: `readwriters [-p] PATTERN FILE...`
+    if std::env::args().len() > 1 {
+        main_fn();
+        return;
+    }
     {
         println!("{:=^49}", " STDIN ".green());
-        // main_fn()
         // let stdin = io::stdin();
         // grep("rust", stdin.lock()).unwrap();
     }
@@ -85,35 +101,109 @@ fn main() {
             lines.push(line);
         }
 
-        // better way
-        let reader = io::BufReader::new(File::open("Cargo.toml").unwrap());
+        // better way, and it would also read a Cargo.toml.gz or Cargo.toml.zst
+        let reader = compress::open_reader(Path::new("Cargo.toml")).unwrap();
         let lines: Vec<String> = reader.lines().collect::<io::Result<Vec<String>>>().unwrap();
         println!("{:?}", lines);
     }
     {
         println!("{:=^49}", " WRITER ".green());
+        // the extension picks the compression, reading it back does not need to know
+        let path = std::env::temp_dir().join("lines.txt.zst");
+        let mut writer = compress::create_writer(&path).unwrap();
+        for i in 0..1000 {
+            writeln!(writer, "line {}", i).unwrap();
+        }
+        writer.finish().unwrap();
+        let size = std::fs::metadata(&path).unwrap().len();
+        let lines = compress::open_reader(&path).unwrap().lines().count();
+        println!("{} lines in {} bytes of {}", lines, size, path.display());
+
+        // copy Cargo.toml to stdout (slowly) and to a SHA-256, counting on both ends
+        let file = File::open("Cargo.toml").unwrap();
+        let mut reader = adapters::CountingReader::new(adapters::ProgressReader::new(
+            io::BufReader::new(file),
+            |total| eprint!("\rread {} bytes", total),
+        ));
+        let sha = adapters::HashingWriter::new(vec![], Sha256::new());
+        let slow_stdout = adapters::RateLimitedWriter::new(io::stdout(), 2000);
+        let mut writer = adapters::CountingWriter::new(adapters::TeeWriter::new(sha, slow_stdout));
+        io::copy(&mut reader, &mut writer).unwrap();
+        eprintln!();
+        println!(
+            "read {} bytes / {} lines, wrote {} bytes / {} lines",
+            reader.bytes_read(),
+            reader.lines_read(),
+            writer.bytes_written(),
+            writer.lines_written()
+        );
+        let _file = reader.into_inner().into_inner();
+        let (sha, slow_stdout) = writer.into_inner().into_inner();
+        let _stdout = slow_stdout.into_inner();
+        let (copy, hash) = sha.finalize();
+        println!(
+            "sha256 of {} bytes: {}",
+            copy.len(),
+            adapters::to_hex(&hash)
+        );
     }
     {
         println!("{:=^49}", " COMMAND ".green());
-        let mut child = Command::new("grep")
-            .arg("-e")
-            .arg("a. *.u")
-            .stdin(Stdio::piped())
-            .spawn();
-
-        let mut to_child = child.as_mut().expect("Not Implented").stdin.take().unwrap();
+        let mut grep = Command::new("grep");
+        grep.arg("-e").arg("a. *.u");
         let my_words = vec!["a", "b", "c", "d", "e", "f", "g", "h", "i"];
-        for word in my_words {
-            writeln!(to_child, "{}", word);
+        let result = pipeline::Pipeline::new()
+            .pipe(grep)
+            .input(my_words.join("\n"))
+            .run();
+        // grep exits with 1 when no line matches
+        match result {
+            Ok(output) => println!("grep: {}", String::from_utf8_lossy(&output.stdout)),
+            Err(e) => println!("grep: {}", e),
         }
-        drop(to_child);
-        child.expect("Not implemented").wait();
+
+        let mut sort = Command::new("sort");
+        sort.arg("-r");
+        let output = pipeline::Pipeline::new()
+            .pipe(Command::new("cat"))
+            .pipe(sort)
+            .input(my_words.join("\n"))
+            .timeout(std::time::Duration::from_secs(5))
+            .run()
+            .unwrap();
+        println!(
+            "cat | sort -r: {:?}",
+            String::from_utf8_lossy(&output.stdout)
+        );
+        println!("stderr: {:?}", output.stderr);
     }
     {
         println!("{:=^49}", " BINARY - COMPRESSION ".green());
         let mut reader = io::BufReader::new(File::open("Cargo.toml").unwrap());
         let n = reader.read_u32::<LittleEndian>().unwrap();
         println!("n = {}", n);
+
+        // players as length-prefixed records, with an index to jump to any of them
+        let mut writer = record::RecordWriter::new(Vec::new(), record::Endian::Big).unwrap();
+        for (name, health) in [("John", 100), ("Jane", 80), ("Jack", 60)] {
+            let player = Player {
+                name: name.to_string(),
+                items: vec!["axe".to_string()],
+                health,
+            };
+            writer.push(&player).unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+        let mut records = record::RecordReader::open(io::Cursor::new(bytes)).unwrap();
+        if !records.is_empty() {
+            println!(
+                "{} records ({:?} endian), the last one: {:?}",
+                records.len(),
+                records.endian(),
+                records.get(records.len() - 1).unwrap()
+            );
+        }
+        println!("all: {:?}", records.read_all().unwrap());
     }
     {
         println!("{:=^49}", " JSON ".green());
@@ -124,6 +214,17 @@ fn main() {
         };
         println!("Player = {:?}", player);
         serde_json::to_writer(std::io::stdout(), &player).unwrap();
+        println!();
+
+        // save files, loaded back without telling which format they are in
+        for name in ["player.json", "player.bin", "player.json.gz"] {
+            let path = std::env::temp_dir().join(name);
+            let format = save::SaveFormat::from_path(&path).unwrap();
+            save::save_player(&path, &player, format).unwrap();
+            let size = std::fs::metadata(&path).unwrap().len();
+            let loaded = save::load_player(&path).unwrap();
+            println!("{:?} ({} bytes): {:?}", format, size, loaded);
+        }
     }
 
 }
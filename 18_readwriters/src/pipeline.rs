@@ -0,0 +1,319 @@
+// Running `cmd1 | cmd2 | cmd3` without a shell
+use std::io::{self, Read, Write};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum PipelineError {
+    #[error("the pipeline has no command")]
+    Empty,
+    #[error("could not start '{command}': {source}")]
+    Spawn { command: String, source: io::Error },
+    #[error("'{command}' failed with {status}: {stderr}")]
+    Failed {
+        command: String,
+        status: ExitStatus,
+        stderr: String,
+    },
+    #[error("the pipeline did not finish within {0:?}")]
+    Timeout(Duration),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// What the pipeline produced: the stdout of the last command and the stderr of every command.
+#[derive(Debug)]
+pub struct PipelineOutput {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<Vec<u8>>,
+}
+
+/// Commands connected stdout to stdin.
+/// The input is fed and every output drained on their own threads,
+/// so no child can block on a full pipe while we wait on another one.
+#[derive(Default)]
+pub struct Pipeline {
+    commands: Vec<Command>,
+    input: Option<Vec<u8>>,
+    timeout: Option<Duration>,
+}
+
+fn describe(command: &Command) -> String {
+    let mut text = command.get_program().to_string_lossy().into_owned();
+    for arg in command.get_args() {
+        text.push(' ');
+        text.push_str(&arg.to_string_lossy());
+    }
+    text
+}
+
+fn drain<R: Read + Send + 'static>(mut reader: R) -> JoinHandle<io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut buf = vec![];
+        reader.read_to_end(&mut buf)?;
+        Ok(buf)
+    })
+}
+
+fn join<T>(handle: JoinHandle<io::Result<T>>) -> io::Result<T> {
+    handle
+        .join()
+        .unwrap_or_else(|_| Err(io::Error::other("I/O thread panicked")))
+}
+
+fn kill_all(children: &mut [Child]) {
+    for child in children {
+        // the child may have exited already, that is fine
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Pipeline::default()
+    }
+
+    /// Append a command, its stdin is the stdout of the previous one.
+    pub fn pipe(mut self, command: Command) -> Self {
+        self.commands.push(command);
+        self
+    }
+
+    /// Bytes written to the stdin of the first command, it gets an empty stdin otherwise.
+    pub fn input(mut self, input: impl Into<Vec<u8>>) -> Self {
+        self.input = Some(input.into());
+        self
+    }
+
+    /// Kill every command still running after `timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Run the commands and wait for all of them.
+    /// Like `set -o pipefail`, the pipeline fails if any command fails,
+    /// and the error reports the last one that did, with its stderr.
+    pub fn run(mut self) -> Result<PipelineOutput, PipelineError> {
+        if self.commands.is_empty() {
+            return Err(PipelineError::Empty);
+        }
+        let names: Vec<String> = self.commands.iter().map(describe).collect();
+        let last = self.commands.len() - 1;
+
+        let mut children: Vec<Child> = vec![];
+        let mut stderr_threads = vec![];
+        let mut previous_stdout = None;
+        // each command is dropped once spawned: a Command keeps its Stdio, and holding on to the
+        // read end of a pipe would spare the writer its broken pipe when the reader exits
+        for (i, mut command) in std::mem::take(&mut self.commands).into_iter().enumerate() {
+            let stdin = match previous_stdout.take() {
+                Some(stdout) => Stdio::from(stdout),
+                None if self.input.is_some() => Stdio::piped(),
+                None => Stdio::null(),
+            };
+            let spawned = command
+                .stdin(stdin)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn();
+            let mut child = match spawned {
+                Ok(child) => child,
+                Err(source) => {
+                    kill_all(&mut children);
+                    return Err(PipelineError::Spawn {
+                        command: names[i].clone(),
+                        source,
+                    });
+                }
+            };
+            stderr_threads.push(drain(child.stderr.take().unwrap()));
+            if i < last {
+                previous_stdout = child.stdout.take();
+            }
+            children.push(child);
+        }
+
+        let feeder = match (self.input.take(), children[0].stdin.take()) {
+            (Some(input), Some(mut stdin)) => Some(thread::spawn(move || {
+                // dropping stdin at the end of the thread closes it, the command sees end of file
+                match stdin.write_all(&input) {
+                    // the command may exit without reading everything (e.g. `head`)
+                    Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+                    result => result,
+                }
+            })),
+            _ => None,
+        };
+        let stdout_thread = drain(children[last].stdout.take().unwrap());
+
+        let waited = match self.timeout {
+            None => children
+                .iter_mut()
+                .map(|child| child.wait())
+                .collect::<io::Result<Vec<ExitStatus>>>()
+                .map_err(PipelineError::from),
+            Some(timeout) => wait_with_timeout(&mut children, timeout),
+        };
+        let statuses = match waited {
+            Ok(statuses) => statuses,
+            Err(error) => {
+                kill_all(&mut children);
+                // the pipes close with the children, the threads end with them
+                if let Some(feeder) = feeder {
+                    let _ = feeder.join();
+                }
+                let _ = stdout_thread.join();
+                for thread in stderr_threads {
+                    let _ = thread.join();
+                }
+                return Err(error);
+            }
+        };
+
+        // all the children are gone, their pipes are closed and these threads can only end
+        if let Some(feeder) = feeder {
+            join(feeder)?;
+        }
+        let stdout = join(stdout_thread)?;
+        let stderr = stderr_threads
+            .into_iter()
+            .map(join)
+            .collect::<io::Result<Vec<Vec<u8>>>>()?;
+
+        if let Some(i) = statuses.iter().rposition(|status| !status.success()) {
+            return Err(PipelineError::Failed {
+                command: names[i].clone(),
+                status: statuses[i],
+                stderr: String::from_utf8_lossy(&stderr[i]).trim_end().to_string(),
+            });
+        }
+        Ok(PipelineOutput { stdout, stderr })
+    }
+}
+
+fn wait_with_timeout(
+    children: &mut [Child],
+    timeout: Duration,
+) -> Result<Vec<ExitStatus>, PipelineError> {
+    let deadline = Instant::now() + timeout;
+    let mut statuses = vec![None; children.len()];
+    loop {
+        for (child, status) in children.iter_mut().zip(statuses.iter_mut()) {
+            if status.is_none() {
+                *status = child.try_wait()?;
+            }
+        }
+        if statuses.iter().all(Option::is_some) {
+            return Ok(statuses.into_iter().flatten().collect());
+        }
+        if Instant::now() >= deadline {
+            kill_all(children);
+            return Err(PipelineError::Timeout(timeout));
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[cfg(test)]
+fn command(program: &str, args: &[&str]) -> Command {
+    let mut command = Command::new(program);
+    command.args(args);
+    command
+}
+
+#[test]
+fn test_pipeline() {
+    let output = Pipeline::new()
+        .pipe(command("cat", &[]))
+        .pipe(command("sort", &[]))
+        .pipe(command("grep", &["a"]))
+        .input("banana\ncherry\napple\n")
+        .run()
+        .unwrap();
+    assert_eq!(output.stdout, b"apple\nbanana\n");
+    assert_eq!(output.stderr.len(), 3);
+}
+
+#[test]
+fn test_pipeline_large_input_does_not_deadlock() {
+    // much more than a pipe buffer, in and out
+    let input = "line of text\n".repeat(100_000);
+    let output = Pipeline::new()
+        .pipe(command("cat", &[]))
+        .pipe(command("cat", &[]))
+        .input(input.clone())
+        .timeout(Duration::from_secs(30))
+        .run()
+        .unwrap();
+    assert_eq!(output.stdout, input.as_bytes());
+}
+
+#[test]
+fn test_pipeline_failure_has_stderr() {
+    let error = Pipeline::new()
+        .pipe(command("cat", &["/no/such/file"]))
+        .pipe(command("sort", &[]))
+        .run()
+        .unwrap_err();
+    match error {
+        PipelineError::Failed {
+            command, stderr, ..
+        } => {
+            assert_eq!(command, "cat /no/such/file");
+            assert!(stderr.contains("/no/such/file"), "{}", stderr);
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+    // grep exits with 1 when nothing matches
+    let error = Pipeline::new()
+        .pipe(command("grep", &["x"]))
+        .input("a\n")
+        .run()
+        .unwrap_err();
+    assert!(matches!(error, PipelineError::Failed { .. }));
+}
+
+#[test]
+fn test_pipeline_spawn_error_and_empty() {
+    let error = Pipeline::new()
+        .pipe(command("cat", &[]))
+        .pipe(command("no-such-program-here", &[]))
+        .run()
+        .unwrap_err();
+    assert!(matches!(error, PipelineError::Spawn { .. }));
+    assert!(matches!(Pipeline::new().run(), Err(PipelineError::Empty)));
+}
+
+#[test]
+fn test_pipeline_timeout() {
+    let start = Instant::now();
+    let error = Pipeline::new()
+        .pipe(command("sleep", &["10"]))
+        .timeout(Duration::from_millis(100))
+        .run()
+        .unwrap_err();
+    assert!(matches!(error, PipelineError::Timeout(_)));
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn test_pipeline_writer_outlives_reader() {
+    // head exits after one line, yes only stops on the broken pipe
+    let start = Instant::now();
+    let result = Pipeline::new()
+        .pipe(command("yes", &[]))
+        .pipe(command("head", &["-n1"]))
+        .timeout(Duration::from_secs(10))
+        .run();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    match result {
+        // killed by SIGPIPE, which pipefail counts as a failure
+        Err(PipelineError::Failed { command, .. }) => assert_eq!(command, "yes"),
+        other => panic!("unexpected result: {:?}", other.map(|output| output.stdout)),
+    }
+}
@@ -0,0 +1,396 @@
+// A small binary record file for players, written by hand with byteorder
+//
+// header:  b"PREC" | version: u8 | endian: u8 (0 little, 1 big)
+// records: length: u32 | payload | crc32 of the payload: u32
+// index:   count: u32 | offset of each record from the start of the file: u64...
+// trailer: offset of the index: u64 | b"PIDX"
+//
+// Every number after the header is written in the endianness named by the header.
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::save::invalid_data;
+use crate::Player;
+
+pub const RECORD_MAGIC: &[u8; 4] = b"PREC";
+pub const INDEX_MAGIC: &[u8; 4] = b"PIDX";
+pub const RECORD_VERSION: u8 = 1;
+
+const HEADER_LEN: u64 = 6;
+const TRAILER_LEN: u64 = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+// byteorder picks the order with a type parameter, we pick it when the file is opened
+impl Endian {
+    fn write_u16<W: Write>(self, writer: &mut W, n: u16) -> io::Result<()> {
+        match self {
+            Endian::Little => writer.write_u16::<LittleEndian>(n),
+            Endian::Big => writer.write_u16::<BigEndian>(n),
+        }
+    }
+
+    fn write_u32<W: Write>(self, writer: &mut W, n: u32) -> io::Result<()> {
+        match self {
+            Endian::Little => writer.write_u32::<LittleEndian>(n),
+            Endian::Big => writer.write_u32::<BigEndian>(n),
+        }
+    }
+
+    fn write_u64<W: Write>(self, writer: &mut W, n: u64) -> io::Result<()> {
+        match self {
+            Endian::Little => writer.write_u64::<LittleEndian>(n),
+            Endian::Big => writer.write_u64::<BigEndian>(n),
+        }
+    }
+
+    fn read_u16<R: Read>(self, reader: &mut R) -> io::Result<u16> {
+        match self {
+            Endian::Little => reader.read_u16::<LittleEndian>(),
+            Endian::Big => reader.read_u16::<BigEndian>(),
+        }
+    }
+
+    fn read_u32<R: Read>(self, reader: &mut R) -> io::Result<u32> {
+        match self {
+            Endian::Little => reader.read_u32::<LittleEndian>(),
+            Endian::Big => reader.read_u32::<BigEndian>(),
+        }
+    }
+
+    fn read_u64<R: Read>(self, reader: &mut R) -> io::Result<u64> {
+        match self {
+            Endian::Little => reader.read_u64::<LittleEndian>(),
+            Endian::Big => reader.read_u64::<BigEndian>(),
+        }
+    }
+}
+
+fn write_str(endian: Endian, buf: &mut Vec<u8>, s: &str) -> io::Result<()> {
+    let len = u16::try_from(s.len()).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("string of {} bytes does not fit in a record", s.len()),
+        )
+    })?;
+    endian.write_u16(buf, len)?;
+    buf.write_all(s.as_bytes())
+}
+
+fn read_str(endian: Endian, reader: &mut &[u8]) -> io::Result<String> {
+    let mut bytes = vec![0; endian.read_u16(reader)? as usize];
+    reader.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(invalid_data)
+}
+
+/// name | item count: u16 | items | health: u64, strings are a u16 length then UTF-8 bytes
+fn encode_player(endian: Endian, player: &Player) -> io::Result<Vec<u8>> {
+    let mut buf = vec![];
+    write_str(endian, &mut buf, &player.name)?;
+    let count = u16::try_from(player.items.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many items"))?;
+    endian.write_u16(&mut buf, count)?;
+    for item in &player.items {
+        write_str(endian, &mut buf, item)?;
+    }
+    endian.write_u64(&mut buf, player.health)?;
+    Ok(buf)
+}
+
+fn decode_player(endian: Endian, mut payload: &[u8]) -> io::Result<Player> {
+    let reader = &mut payload;
+    let name = read_str(endian, reader)?;
+    let count = endian.read_u16(reader)?;
+    let items = (0..count)
+        .map(|_| read_str(endian, reader))
+        .collect::<io::Result<Vec<String>>>()?;
+    let health = endian.read_u64(reader)?;
+    if !payload.is_empty() {
+        return Err(invalid_data("trailing bytes after player record"));
+    }
+    Ok(Player {
+        name,
+        items,
+        health,
+    })
+}
+
+/// Writes the header on creation, the records one by one, and the index on `finish`.
+/// Without `finish` the file cannot be opened by `RecordReader`.
+pub struct RecordWriter<W: Write> {
+    writer: W,
+    endian: Endian,
+    offset: u64,
+    index: Vec<u64>,
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub fn new(mut writer: W, endian: Endian) -> io::Result<Self> {
+        writer.write_all(RECORD_MAGIC)?;
+        writer.write_u8(RECORD_VERSION)?;
+        writer.write_u8(match endian {
+            Endian::Little => 0,
+            Endian::Big => 1,
+        })?;
+        Ok(RecordWriter {
+            writer,
+            endian,
+            offset: HEADER_LEN,
+            index: vec![],
+        })
+    }
+
+    pub fn push(&mut self, player: &Player) -> io::Result<()> {
+        let payload = encode_player(self.endian, player)?;
+        self.endian
+            .write_u32(&mut self.writer, payload.len() as u32)?;
+        self.writer.write_all(&payload)?;
+        self.endian
+            .write_u32(&mut self.writer, crc32fast::hash(&payload))?;
+        self.index.push(self.offset);
+        self.offset += 4 + payload.len() as u64 + 4;
+        Ok(())
+    }
+
+    /// Write the index and give the underlying writer back.
+    pub fn finish(mut self) -> io::Result<W> {
+        let endian = self.endian;
+        endian.write_u32(&mut self.writer, self.index.len() as u32)?;
+        for &offset in &self.index {
+            endian.write_u64(&mut self.writer, offset)?;
+        }
+        endian.write_u64(&mut self.writer, self.offset)?;
+        self.writer.write_all(INDEX_MAGIC)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Reads the header and the index when opened, then any record on demand.
+pub struct RecordReader<R: Read + Seek> {
+    reader: R,
+    endian: Endian,
+    index: Vec<u64>,
+    // Where the records stop and the index starts
+    index_offset: u64,
+}
+
+impl<R: Read + Seek> RecordReader<R> {
+    pub fn open(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != RECORD_MAGIC {
+            return Err(invalid_data("not a player record file"));
+        }
+        let version = reader.read_u8()?;
+        if version != RECORD_VERSION {
+            return Err(invalid_data(format!(
+                "record file version {} is not supported",
+                version
+            )));
+        }
+        let endian = match reader.read_u8()? {
+            0 => Endian::Little,
+            1 => Endian::Big,
+            other => return Err(invalid_data(format!("unknown endianness {}", other))),
+        };
+
+        let len = reader.seek(SeekFrom::End(0))?;
+        if len < HEADER_LEN + TRAILER_LEN {
+            return Err(invalid_data("record file has no index"));
+        }
+        reader.seek(SeekFrom::Start(len - TRAILER_LEN))?;
+        let index_offset = endian.read_u64(&mut reader)?;
+        reader.read_exact(&mut magic)?;
+        if &magic != INDEX_MAGIC || index_offset > len - TRAILER_LEN {
+            return Err(invalid_data("record file index is missing or damaged"));
+        }
+        reader.seek(SeekFrom::Start(index_offset))?;
+        let count = endian.read_u32(&mut reader)?;
+        // The count and every offset come from the file, so check them against its length
+        // before allocating: a record needs at least its length and CRC before the index
+        if index_offset < HEADER_LEN || index_offset + 4 + count as u64 * 8 > len - TRAILER_LEN {
+            return Err(invalid_data("record file index does not fit in the file"));
+        }
+        let index = (0..count)
+            .map(|_| {
+                let offset = endian.read_u64(&mut reader)?;
+                // room for at least a length and a CRC between the record and the index
+                match index_offset.checked_sub(offset) {
+                    Some(room) if offset >= HEADER_LEN && room >= 8 => Ok(offset),
+                    _ => Err(invalid_data(format!(
+                        "record offset {} is outside the file",
+                        offset
+                    ))),
+                }
+            })
+            .collect::<io::Result<Vec<u64>>>()?;
+
+        Ok(RecordReader {
+            reader,
+            endian,
+            index,
+            index_offset,
+        })
+    }
+
+    pub fn endian(&self) -> Endian {
+        self.endian
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Seek straight to the `n`th record and check its CRC.
+    pub fn get(&mut self, n: usize) -> io::Result<Player> {
+        let offset = *self.index.get(n).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no record {} in a file of {}", n, self.index.len()),
+            )
+        })?;
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let len = self.endian.read_u32(&mut self.reader)?;
+        if len as u64 > self.index_offset - offset - 8 {
+            return Err(invalid_data(format!(
+                "record {} claims {} bytes, more than the file holds",
+                n, len
+            )));
+        }
+        let mut payload = vec![0; len as usize];
+        self.reader.read_exact(&mut payload)?;
+        let crc = self.endian.read_u32(&mut self.reader)?;
+        if crc != crc32fast::hash(&payload) {
+            return Err(invalid_data(format!("record {} is corrupted (bad CRC)", n)));
+        }
+        decode_player(self.endian, &payload)
+    }
+
+    pub fn read_all(&mut self) -> io::Result<Vec<Player>> {
+        (0..self.len()).map(|n| self.get(n)).collect()
+    }
+}
+
+#[cfg(test)]
+fn players() -> Vec<Player> {
+    (0..5)
+        .map(|i| Player {
+            name: format!("Player {}", i),
+            items: (0..i).map(|j| format!("item {}", j)).collect(),
+            health: 100 * i as u64,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn write_records(endian: Endian) -> Vec<u8> {
+    let mut writer = RecordWriter::new(vec![], endian).unwrap();
+    for player in players() {
+        writer.push(&player).unwrap();
+    }
+    writer.finish().unwrap()
+}
+
+#[test]
+fn test_round_trip_both_endians() {
+    for endian in [Endian::Little, Endian::Big] {
+        let mut reader = RecordReader::open(io::Cursor::new(write_records(endian))).unwrap();
+        assert_eq!(reader.endian(), endian);
+        assert_eq!(reader.len(), 5);
+        assert_eq!(reader.read_all().unwrap(), players());
+    }
+    assert_ne!(write_records(Endian::Little), write_records(Endian::Big));
+}
+
+#[test]
+fn test_seek_to_nth_record() {
+    let mut reader = RecordReader::open(io::Cursor::new(write_records(Endian::Big))).unwrap();
+    assert_eq!(reader.get(3).unwrap(), players()[3]);
+    assert_eq!(reader.get(1).unwrap(), players()[1]);
+    assert_eq!(reader.get(5).unwrap_err().kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_empty_file() {
+    let bytes = RecordWriter::new(vec![], Endian::Little)
+        .unwrap()
+        .finish()
+        .unwrap();
+    let reader = RecordReader::open(io::Cursor::new(bytes)).unwrap();
+    assert!(reader.is_empty());
+}
+
+#[test]
+fn test_corruption_is_detected() {
+    let mut bytes = write_records(Endian::Little);
+    // first byte of the name of the first record, right after the header and length
+    bytes[HEADER_LEN as usize + 4 + 2] ^= 0xff;
+    let mut reader = RecordReader::open(io::Cursor::new(bytes.clone())).unwrap();
+    assert_eq!(
+        reader.get(0).unwrap_err().kind(),
+        io::ErrorKind::InvalidData
+    );
+    assert_eq!(reader.get(1).unwrap(), players()[1]);
+
+    bytes[0] = b'X';
+    assert!(RecordReader::open(io::Cursor::new(bytes)).is_err());
+    let truncated = write_records(Endian::Little)[..20].to_vec();
+    assert!(RecordReader::open(io::Cursor::new(truncated)).is_err());
+}
+
+#[test]
+fn test_corrupted_lengths_are_rejected() {
+    let bytes = write_records(Endian::Little);
+    let trailer = bytes.len() - TRAILER_LEN as usize;
+    let index_offset = u64::from_le_bytes(bytes[trailer..trailer + 8].try_into().unwrap()) as usize;
+
+    // a record length of 4 GB must not be allocated
+    let mut huge_record = bytes.clone();
+    huge_record[HEADER_LEN as usize..HEADER_LEN as usize + 4]
+        .copy_from_slice(&u32::MAX.to_le_bytes());
+    let mut reader = RecordReader::open(io::Cursor::new(huge_record)).unwrap();
+    assert_eq!(
+        reader.get(0).unwrap_err().kind(),
+        io::ErrorKind::InvalidData
+    );
+    assert_eq!(reader.get(1).unwrap(), players()[1]);
+
+    // nor an index of four billion offsets
+    let mut huge_count = bytes.clone();
+    huge_count[index_offset..index_offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    let error = RecordReader::open(io::Cursor::new(huge_count))
+        .err()
+        .unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+    // and an offset past the records is refused up front
+    let mut bad_offset = bytes;
+    bad_offset[index_offset + 4..index_offset + 12].copy_from_slice(&u64::MAX.to_le_bytes());
+    let error = RecordReader::open(io::Cursor::new(bad_offset))
+        .err()
+        .unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_index_right_after_the_header() {
+    // one record at offset 6, where the index itself starts: no room for it
+    let mut bytes = b"PREC\x01\x00".to_vec();
+    bytes.extend(1u32.to_le_bytes());
+    bytes.extend(6u64.to_le_bytes());
+    bytes.extend(6u64.to_le_bytes());
+    bytes.extend(b"PIDX");
+    assert_eq!(bytes.len(), 30);
+    let error = RecordReader::open(io::Cursor::new(bytes)).err().unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,246 @@
+// Saving players to disk and loading them back, whatever format and version they were saved in
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::compress;
+use crate::Player;
+
+/// Version written in every new save file.
+///
+/// * 1: the bare `Player` JSON object written by the JSON demo, there is no header at all.
+/// * 2: JSON is wrapped in `{ "version": 2, "player": {..} }`,
+///   binary files start with `BINARY_MAGIC` and a little-endian `u16` version.
+pub const SAVE_VERSION: u16 = 2;
+
+/// First bytes of a binary save file.
+pub const BINARY_MAGIC: &[u8; 4] = b"PLYR";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveFormat {
+    /// Pretty-printed JSON, for humans and diffs
+    Json,
+    /// `bincode`, the smallest and fastest to load
+    Binary,
+    /// Compact JSON compressed with gzip
+    GzipJson,
+}
+
+impl SaveFormat {
+    /// Pick the format from the file name: `.json`, `.bin` or `.json.gz`.
+    pub fn from_path(path: &Path) -> Option<SaveFormat> {
+        let name = path.file_name()?.to_str()?;
+        if name.ends_with(".json.gz") {
+            Some(SaveFormat::GzipJson)
+        } else if name.ends_with(".json") {
+            Some(SaveFormat::Json)
+        } else if name.ends_with(".bin") {
+            Some(SaveFormat::Binary)
+        } else {
+            None
+        }
+    }
+
+    /// Recognise the format from the first bytes of a save file.
+    pub fn sniff(head: &[u8]) -> SaveFormat {
+        if head.starts_with(BINARY_MAGIC) {
+            SaveFormat::Binary
+        } else if head.starts_with(compress::GZIP_MAGIC) {
+            SaveFormat::GzipJson
+        } else {
+            SaveFormat::Json
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SaveFileRef<'a> {
+    version: u16,
+    player: &'a Player,
+}
+
+#[derive(Deserialize)]
+struct SaveFile {
+    version: u16,
+    player: Value,
+}
+
+pub(crate) fn invalid_data<E>(error: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+fn unsupported_version(version: u16) -> io::Error {
+    invalid_data(format!(
+        "save file version {} is not supported (this build reads up to {})",
+        version, SAVE_VERSION
+    ))
+}
+
+pub fn write_player<W: Write>(
+    mut writer: W,
+    player: &Player,
+    format: SaveFormat,
+) -> io::Result<()> {
+    let save = SaveFileRef {
+        version: SAVE_VERSION,
+        player,
+    };
+    match format {
+        SaveFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, &save)?;
+            writeln!(writer)?;
+        }
+        SaveFormat::Binary => {
+            writer.write_all(BINARY_MAGIC)?;
+            writer.write_u16::<LittleEndian>(SAVE_VERSION)?;
+            bincode::serialize_into(&mut writer, player).map_err(invalid_data)?;
+        }
+        SaveFormat::GzipJson => {
+            let mut encoder = GzEncoder::new(&mut writer, Compression::default());
+            serde_json::to_writer(&mut encoder, &save)?;
+            encoder.finish()?;
+        }
+    }
+    writer.flush()
+}
+
+pub fn read_player<R: Read>(mut reader: R, format: SaveFormat) -> io::Result<Player> {
+    match format {
+        SaveFormat::Json => read_json(reader),
+        SaveFormat::GzipJson => read_json(GzDecoder::new(reader)),
+        SaveFormat::Binary => {
+            let mut magic = [0; 4];
+            reader.read_exact(&mut magic)?;
+            if &magic != BINARY_MAGIC {
+                return Err(invalid_data("not a binary player save file"));
+            }
+            match reader.read_u16::<LittleEndian>()? {
+                SAVE_VERSION => bincode::deserialize_from(reader).map_err(invalid_data),
+                version => Err(unsupported_version(version)),
+            }
+        }
+    }
+}
+
+fn read_json<R: Read>(reader: R) -> io::Result<Player> {
+    let value: Value = serde_json::from_reader(reader)?;
+    let (version, player) = if value.get("version").is_some() {
+        let save: SaveFile = serde_json::from_value(value)?;
+        (save.version, save.player)
+    } else {
+        (1, value)
+    };
+    migrate(version, player)
+}
+
+/// Bring the `player` object of a `version` save file up to the current `Player`.
+fn migrate(version: u16, player: Value) -> io::Result<Player> {
+    match version {
+        // only the envelope changed between 1 and 2
+        1 | SAVE_VERSION => Ok(serde_json::from_value(player)?),
+        version => Err(unsupported_version(version)),
+    }
+}
+
+pub fn save_player(path: &Path, player: &Player, format: SaveFormat) -> io::Result<()> {
+    write_player(BufWriter::new(File::create(path)?), player, format)
+}
+
+/// Load a player, the format is recognised from the content, not from the file name.
+/// Any save file can also be compressed afterwards, e.g. `zstd player.bin`.
+pub fn load_player(path: &Path) -> io::Result<Player> {
+    let mut reader = compress::open_reader(path)?;
+    let format = SaveFormat::sniff(reader.fill_buf()?);
+    read_player(reader, format)
+}
+
+#[cfg(test)]
+fn john() -> Player {
+    Player {
+        name: "John".to_string(),
+        items: vec!["axe".to_string(), "sword".to_string()],
+        health: 100,
+    }
+}
+
+#[test]
+fn test_round_trip_every_format() {
+    for format in [SaveFormat::Json, SaveFormat::Binary, SaveFormat::GzipJson] {
+        let mut buf = vec![];
+        write_player(&mut buf, &john(), format).unwrap();
+        assert_eq!(SaveFormat::sniff(&buf), format);
+        assert_eq!(
+            read_player(&buf[..], format).unwrap(),
+            john(),
+            "{:?}",
+            format
+        );
+    }
+}
+
+#[test]
+fn test_save_and_load_files() {
+    let dir = std::env::temp_dir();
+    for name in ["player.json", "player.bin", "player.json.gz"] {
+        let path = dir.join(format!("readwriters_{}_{}", std::process::id(), name));
+        let format = SaveFormat::from_path(&path).unwrap();
+        save_player(&path, &john(), format).unwrap();
+        let player = load_player(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(player, john(), "{}", name);
+    }
+    assert_eq!(SaveFormat::from_path(Path::new("player.txt")), None);
+}
+
+#[test]
+fn test_json_is_pretty_and_versioned() {
+    let mut buf = vec![];
+    write_player(&mut buf, &john(), SaveFormat::Json).unwrap();
+    let text = String::from_utf8(buf).unwrap();
+    assert!(text.starts_with("{\n  \"version\": 2,\n  \"player\": {"));
+}
+
+#[test]
+fn test_load_version_1() {
+    // what `serde_json::to_writer(stdout, &player)` used to produce
+    let legacy = r#"{"name":"John","items":["axe","sword"],"health":100}"#;
+    assert_eq!(
+        read_player(legacy.as_bytes(), SaveFormat::Json).unwrap(),
+        john()
+    );
+}
+
+#[test]
+fn test_unsupported_versions() {
+    let future = r#"{"version":99,"player":{}}"#;
+    let error = read_player(future.as_bytes(), SaveFormat::Json).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+    let mut buf = vec![];
+    write_player(&mut buf, &john(), SaveFormat::Binary).unwrap();
+    buf[4] = 99;
+    assert!(read_player(&buf[..], SaveFormat::Binary).is_err());
+    assert!(read_player(&b"NOPE"[..], SaveFormat::Binary).is_err());
+}
+
+#[test]
+fn test_load_compressed_after_the_fact() {
+    let path =
+        std::env::temp_dir().join(format!("readwriters_{}_player.bin.zst", std::process::id()));
+    let mut writer = compress::create_writer(&path).unwrap();
+    write_player(&mut writer, &john(), SaveFormat::Binary).unwrap();
+    writer.finish().unwrap();
+    let player = load_player(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(player, john());
+}